@@ -0,0 +1,46 @@
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, spanned::Spanned, Data, DeriveInput, Error, Fields, Index};
+
+/// Implement `async_pool::Reusable` for a struct, resetting each of its fields to its `Default`
+///
+/// Each field's type must implement `Default`. Fields are assigned rather than cleared, so a reset
+/// drops whatever they held, buffers included.
+#[proc_macro_derive(Reusable)]
+pub fn derive_reusable(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    let fields = match &input.data {
+        Data::Struct(data) => &data.fields,
+        _ => return Error::new(input.span(), "Reusable can only be derived for structs").to_compile_error().into(),
+    };
+
+    let resets = match fields {
+        Fields::Named(fields) => fields
+            .named
+            .iter()
+            .map(|field| {
+                let name = &field.ident;
+                quote!(self.#name = ::core::default::Default::default();)
+            })
+            .collect::<Vec<_>>(),
+        Fields::Unnamed(fields) => (0..fields.unnamed.len())
+            .map(|i| {
+                let index = Index::from(i);
+                quote!(self.#index = ::core::default::Default::default();)
+            })
+            .collect(),
+        Fields::Unit => Vec::new(),
+    };
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    TokenStream::from(quote! {
+        impl #impl_generics ::async_pool::Reusable for #name #ty_generics #where_clause {
+            fn reset(&mut self) {
+                #(#resets)*
+            }
+        }
+    })
+}
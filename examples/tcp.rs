@@ -0,0 +1,55 @@
+//! Pool connections to a local echo server, reconnecting whenever the server drops one
+//!
+//! Run with `cargo run --example tcp --features net`.
+
+use std::time::Duration;
+
+use async_pool::{net::TcpManager, ManagedAsyncPool};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+    runtime::Builder,
+};
+
+fn main() -> std::io::Result<()> {
+    Builder::new_current_thread().enable_all().build()?.block_on(async {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+
+        // Echo one message per connection, then hang up
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let mut buf = [0; 64];
+                    if let Ok(read) = stream.read(&mut buf).await {
+                        let _ = stream.write_all(&buf[..read]).await;
+                    }
+                });
+            }
+        });
+
+        let manager = TcpManager::from_addr(addr)
+            .with_connect_timeout(Duration::from_secs(1))
+            .with_nodelay(true);
+        let pool = ManagedAsyncPool::new(manager).with_max_size(2);
+
+        for i in 0..4 {
+            let mut conn = match pool.rsvp().await {
+                Ok(conn) => conn,
+                Err(e) => panic!("Couldn't connect: {}", e),
+            };
+
+            let message = format!("hello #{}", i);
+            conn.write_all(message.as_bytes()).await?;
+
+            let mut buf = [0; 64];
+            let read = conn.read(&mut buf).await?;
+            println!("{} -> {}", conn.local_addr()?, String::from_utf8_lossy(&buf[..read]));
+
+            // Give the server time to hang up, so the pool redials next time
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        Ok(())
+    })
+}
@@ -0,0 +1,202 @@
+use std::{
+    cell::UnsafeCell,
+    future::Future,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+    pin::Pin,
+    sync::{Mutex, MutexGuard, PoisonError},
+    task::{Context, Poll},
+};
+
+use crate::waiters::Waiters;
+
+/// A pool of exactly `N` resources, stored inline, that doesn't allocate once it's created
+///
+/// Guards borrow the pool rather than sharing ownership of it, and reservations are plain futures,
+/// so nothing is boxed or queued on the heap to hand out or return a resource. The only allocation is
+/// the waiter queue, made up front with room for `N` waiters; more than that waiting at once grow it.
+///
+/// It still needs the `std` feature, as its state is behind a `std::sync::Mutex`. Without `std`, use
+/// `LocalAsyncPool`.
+pub struct ArrayPool<T, const N: usize> {
+    slots: [UnsafeCell<T>; N],
+    state: Mutex<ArrayState<N>>,
+}
+
+/// The guard on a resource from an `ArrayPool`. Returns it to the pool on drop.
+///
+/// Like `std::sync::MutexGuard`, it can only be shared between threads if the resource can be:
+///
+/// ```compile_fail
+/// use std::cell::Cell;
+///
+/// fn assert_sync<T: Sync>() {}
+/// assert_sync::<async_pool::ArrayPoolGuard<'static, Cell<u8>, 1>>();
+/// ```
+pub struct ArrayPoolGuard<'a, T, const N: usize> {
+    pool: &'a ArrayPool<T, N>,
+    index: usize,
+    /// Opts out of the auto traits, which would only ask that the pool be `Sync`.
+    _resource: PhantomData<*mut T>,
+}
+
+/// The future of `ArrayPool::rsvp`
+pub struct ArrayRsvp<'a, T, const N: usize> {
+    pool: &'a ArrayPool<T, N>,
+    /// This waiter's place in the queue, once it's waiting.
+    ticket: Option<u64>,
+}
+
+struct ArrayState<const N: usize> {
+    /// The indices of the idle slots, the last being the next handed out.
+    free: [usize; N],
+    /// How many of `free` are in use.
+    free_len: usize,
+    /// The waiting `rsvp`s.
+    waiters: Waiters,
+}
+
+// SAFETY: Each slot is only ever reached through the one guard that holds its index, which the
+// lock hands out to a single caller at a time, so resources are only sent between threads.
+unsafe impl<T: Send, const N: usize> Sync for ArrayPool<T, N> {}
+
+// SAFETY: Sending the guard sends the resource, and it's returned through the pool's lock.
+unsafe impl<T: Send, const N: usize> Send for ArrayPoolGuard<'_, T, N> {}
+
+// SAFETY: A shared guard only gives out `&T`, like a shared `&T` would.
+unsafe impl<T: Sync, const N: usize> Sync for ArrayPoolGuard<'_, T, N> {}
+
+impl<T, const N: usize> ArrayPool<T, N> {
+    /// Create a pool of the given resources
+    pub fn new(resources: [T; N]) -> Self {
+        Self {
+            slots: resources.map(UnsafeCell::new),
+            state: Mutex::new(ArrayState {
+                free: std::array::from_fn(|index| index),
+                free_len: N,
+                waiters: Waiters::with_capacity(N),
+            }),
+        }
+    }
+
+    /// Await the next available resource
+    pub fn rsvp(&self) -> ArrayRsvp<'_, T, N> {
+        ArrayRsvp { pool: self, ticket: None }
+    }
+
+    /// Reserve a resource only if one is idle right now, without awaiting
+    pub fn try_rsvp(&self) -> Option<ArrayPoolGuard<'_, T, N>> {
+        let index = self.lock().pop()?;
+
+        Some(ArrayPoolGuard::new(self, index))
+    }
+
+    /// The number of unreserved resources
+    pub fn available(&self) -> usize {
+        self.lock().free_len
+    }
+
+    /// The number of resources in the pool, reserved or not
+    pub fn capacity(&self) -> usize {
+        N
+    }
+
+    fn lock(&self) -> MutexGuard<'_, ArrayState<N>> {
+        // The state is only ever updated in full, so it's safe to use after a panic
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<const N: usize> ArrayState<N> {
+    fn pop(&mut self) -> Option<usize> {
+        self.free_len = self.free_len.checked_sub(1)?;
+        Some(self.free[self.free_len])
+    }
+}
+
+impl<'a, T, const N: usize> Future for ArrayRsvp<'a, T, N> {
+    type Output = ArrayPoolGuard<'a, T, N>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let pool = self.pool;
+        let mut state = pool.lock();
+        let index = state.pop();
+
+        state.waiters.poll(&mut self.ticket, cx, index).map(|index| ArrayPoolGuard::new(pool, index))
+    }
+}
+
+impl<T, const N: usize> Drop for ArrayRsvp<'_, T, N> {
+    fn drop(&mut self) {
+        if self.ticket.is_some() {
+            let mut state = self.pool.lock();
+            let available = state.free_len > 0;
+            state.waiters.cancel(self.ticket, available);
+        }
+    }
+}
+
+impl<'a, T, const N: usize> ArrayPoolGuard<'a, T, N> {
+    fn new(pool: &'a ArrayPool<T, N>, index: usize) -> Self {
+        Self { pool, index, _resource: PhantomData }
+    }
+}
+
+impl<T, const N: usize> Drop for ArrayPoolGuard<'_, T, N> {
+    fn drop(&mut self) {
+        let mut state = self.pool.lock();
+        let len = state.free_len;
+        state.free[len] = self.index;
+        state.free_len += 1;
+        state.waiters.wake_next();
+    }
+}
+
+impl<T, const N: usize> Deref for ArrayPoolGuard<'_, T, N> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: This guard holds the slot's index, so nothing else reaches the slot until it drops
+        unsafe { &*self.pool.slots[self.index].get() }
+    }
+}
+
+impl<T, const N: usize> DerefMut for ArrayPoolGuard<'_, T, N> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // SAFETY: As for `deref`, and `&mut self` makes this the only access through the guard
+        unsafe { &mut *self.pool.slots[self.index].get() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_executor::block_on;
+
+    use super::*;
+
+    #[test]
+    fn array_pools_hand_out_and_take_back_in_place() {
+        block_on(async {
+            let pool = ArrayPool::new([1, 2]);
+            let mut one = pool.rsvp().await;
+            let _two = pool.try_rsvp().unwrap();
+            *one += 10;
+
+            let mut waiting = Box::pin(pool.rsvp());
+            assert!(futures_util::poll!(waiting.as_mut()).is_pending());
+
+            drop(one);
+            assert_eq!(*waiting.await, 12);
+            assert_eq!(pool.available(), 1);
+        });
+    }
+
+    #[test]
+    fn guards_are_send_and_sync_with_their_resource() {
+        fn assert_send<T: Send>() {}
+        fn assert_sync<T: Sync>() {}
+
+        assert_send::<ArrayPoolGuard<'static, std::cell::Cell<u8>, 1>>();
+        assert_sync::<ArrayPoolGuard<'static, std::sync::Mutex<u8>, 1>>();
+    }
+}
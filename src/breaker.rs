@@ -0,0 +1,98 @@
+use std::time::Duration;
+
+use crate::{pool::Entry, time::Instant};
+
+/// When a pool stops handing out a resource that keeps failing
+///
+/// Once `threshold` failures in a row are reported against a resource with
+/// `AsyncPoolGuard::report_failure`, it's set aside on return, for `cooldown`. It's then handed out
+/// again on probation: if its next reservation reports another failure it's set aside again, and
+/// otherwise it's back in full service.
+///
+/// With the `serde` feature this can be deserialized, with every field optional and the cooldown
+/// given in (fractional) seconds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize), serde(default, deny_unknown_fields))]
+pub struct CircuitBreaker {
+    /// How many failures in a row set a resource aside
+    pub threshold: u32,
+    /// How long a resource is set aside for
+    #[cfg_attr(feature = "serde", serde(with = "crate::config::secs"))]
+    pub cooldown: Duration,
+}
+
+/// A resource set aside by the circuit breaker, and when it's handed out again
+pub(crate) struct Quarantined<T> {
+    pub(crate) entry: Entry<T>,
+    pub(crate) until: Instant,
+}
+
+impl CircuitBreaker {
+    /// Set resources aside for `cooldown` after `threshold` failures in a row
+    pub fn new(threshold: u32, cooldown: Duration) -> Self {
+        Self { threshold, cooldown }
+    }
+
+    /// When a returned resource is handed out again, if it needs setting aside
+    pub(crate) fn trip<T>(&self, entry: &mut Entry<T>) -> Option<Instant> {
+        // A resource on probation only gets the one chance
+        let tripped = match entry.probing {
+            true => entry.failures > 0,
+            false => entry.failures >= self.threshold,
+        };
+        entry.probing = false;
+
+        match tripped {
+            true => Some(Instant::now() + self.cooldown),
+            false => None,
+        }
+    }
+}
+
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        Self {
+            threshold: 5,
+            cooldown: Duration::from_secs(30),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use futures_executor::block_on;
+
+    use crate::{AsyncPool, Strategy};
+
+    use super::*;
+
+    #[test]
+    fn failing_resources_are_set_aside_then_probed() {
+        let cooldown = Duration::from_millis(20);
+        let pool = block_on(AsyncPool::new_with(vec![1, 2]))
+            .with_strategy(Strategy::Lifo)
+            .with_circuit_breaker(CircuitBreaker::new(2, cooldown));
+        let fail = || pool.try_rsvp().map(|mut guard| guard.report_failure());
+
+        fail();
+        fail();
+        assert_eq!(pool.quarantined(), 1);
+        assert_eq!(*pool.try_rsvp().unwrap(), 2);
+
+        // Back on probation once the cooldown is up, and set aside again by a single failure
+        thread::sleep(cooldown);
+        fail();
+        assert_eq!(pool.quarantined(), 1);
+
+        thread::sleep(cooldown);
+        let mut probed = pool.try_rsvp().unwrap();
+        assert_eq!(*probed, 1);
+        probed.report_success();
+        drop(probed);
+
+        fail();
+        assert_eq!(pool.quarantined(), 0);
+    }
+}
@@ -0,0 +1,98 @@
+use crate::{AsyncPool, AsyncPoolGuard, PoolError, Strategy};
+
+/// A pool of byte buffers, cleared as they're returned
+///
+/// Buffers are created as needed, up to the maximum number if there is one, and each reservation
+/// asks for the capacity it needs: a reused buffer is grown to fit if it's too small, and keeps its
+/// capacity once it's returned. The most recently returned buffer is handed out first, as it's the
+/// likeliest to still be in cache.
+///
+/// Cloning a `BufPool` is cheap, and gives another handle to the same buffers.
+pub struct BufPool {
+    pool: AsyncPool<Vec<u8>>,
+}
+
+impl BufPool {
+    /// Create an empty `BufPool`, with no maximum number of buffers
+    pub fn new() -> Self {
+        Self {
+            pool: AsyncPool::new().with_strategy(Strategy::Lifo).with_reset(),
+        }
+    }
+
+    /// Own at most `max_buffers` buffers, reserved or not
+    pub fn with_max_buffers(self, max_buffers: usize) -> Self {
+        Self { pool: self.pool.with_max_size(max_buffers) }
+    }
+
+    /// Shrink each returned buffer back to `max_capacity` bytes if it grew past it
+    pub fn with_max_capacity(self, max_capacity: usize) -> Self {
+        Self { pool: self.pool.with_capacity_limit(max_capacity) }
+    }
+
+    /// The underlying pool, to configure or inspect
+    pub fn pool(&self) -> &AsyncPool<Vec<u8>> {
+        &self.pool
+    }
+
+    /// Await an empty buffer with room for at least `capacity` bytes, creating one if there's room
+    ///
+    /// Fails like `AsyncPool::rsvp`.
+    pub async fn rsvp(&self, capacity: usize) -> Result<AsyncPoolGuard<Vec<u8>>, PoolError> {
+        let mut buf = match self.pool.rsvp_or_claim().await? {
+            Some(buf) => buf,
+            None => self.pool.guard(Vec::new()),
+        };
+
+        buf.reserve(capacity);
+        Ok(buf)
+    }
+
+    /// Reserve an empty buffer with room for at least `capacity` bytes, if one is idle or there's
+    /// room to create one, without awaiting
+    pub fn try_rsvp(&self, capacity: usize) -> Option<AsyncPoolGuard<Vec<u8>>> {
+        let mut buf = match self.pool.try_rsvp() {
+            Some(buf) => buf,
+            None if self.pool.claim_slot() => self.pool.guard(Vec::new()),
+            None => return None,
+        };
+
+        buf.reserve(capacity);
+        Some(buf)
+    }
+}
+
+impl Default for BufPool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clone for BufPool {
+    fn clone(&self) -> Self {
+        Self { pool: self.pool.clone() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_executor::block_on;
+
+    use super::*;
+
+    #[test]
+    fn buffers_come_back_empty_with_the_capacity_asked_for() {
+        block_on(async {
+            let pool = BufPool::new().with_max_buffers(1);
+
+            let mut buf = pool.rsvp(16).await.unwrap();
+            assert!(buf.capacity() >= 16 && pool.try_rsvp(1).is_none());
+            buf.extend_from_slice(b"request");
+            drop(buf);
+
+            let buf = pool.rsvp(1024).await.unwrap();
+            assert!(buf.is_empty() && buf.capacity() >= 1024);
+            assert_eq!(pool.pool().len(), 1);
+        });
+    }
+}
@@ -0,0 +1,248 @@
+use std::{sync::Arc, time::Duration};
+
+use crate::{
+    AsyncPool, CircuitBreaker, Hooks, ManagedAsyncPool, Manager, PoisonPolicy, PoolConfig, RateLimit, RetryPolicy,
+    Strategy, Timer,
+};
+
+/// A builder for an `AsyncPool` or `ManagedAsyncPool`, created by `AsyncPool::builder`
+///
+/// Options left unset keep the pool's defaults.
+pub struct AsyncPoolBuilder<T> {
+    max_size: Option<usize>,
+    min_idle: usize,
+    timeout: Option<Duration>,
+    idle_timeout: Option<Duration>,
+    max_lifetime: Option<Duration>,
+    strategy: Option<Strategy>,
+    poison_policy: Option<PoisonPolicy>,
+    circuit_breaker: Option<CircuitBreaker>,
+    rate_limit: Option<RateLimit>,
+    thread_cache: bool,
+    retry: Option<RetryPolicy>,
+    warm_up_concurrency: Option<usize>,
+    hooks: Option<Hooks<T>>,
+    timer: Option<Arc<dyn Timer>>,
+}
+
+impl<T: Send + 'static> AsyncPoolBuilder<T> {
+    pub(crate) fn new() -> Self {
+        Self {
+            max_size: None,
+            min_idle: 0,
+            timeout: None,
+            idle_timeout: None,
+            max_lifetime: None,
+            strategy: None,
+            poison_policy: None,
+            circuit_breaker: None,
+            rate_limit: None,
+            thread_cache: false,
+            retry: None,
+            warm_up_concurrency: None,
+            hooks: None,
+            timer: None,
+        }
+    }
+
+    /// Apply every option set in `config`, keeping the rest as they are
+    pub fn config(mut self, config: PoolConfig) -> Self {
+        self.max_size = config.max_size.or(self.max_size);
+        self.min_idle = config.min_idle.unwrap_or(self.min_idle);
+        self.timeout = config.timeout.or(self.timeout);
+        self.idle_timeout = config.idle_timeout.or(self.idle_timeout);
+        self.max_lifetime = config.max_lifetime.or(self.max_lifetime);
+        self.strategy = config.strategy.or(self.strategy);
+        self.circuit_breaker = config.circuit_breaker.or(self.circuit_breaker);
+        self.rate_limit = config.rate_limit.or(self.rate_limit);
+        self.retry = config.retry.or(self.retry);
+        self.warm_up_concurrency = config.warm_up_concurrency.or(self.warm_up_concurrency);
+        self
+    }
+
+    /// Limit the pool to owning at most `max_size` resources
+    pub fn max_size(mut self, max_size: usize) -> Self {
+        self.max_size = Some(max_size);
+        self
+    }
+
+    /// Keep at least `min_idle` unreserved resources ready, for managed pools
+    pub fn min_idle(mut self, min_idle: usize) -> Self {
+        self.min_idle = min_idle;
+        self
+    }
+
+    /// Make every `rsvp` give up after `timeout`
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Drop resources that have been idle for longer than `idle_timeout`
+    pub fn idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.idle_timeout = Some(idle_timeout);
+        self
+    }
+
+    /// Retire resources once they're older than `max_lifetime`
+    pub fn max_lifetime(mut self, max_lifetime: Duration) -> Self {
+        self.max_lifetime = Some(max_lifetime);
+        self
+    }
+
+    /// Hand out idle resources in the order given by `strategy`
+    pub fn strategy(mut self, strategy: Strategy) -> Self {
+        self.strategy = Some(strategy);
+        self
+    }
+
+    /// Handle a panic that poisoned the pool's lock according to `policy`
+    pub fn poison_policy(mut self, policy: PoisonPolicy) -> Self {
+        self.poison_policy = Some(policy);
+        self
+    }
+
+    /// Set aside resources that keep failing, according to `breaker`
+    pub fn circuit_breaker(mut self, breaker: CircuitBreaker) -> Self {
+        self.circuit_breaker = Some(breaker);
+        self
+    }
+
+    /// Limit how many reservations are handed out a second, as with `AsyncPool::with_rate_limit`
+    ///
+    /// Waiting for the limit counts against the `timeout`.
+    pub fn rate_limit(mut self, limit: RateLimit) -> Self {
+        self.rate_limit = Some(limit);
+        self
+    }
+
+    /// Park returned resources on the thread that returned them, as with `AsyncPool::with_thread_cache`
+    pub fn thread_cache(mut self) -> Self {
+        self.thread_cache = true;
+        self
+    }
+
+    /// Retry failed creations according to `retry`, for managed pools
+    pub fn retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = Some(retry);
+        self
+    }
+
+    /// Let `warm_up` create up to `concurrency` resources at once, for managed pools
+    pub fn warm_up_concurrency(mut self, concurrency: usize) -> Self {
+        self.warm_up_concurrency = Some(concurrency);
+        self
+    }
+
+    /// Run `hooks` at each point in the resources' lives
+    pub fn hooks(mut self, hooks: Hooks<T>) -> Self {
+        self.hooks = Some(hooks);
+        self
+    }
+
+    /// Use `timer` for timeouts and maintenance, instead of the `DefaultTimer`
+    pub fn timer(mut self, timer: impl Timer) -> Self {
+        self.timer = Some(Arc::new(timer));
+        self
+    }
+
+    /// Build a pool of the given resources
+    pub fn build_with(self, resources: impl IntoIterator<Item = T>) -> AsyncPool<T> {
+        self.configure(resources.into_iter().collect())
+    }
+
+    /// Build a pool that creates its resources on demand with `manager`
+    pub fn build<M: Manager<Resource = T>>(self, manager: M) -> ManagedAsyncPool<M> {
+        let (min_idle, retry, warm_up_concurrency) = (self.min_idle, self.retry.clone(), self.warm_up_concurrency);
+        let mut pool = ManagedAsyncPool::with_pool(manager, self.configure(AsyncPool::new())).with_min_idle(min_idle);
+
+        if let Some(retry) = retry {
+            pool = pool.with_retry(retry);
+        }
+        if let Some(concurrency) = warm_up_concurrency {
+            pool = pool.with_warm_up_concurrency(concurrency);
+        }
+
+        pool
+    }
+
+    /// Apply the options shared by every kind of pool
+    fn configure(self, mut pool: AsyncPool<T>) -> AsyncPool<T> {
+        if let Some(max_size) = self.max_size {
+            pool = pool.with_max_size(max_size);
+        }
+        if let Some(timeout) = self.timeout {
+            pool = pool.with_timeout(timeout);
+        }
+        if let Some(idle_timeout) = self.idle_timeout {
+            pool = pool.with_idle_timeout(idle_timeout);
+        }
+        if let Some(max_lifetime) = self.max_lifetime {
+            pool = pool.with_max_lifetime(max_lifetime);
+        }
+        if let Some(strategy) = self.strategy {
+            pool = pool.with_strategy(strategy);
+        }
+        if let Some(policy) = self.poison_policy {
+            pool = pool.with_poison_policy(policy);
+        }
+        if let Some(breaker) = self.circuit_breaker {
+            pool = pool.with_circuit_breaker(breaker);
+        }
+        if let Some(limit) = self.rate_limit {
+            pool = pool.with_rate_limit(limit);
+        }
+        if let Some(hooks) = self.hooks {
+            pool = pool.with_hooks(hooks);
+        }
+        if self.thread_cache {
+            pool = pool.with_thread_cache();
+        }
+        if let Some(timer) = self.timer {
+            pool = pool.with_timer(timer);
+        }
+
+        pool
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_executor::block_on;
+
+    use crate::fixtures::Counter;
+
+    use super::*;
+
+    #[test]
+    fn builder_configures_both_kinds_of_pool() {
+        block_on(async {
+            let pool = AsyncPool::builder()
+                .max_size(1)
+                .timeout(Duration::from_millis(1))
+                .build_with(vec![1, 2]);
+            assert_eq!((pool.max_size(), pool.timeout(), pool.len()), (Some(1), Some(Duration::from_millis(1)), 2));
+
+            let managed = AsyncPool::builder()
+                .max_size(3)
+                .min_idle(2)
+                .idle_timeout(Duration::from_secs(60))
+                .build(Counter::default());
+            assert_eq!(managed.replenish().await, Ok(2));
+            assert_eq!(managed.pool().idle_timeout(), Some(Duration::from_secs(60)));
+            assert_eq!(managed.pool().max_size(), Some(3));
+        });
+    }
+
+    #[test]
+    fn config_overrides_only_what_it_sets() {
+        let config = PoolConfig { max_size: Some(4), ..PoolConfig::default() };
+        let pool = AsyncPool::builder()
+            .max_size(1)
+            .timeout(Duration::from_secs(1))
+            .config(config)
+            .build_with(Vec::<u8>::new());
+
+        assert_eq!((pool.max_size(), pool.timeout()), (Some(4), Some(Duration::from_secs(1))));
+    }
+}
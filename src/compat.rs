@@ -0,0 +1,131 @@
+//! Adapters for the managers of other pool crates, so their connectors can fill a `ManagedAsyncPool`
+
+use std::future::Future;
+
+use crate::Manager;
+
+/// A `Manager` made from a `bb8::ManageConnection`
+///
+/// Resources are validated with `has_broken` and then `is_valid`, as bb8 checks them out.
+#[cfg(feature = "bb8")]
+pub struct Bb8Manager<M>(pub M);
+
+/// A `Manager` made from a `deadpool::managed::Manager`
+///
+/// Resources are validated with `recycle`, as deadpool checks them out. The metrics given to it
+/// are fresh, as the pool doesn't hand its own stats to managers.
+#[cfg(feature = "deadpool")]
+pub struct DeadpoolManager<M>(pub M);
+
+#[cfg(feature = "bb8")]
+impl<M: bb8::ManageConnection> Manager for Bb8Manager<M> {
+    type Resource = M::Connection;
+    type Error = M::Error;
+
+    fn create(&self) -> impl Future<Output = Result<M::Connection, M::Error>> + Send {
+        self.0.connect()
+    }
+
+    async fn validate(&self, conn: &mut M::Connection) -> bool {
+        !self.0.has_broken(conn) && self.0.is_valid(conn).await.is_ok()
+    }
+}
+
+#[cfg(feature = "deadpool")]
+impl<M> Manager for DeadpoolManager<M>
+where
+    M: deadpool::managed::Manager + 'static,
+    M::Type: 'static,
+{
+    type Resource = M::Type;
+    type Error = M::Error;
+
+    fn create(&self) -> impl Future<Output = Result<M::Type, M::Error>> + Send {
+        self.0.create()
+    }
+
+    async fn validate(&self, obj: &mut M::Type) -> bool {
+        let metrics = deadpool::managed::Metrics::default();
+        self.0.recycle(obj, &metrics).await.is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "deadpool")]
+    use deadpool::managed::{Metrics, RecycleError, RecycleResult};
+    use futures_executor::block_on;
+
+    use crate::{fixtures::Counter, ManagedAsyncPool};
+
+    use super::*;
+
+    // Both wrap the counter as a connection manager that treats odd numbers as broken
+
+    #[cfg(feature = "bb8")]
+    impl bb8::ManageConnection for Counter {
+        type Connection = usize;
+        type Error = ();
+
+        async fn connect(&self) -> Result<usize, ()> {
+            Ok(self.next())
+        }
+
+        async fn is_valid(&self, conn: &mut usize) -> Result<(), ()> {
+            match *conn % 2 {
+                0 => Ok(()),
+                _ => Err(()),
+            }
+        }
+
+        fn has_broken(&self, _: &mut usize) -> bool {
+            false
+        }
+    }
+
+    #[cfg(feature = "deadpool")]
+    impl deadpool::managed::Manager for Counter {
+        type Type = usize;
+        type Error = ();
+
+        async fn create(&self) -> Result<usize, ()> {
+            Ok(self.next())
+        }
+
+        async fn recycle(&self, obj: &mut usize, _: &Metrics) -> RecycleResult<()> {
+            match *obj % 2 {
+                0 => Ok(()),
+                _ => Err(RecycleError::message("Odd")),
+            }
+        }
+    }
+
+    /// Check that a pool using `manager` drops the broken resource it created
+    fn drops_broken_resources<M: Manager<Resource = usize>>(manager: M)
+    where
+        M::Error: std::fmt::Debug,
+    {
+        block_on(async {
+            let pool = ManagedAsyncPool::new(manager);
+            pool.warm_up(2).await.unwrap();
+
+            let guard = pool.rsvp().await.unwrap();
+            assert_eq!(*guard, 0);
+            drop(guard);
+
+            assert_eq!(*pool.rsvp().await.unwrap(), 0);
+        });
+    }
+
+    #[cfg(feature = "bb8")]
+    #[test]
+    fn bb8_managers_fill_managed_pools() {
+        drops_broken_resources(Bb8Manager(Counter::default()));
+    }
+
+    #[cfg(feature = "deadpool")]
+    #[test]
+    fn deadpool_managers_fill_managed_pools() {
+        drops_broken_resources(DeadpoolManager(Counter::default()));
+    }
+}
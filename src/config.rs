@@ -0,0 +1,83 @@
+use std::time::Duration;
+
+use crate::{CircuitBreaker, RateLimit, RetryPolicy, Strategy};
+
+/// The tunable options of a pool, as they'd be read from a config file
+///
+/// With the `serde` feature this can be deserialized, with every field optional and durations given
+/// in (fractional) seconds. Apply it with `AsyncPoolBuilder::config`.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize), serde(default, deny_unknown_fields))]
+pub struct PoolConfig {
+    /// The most resources the pool may own
+    pub max_size: Option<usize>,
+    /// How many unreserved resources a managed pool keeps ready
+    pub min_idle: Option<usize>,
+    /// How long `rsvp` waits before giving up
+    #[cfg_attr(feature = "serde", serde(with = "secs::option"))]
+    pub timeout: Option<Duration>,
+    /// How long a resource may sit idle before it's dropped
+    #[cfg_attr(feature = "serde", serde(with = "secs::option"))]
+    pub idle_timeout: Option<Duration>,
+    /// How long a resource may be owned by the pool before it's retired
+    #[cfg_attr(feature = "serde", serde(with = "secs::option"))]
+    pub max_lifetime: Option<Duration>,
+    /// The order idle resources are handed out in, as `"fifo"`, `"lifo"` or `"lru"`
+    pub strategy: Option<Strategy>,
+    /// When to set aside resources that keep failing
+    pub circuit_breaker: Option<CircuitBreaker>,
+    /// How many reservations the pool hands out a second
+    pub rate_limit: Option<RateLimit>,
+    /// How a managed pool retries failed creations
+    pub retry: Option<RetryPolicy>,
+    /// How many resources a managed pool's `warm_up` creates at once
+    pub warm_up_concurrency: Option<usize>,
+}
+
+/// Durations as a number of seconds, for config files
+#[cfg(feature = "serde")]
+pub(crate) mod secs {
+    use std::time::Duration;
+
+    use serde::{de::Error, Deserialize, Deserializer};
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        Duration::try_from_secs_f64(f64::deserialize(deserializer)?).map_err(D::Error::custom)
+    }
+
+    pub(crate) mod option {
+        use std::time::Duration;
+
+        use serde::{Deserialize, Deserializer};
+
+        pub(crate) fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Duration>, D::Error> {
+            #[derive(Deserialize)]
+            struct Secs(#[serde(with = "super")] Duration);
+
+            Ok(Option::<Secs>::deserialize(deserializer)?.map(|Secs(dur)| dur))
+        }
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_deserializes_with_durations_in_seconds() {
+        let config: PoolConfig = serde_json::from_str(r#"{
+            "max_size": 16,
+            "timeout": 2.5,
+            "strategy": "lifo",
+            "retry": { "max_retries": 5, "initial_delay": 0.1 }
+        }"#).unwrap();
+
+        assert_eq!(config, PoolConfig {
+            max_size: Some(16),
+            timeout: Some(Duration::from_millis(2500)),
+            strategy: Some(Strategy::Lifo),
+            retry: Some(RetryPolicy { initial_delay: Duration::from_millis(100), ..RetryPolicy::new(5) }),
+            ..PoolConfig::default()
+        });
+    }
+}
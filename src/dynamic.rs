@@ -0,0 +1,134 @@
+use std::{
+    any::{Any, TypeId},
+    iter,
+};
+
+use crate::{AddError, AsyncPool, MappedAsyncPoolGuard, PoolError};
+
+/// A resource of any type, as held by a `DynPool`
+pub type AnyResource = Box<dyn Any + Send>;
+
+/// A pool of resources of different types, sharing one maximum size
+///
+/// This is for resources provided by plugins or the like, which have nothing in common but the
+/// capacity they're limited to. Each is added as its concrete type, and `rsvp_as` only hands out
+/// resources of the type asked for, waiting for as long as those are reserved even if others are
+/// idle.
+///
+/// Cloning a `DynPool` is cheap, and gives another handle to the same resources.
+pub struct DynPool {
+    pool: AsyncPool<AnyResource>,
+}
+
+impl DynPool {
+    /// Create an empty `DynPool`, with no maximum size
+    pub fn new() -> Self {
+        Self { pool: AsyncPool::new() }
+    }
+
+    /// Hold at most `max_size` resources, of every type together
+    pub fn with_max_size(self, max_size: usize) -> Self {
+        Self { pool: self.pool.with_max_size(max_size) }
+    }
+
+    /// The underlying pool, to configure or inspect
+    ///
+    /// Its resources are tagged with their type, so they shouldn't be added to it directly.
+    pub fn pool(&self) -> &AsyncPool<AnyResource> {
+        &self.pool
+    }
+
+    /// Add `item` to the pool, to be reserved with `rsvp_as::<T>`
+    ///
+    /// Fails like `AsyncPool::add`.
+    pub async fn add<T: Any + Send>(&self, item: T) -> Result<(), AddError<T>> {
+        self.pool.add_tagged(Box::new(item), [tag::<T>()]).await.map_err(downcast_error)
+    }
+
+    /// Add `item` to the pool if there's room for it right now
+    ///
+    /// Fails like `AsyncPool::try_add`.
+    pub fn try_add<T: Any + Send>(&self, item: T) -> Result<(), AddError<T>> {
+        self.pool.try_insert(Box::new(item), iter::once(tag::<T>()).collect(), 1).map_err(downcast_error)
+    }
+
+    /// Await the next available resource of type `T`
+    ///
+    /// Fails like `AsyncPool::rsvp`.
+    pub async fn rsvp_as<T: Any + Send>(&self) -> Result<MappedAsyncPoolGuard<AnyResource, T>, PoolError> {
+        let guard = self.pool.rsvp_matching([tag::<T>()]).await?;
+
+        Ok(guard.map(|item| (**item).downcast_mut().expect("Resource was tagged with the wrong type")))
+    }
+
+    /// The number of unreserved resources of type `T`
+    pub fn available_as<T: Any + Send>(&self) -> usize {
+        self.pool.available_matching([tag::<T>()])
+    }
+
+    /// The number of resources owned by the pool, of every type, reserved or not
+    pub fn len(&self) -> usize {
+        self.pool.len()
+    }
+
+    /// Whether the pool owns no resources at all
+    pub fn is_empty(&self) -> bool {
+        self.pool.is_empty()
+    }
+
+    /// Close the pool, like `AsyncPool::close`
+    pub async fn close(&self) -> Result<(), PoolError> {
+        self.pool.close().await
+    }
+}
+
+/// The tag marking resources of type `T`
+fn tag<T: Any>() -> String {
+    // Unlike `type_name`, this is unique to the type
+    format!("{:?}", TypeId::of::<T>())
+}
+
+/// Hand back the resource of a failed `add` as the type it was added as
+fn downcast_error<T: Any>(e: AddError<AnyResource>) -> AddError<T> {
+    let kind = e.kind();
+    let item = e.into_inner().downcast().expect("Rejected resource changed type");
+
+    AddError::new(kind, *item)
+}
+
+impl Default for DynPool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clone for DynPool {
+    fn clone(&self) -> Self {
+        Self { pool: self.pool.clone() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_executor::block_on;
+
+    use super::*;
+
+    #[test]
+    fn resources_are_only_reserved_as_their_own_type() {
+        block_on(async {
+            let pool = DynPool::new().with_max_size(2);
+            pool.add(1u32).await.unwrap();
+            pool.try_add(String::from("plugin")).unwrap();
+            assert_eq!(pool.try_add(2u32).unwrap_err().into_inner(), 2);
+
+            let mut name = pool.rsvp_as::<String>().await.unwrap();
+            name.push_str("-a");
+            assert_eq!(*pool.rsvp_as::<u32>().await.unwrap(), 1);
+            assert_eq!((pool.available_as::<String>(), pool.available_as::<u32>()), (0, 1));
+
+            drop(name);
+            assert_eq!(*pool.rsvp_as::<String>().await.unwrap(), "plugin-a");
+        });
+    }
+}
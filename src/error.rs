@@ -0,0 +1,119 @@
+use std::{error::Error, fmt};
+
+use crate::AsyncPool;
+
+/// The error returned when the pool couldn't do what was asked of it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PoolError {
+    /// No resource became available within the given time
+    Timeout,
+    /// The pool was closed
+    Closed,
+    /// The pool is already at its maximum size
+    Full,
+    /// A thread panicked while holding the pool's lock, so its state can't be trusted
+    Poisoned,
+    /// The call was cancelled before a resource became available
+    Cancelled,
+}
+
+/// What a pool does once a panic poisoned its lock, as set by `AsyncPool::with_poison_policy`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PoisonPolicy {
+    /// Fail every call that would use the state with `PoolError::Poisoned`, except returning guards
+    #[default]
+    Error,
+    /// Carry on as if the lock weren't poisoned
+    ///
+    /// The pool's own bookkeeping is never left half-updated by a panic, so this is safe unless the
+    /// panic came from a hook or resource that left itself broken.
+    Recover,
+    /// Panic in every call that would use the state
+    Panic,
+}
+
+/// The error returned when a resource couldn't be added to a pool. Holds the rejected resource.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AddError<T> {
+    kind: PoolError,
+    item: T,
+}
+
+impl fmt::Display for PoolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PoolError::Timeout => f.write_str("Timed out waiting for a resource"),
+            PoolError::Closed => f.write_str("Pool is closed"),
+            PoolError::Full => f.write_str("Pool is at its maximum size"),
+            PoolError::Poisoned => f.write_str("Pool state was poisoned by a panic"),
+            PoolError::Cancelled => f.write_str("Cancelled while waiting for a resource"),
+        }
+    }
+}
+
+impl Error for PoolError {}
+
+/// The error returned when a pool couldn't be torn down by `AsyncPool::into_inner`. Holds the pool.
+pub struct IntoInnerError<T: Send + 'static> {
+    pool: AsyncPool<T>,
+    /// How many resources were reserved.
+    reserved: usize,
+}
+
+impl<T> AddError<T> {
+    pub(crate) fn new(kind: PoolError, item: T) -> Self {
+        Self { kind, item }
+    }
+
+    /// Why the resource was rejected
+    pub fn kind(&self) -> PoolError {
+        self.kind
+    }
+
+    /// Take back the resource that was rejected
+    pub fn into_inner(self) -> T {
+        self.item
+    }
+}
+
+impl<T: Send + 'static> IntoInnerError<T> {
+    pub(crate) fn new(pool: AsyncPool<T>, reserved: usize) -> Self {
+        Self { pool, reserved }
+    }
+
+    /// How many resources were still reserved
+    pub fn reserved(&self) -> usize {
+        self.reserved
+    }
+
+    /// Take back the pool, still open
+    pub fn into_pool(self) -> AsyncPool<T> {
+        self.pool
+    }
+}
+
+impl<T: Send + 'static> fmt::Debug for IntoInnerError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IntoInnerError").field("reserved", &self.reserved).finish_non_exhaustive()
+    }
+}
+
+impl<T: Send + 'static> fmt::Display for IntoInnerError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Could not tear down the pool: {} resources are still reserved", self.reserved)
+    }
+}
+
+impl<T: Send + 'static> Error for IntoInnerError<T> {}
+
+impl<T> fmt::Display for AddError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Could not add a resource: {}", self.kind)
+    }
+}
+
+impl<T: fmt::Debug> Error for AddError<T> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.kind)
+    }
+}
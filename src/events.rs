@@ -0,0 +1,243 @@
+#[cfg(feature = "metrics")]
+use std::sync::OnceLock;
+use std::{
+    collections::VecDeque,
+    pin::Pin,
+    sync::{atomic::Ordering, Arc, PoisonError},
+    task::{Context, Poll, Waker},
+    time::Duration,
+};
+
+use futures_util::stream::Stream;
+
+#[cfg(feature = "metrics")]
+use crate::metrics::Metrics;
+use crate::sync::{AtomicUsize, Mutex, MutexGuard};
+
+/// The most events a subscriber can fall behind by before the oldest are dropped
+const CAPACITY: usize = 1024;
+
+/// Something that happened in a pool, as seen through `AsyncPool::events`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PoolEvent {
+    /// A resource was reserved
+    Acquired,
+    /// A reserved resource was returned, and is back in the pool
+    Returned,
+    /// The pool dropped a resource, because it was discarded, evicted, retired or closed
+    Discarded,
+    /// A resource failed too many times in a row, and was set aside by the circuit breaker
+    Quarantined,
+    /// Every resource was reserved, so an `rsvp` started waiting
+    Exhausted {
+        /// How many `rsvp` calls are waiting, including this one
+        waiting: usize,
+    },
+    /// The number of resources owned by the pool changed
+    Resized {
+        /// The new number of resources
+        size: usize,
+    },
+}
+
+/// A stream of a pool's events, from when it subscribed, ending once the pool is dropped
+///
+/// A subscriber that falls more than 1024 events behind loses the oldest ones.
+pub struct PoolEvents {
+    queue: Arc<Queue>,
+}
+
+/// The pool's subscribers, which it sends its events to
+pub(crate) struct Events {
+    subscribers: Mutex<Vec<Arc<Queue>>>,
+    /// How many subscribers there are, so events can be skipped without locking.
+    count: AtomicUsize,
+    /// Where the events are also counted, once `AsyncPool::with_metrics` is called.
+    #[cfg(feature = "metrics")]
+    metrics: OnceLock<Metrics>,
+}
+
+/// The events a subscriber hasn't seen yet
+struct Queue {
+    state: Mutex<QueueState>,
+}
+
+struct QueueState {
+    events: VecDeque<PoolEvent>,
+    /// The waker of the subscriber's last poll that found no events.
+    waker: Option<Waker>,
+    /// Whether the pool is gone, and no more events will come.
+    ended: bool,
+}
+
+impl Events {
+    pub(crate) fn new() -> Self {
+        Self {
+            subscribers: Mutex::new(Vec::new()),
+            count: AtomicUsize::new(0),
+            #[cfg(feature = "metrics")]
+            metrics: OnceLock::new(),
+        }
+    }
+
+    /// Report to the `metrics` facade as `name` from now on, unless the pool already does
+    #[cfg(feature = "metrics")]
+    pub(crate) fn export(&self, name: &'static str, size: usize, in_use: usize) {
+        let metrics = self.metrics.get_or_init(|| Metrics::new(name));
+        metrics.occupancy(size, in_use);
+    }
+
+    /// Report the pool owning `size` resources, `in_use` of them reserved, with the `metrics` feature
+    #[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+    pub(crate) fn occupancy(&self, size: usize, in_use: usize) {
+        #[cfg(feature = "metrics")]
+        if let Some(metrics) = self.metrics.get() {
+            metrics.occupancy(size, in_use);
+        }
+    }
+
+    /// Report a reservation labelled `label` that waited for `waited`, with the `metrics` feature
+    #[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+    pub(crate) fn waited(&self, waited: Duration, label: Option<&'static str>) {
+        #[cfg(feature = "metrics")]
+        if let Some(metrics) = self.metrics.get() {
+            metrics.waited(waited, label);
+        }
+    }
+
+    /// Report a manager failing to create a resource, with the `metrics` feature
+    pub(crate) fn failed_to_create(&self) {
+        #[cfg(feature = "metrics")]
+        if let Some(metrics) = self.metrics.get() {
+            metrics.failed_to_create();
+        }
+    }
+
+    /// Start receiving events
+    pub(crate) fn subscribe(&self) -> PoolEvents {
+        let queue = Arc::new(Queue {
+            state: Mutex::new(QueueState {
+                events: VecDeque::new(),
+                waker: None,
+                ended: false,
+            }),
+        });
+
+        let mut subscribers = self.subscribers();
+        subscribers.push(queue.clone());
+        self.count.store(subscribers.len(), Ordering::Release);
+
+        PoolEvents { queue }
+    }
+
+    /// Send `event` to every subscriber
+    pub(crate) fn emit(&self, event: PoolEvent) {
+        #[cfg(feature = "metrics")]
+        if let Some(metrics) = self.metrics.get() {
+            metrics.event(event);
+        }
+
+        if self.count.load(Ordering::Acquire) == 0 {
+            return;
+        }
+
+        let mut subscribers = self.subscribers();
+
+        // Forget subscribers whose stream was dropped
+        subscribers.retain(|queue| Arc::strong_count(queue) > 1);
+        self.count.store(subscribers.len(), Ordering::Release);
+
+        for queue in subscribers.iter() {
+            let mut state = queue.lock();
+
+            if state.events.len() == CAPACITY {
+                state.events.pop_front();
+            }
+            state.events.push_back(event);
+
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+        }
+    }
+
+    fn subscribers(&self) -> MutexGuard<'_, Vec<Arc<Queue>>> {
+        self.subscribers.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Drop for Events {
+    fn drop(&mut self) {
+        for queue in self.subscribers().iter() {
+            let mut state = queue.lock();
+            state.ended = true;
+
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+impl Queue {
+    fn lock(&self) -> MutexGuard<'_, QueueState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Stream for PoolEvents {
+    type Item = PoolEvent;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut state = self.queue.lock();
+
+        match state.events.pop_front() {
+            Some(event) => Poll::Ready(Some(event)),
+            None if state.ended => Poll::Ready(None),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_executor::block_on;
+    use futures_util::stream::StreamExt;
+
+    use crate::AsyncPool;
+
+    use super::*;
+
+    #[test]
+    fn events_follow_the_pool() {
+        block_on(async {
+            let pool = AsyncPool::new();
+            let events = pool.events();
+
+            pool.add(1).await.unwrap();
+            let guard = pool.rsvp().await.unwrap();
+
+            let mut waiting = Box::pin(pool.rsvp());
+            assert!(futures_util::poll!(waiting.as_mut()).is_pending());
+            drop(waiting);
+
+            drop(guard);
+            pool.rsvp().await.unwrap().discard();
+            drop(pool);
+
+            assert_eq!(events.collect::<Vec<_>>().await, [
+                PoolEvent::Resized { size: 1 },
+                PoolEvent::Acquired,
+                PoolEvent::Exhausted { waiting: 1 },
+                PoolEvent::Returned,
+                PoolEvent::Acquired,
+                PoolEvent::Resized { size: 0 },
+                PoolEvent::Discarded,
+            ]);
+        });
+    }
+}
@@ -0,0 +1,114 @@
+use std::time::Duration;
+
+use crate::{AsyncPool, AsyncPoolGuard, PoolError};
+
+/// Two pools chained together: resources come from the primary, and from the secondary when it's
+/// exhausted
+///
+/// This is for a secondary pool of resources that are slower or otherwise worse, like read
+/// replicas, to fall back on under load. By default the secondary is tried as soon as the primary
+/// has nothing idle; with `with_patience`, the primary is waited on for a while first.
+///
+/// Cloning a `FallbackPool` is cheap, and gives another handle to the same pools.
+pub struct FallbackPool<T: Send + 'static> {
+    primary: AsyncPool<T>,
+    secondary: AsyncPool<T>,
+    /// How long to wait on the primary before falling back.
+    patience: Duration,
+}
+
+impl<T: Send + 'static> FallbackPool<T> {
+    /// Chain `primary` and `secondary`, falling back on the secondary right away
+    pub fn new(primary: AsyncPool<T>, secondary: AsyncPool<T>) -> Self {
+        Self {
+            primary,
+            secondary,
+            patience: Duration::ZERO,
+        }
+    }
+
+    /// Wait up to `patience` for a resource from the primary before falling back
+    pub fn with_patience(mut self, patience: Duration) -> Self {
+        self.patience = patience;
+        self
+    }
+
+    /// Await a resource from the primary, or from the secondary if the primary has none in time
+    ///
+    /// Fails if the primary is closed or poisoned, or if it falls back and the secondary fails.
+    pub async fn rsvp(&self) -> Result<AsyncPoolGuard<T>, PoolError> {
+        if let Some(guard) = self.primary.try_rsvp() {
+            return Ok(guard);
+        }
+
+        if !self.patience.is_zero() {
+            match self.primary.rsvp_timeout(self.patience).await {
+                Err(PoolError::Timeout) => {}
+                result => return result,
+            }
+        }
+
+        self.secondary.rsvp().await
+    }
+
+    /// Reserve an idle resource from the primary, or else the secondary, without awaiting
+    pub fn try_rsvp(&self) -> Option<AsyncPoolGuard<T>> {
+        self.primary.try_rsvp().or_else(|| self.secondary.try_rsvp())
+    }
+
+    /// Close both pools, like `AsyncPool::close`
+    pub async fn close(&self) -> Result<(), PoolError> {
+        self.primary.close().await?;
+        self.secondary.close().await
+    }
+
+    /// The pool tried first
+    pub fn primary(&self) -> &AsyncPool<T> {
+        &self.primary
+    }
+
+    /// The pool fallen back on
+    pub fn secondary(&self) -> &AsyncPool<T> {
+        &self.secondary
+    }
+
+    /// The number of unreserved resources in both pools
+    pub fn available(&self) -> usize {
+        self.primary.available() + self.secondary.available()
+    }
+}
+
+impl<T: Send + 'static> Clone for FallbackPool<T> {
+    fn clone(&self) -> Self {
+        Self {
+            primary: self.primary.clone(),
+            secondary: self.secondary.clone(),
+            patience: self.patience,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_executor::block_on;
+
+    use super::*;
+
+    #[test]
+    fn exhausted_primary_falls_back_on_the_secondary() {
+        block_on(async {
+            let primary = AsyncPool::new_with(vec!["fast"]).await;
+            let secondary = AsyncPool::new_with(vec!["slow"]).await;
+            let pool = FallbackPool::new(primary, secondary).with_patience(Duration::from_millis(5));
+
+            let fast = pool.rsvp().await.unwrap();
+            assert_eq!((*fast, *pool.rsvp().await.unwrap()), ("fast", "slow"));
+
+            let slow = pool.try_rsvp().unwrap();
+            assert_eq!(*slow, "slow");
+            assert!(pool.try_rsvp().is_none());
+            drop(fast);
+            assert_eq!(*pool.rsvp().await.unwrap(), "fast");
+        });
+    }
+}
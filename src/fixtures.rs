@@ -0,0 +1,28 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::Manager;
+
+/// A manager that hands out increasing numbers, from zero
+#[derive(Default)]
+pub(crate) struct Counter(AtomicUsize);
+
+impl Counter {
+    /// Hand out the next number
+    pub(crate) fn next(&self) -> usize {
+        self.0.fetch_add(1, Ordering::SeqCst)
+    }
+
+    /// How many numbers have been handed out
+    pub(crate) fn created(&self) -> usize {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+impl Manager for Counter {
+    type Resource = usize;
+    type Error = ();
+
+    async fn create(&self) -> Result<usize, ()> {
+        Ok(self.next())
+    }
+}
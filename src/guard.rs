@@ -0,0 +1,482 @@
+use std::{
+    mem::{self, ManuallyDrop},
+    ops::{Deref, DerefMut},
+    ptr::NonNull,
+    sync::{Arc, Weak},
+    time::Duration,
+};
+
+use crate::{
+    hooks::{self, Hooks},
+    pool::{Entry, Shared},
+    time::Instant,
+    trace::event,
+    PoolEvent,
+};
+
+// TODO: Avoid move-by-value (Issue #1)
+/// The guard on a resource's borrow. Returns the value to the `AsyncPool` on drop.
+///
+/// The guard is `Send` whenever `T` is, so it can be held across `await` points in spawned tasks.
+/// It doesn't keep the pool alive: if the pool is dropped first, the resource is dropped with the
+/// guard, without running the pool's hooks.
+pub struct AsyncPoolGuard<T: Send + 'static> {
+    /// The resource, along with what the pool knows about it.
+    inner: Option<Entry<T>>,
+    /// When the resource was reserved.
+    reserved: Instant,
+    /// How long the reservation waited for the resource.
+    waited: Duration,
+    /// What the reservation was labelled, by `rsvp_labeled`.
+    label: Option<&'static str>,
+    /// The pool to return the resource to, if it's still alive.
+    shared: Weak<Shared<T>>,
+}
+
+/// The guard on a batch of resources reserved together. Returns them all to the `AsyncPool` on drop.
+pub struct AsyncPoolBatchGuard<T: Send + 'static> {
+    inner: Vec<T>,
+    /// What the pool knows about each resource, in the same order.
+    entries: Vec<Entry<()>>,
+    /// When the resources were reserved.
+    reserved: Instant,
+    /// The pool to return the resources to, if it's still alive.
+    shared: Weak<Shared<T>>,
+}
+
+/// The guard on part of a resource, from `AsyncPoolGuard::map`. Returns the whole resource on drop.
+pub struct MappedAsyncPoolGuard<T: Send + 'static, U: ?Sized> {
+    /// The guard on the whole resource, leaked from a box so the resource never moves.
+    guard: NonNull<AsyncPoolGuard<T>>,
+    /// The part of the resource this guard gives access to.
+    value: NonNull<U>,
+}
+
+impl<T: Send + 'static> AsyncPoolGuard<T> {
+    /// Guard a resource that's already counted as reserved by `shared`, running its acquire hook
+    pub(crate) fn new(shared: Arc<Shared<T>>, entry: Entry<T>) -> Self {
+        let hooks = shared.hooks();
+        Self::hooked(shared, entry, &hooks)
+    }
+
+    /// Guard a resource that's already counted as reserved by `shared`, running the acquire hook in
+    /// `hooks`
+    pub(crate) fn hooked(shared: Arc<Shared<T>>, entry: Entry<T>, hooks: &Hooks<T>) -> Self {
+        shared.trace(Some(entry.id));
+        let mut guard = Self::reserved(Arc::downgrade(&shared), entry, Instant::now());
+
+        // Once guarded, the resource still returns to the pool if the hook panics
+        let info = guard.info();
+        hooks::run(&hooks.on_acquire, &mut *guard, info);
+        shared.events.emit(PoolEvent::Acquired);
+
+        guard
+    }
+
+    /// Guard a resource reserved at `reserved`
+    fn reserved(shared: Weak<Shared<T>>, entry: Entry<T>, reserved: Instant) -> Self {
+        Self {
+            inner: Some(entry),
+            reserved,
+            waited: Duration::ZERO,
+            label: None,
+            shared,
+        }
+    }
+
+    /// Record that the reservation waited `waited` for the resource
+    pub(crate) fn waited_for(mut self, waited: Duration) -> Self {
+        self.waited = waited;
+        self
+    }
+
+    /// Record that the reservation was labelled `label`
+    pub(crate) fn labeled(mut self, label: Option<&'static str>) -> Self {
+        self.label = label;
+        self
+    }
+
+    /// What the reservation was labelled, if it was made with `rsvp_labeled`
+    pub fn label(&self) -> Option<&'static str> {
+        self.label
+    }
+
+    /// When the resource was created, or first added to the pool
+    pub fn created(&self) -> Instant {
+        self.entry().created
+    }
+
+    /// The resource's ID, as found in `AsyncPool::resource_stats`
+    pub fn id(&self) -> u64 {
+        self.entry().id
+    }
+
+    /// The tags the resource was added with
+    pub fn tags(&self) -> impl Iterator<Item = &str> {
+        self.entry().tags.iter().map(String::as_str)
+    }
+
+    /// The resource's capacity, as given to `add_weighted`
+    pub fn weight(&self) -> usize {
+        self.entry().weight
+    }
+
+    /// Count a failure against the resource, for the pool's circuit breaker
+    ///
+    /// Once enough failures in a row are counted, the resource is set aside for a while when the guard
+    /// drops.
+    pub fn report_failure(&mut self) {
+        self.entry_mut().failures += 1;
+    }
+
+    /// Count a success for the resource, clearing its failures
+    pub fn report_success(&mut self) {
+        self.entry_mut().failures = 0;
+    }
+
+    /// When the resource was reserved
+    pub fn reserved_at(&self) -> Instant {
+        self.reserved
+    }
+
+    /// How long the reservation waited for the resource, from the call to `rsvp`
+    ///
+    /// For `ManagedAsyncPool::rsvp`, this includes creating or validating the resource. It's zero for
+    /// guards from `try_rsvp`, and those split from a batch.
+    pub fn wait_duration(&self) -> Duration {
+        self.waited
+    }
+
+    /// Take the resource out of the pool for good, shrinking the pool
+    pub fn take(mut self) -> T {
+        let entry = self.inner.take()
+            .expect("Inner value dropped while Guard was active");
+
+        if let Some(shared) = self.shared.upgrade() {
+            shared.forget(entry.id);
+        }
+
+        entry.item
+    }
+
+    /// Drop the resource instead of returning it, shrinking the pool
+    ///
+    /// Use this for resources that broke while reserved.
+    pub fn discard(mut self) {
+        let entry = self.inner.take()
+            .expect("Inner value dropped while Guard was active");
+
+        if let Some(shared) = self.shared.upgrade() {
+            event!(id = entry.id, "Discarding a reserved resource");
+            shared.forget(entry.id);
+            shared.destroy(entry, Some(self.reserved));
+        }
+    }
+
+    /// Swap in `value` as a new resource, in place of the reserved one, which is handed back
+    ///
+    /// This is for resources that had to be rebuilt while reserved, like a connection that had to
+    /// reconnect: the replacement returns to the pool on drop, with the original's tags and weight
+    /// but its own ID and stats, and the original leaves the pool as with `take`.
+    pub fn replace(&mut self, value: T) -> T {
+        let old = self.entry();
+        let mut entry = match self.shared.upgrade() {
+            Some(shared) => shared.replace(old.id, value),
+            None => Entry::new(value),
+        };
+        entry.tags = old.tags.clone();
+        entry.weight = old.weight;
+
+        mem::replace(self.entry_mut(), entry).item
+    }
+
+    /// Narrow the guard down to part of the resource, which still returns whole on drop
+    ///
+    /// This is for handing out only, say, the connection in a pooled struct that also holds its
+    /// metadata.
+    pub fn map<U: ?Sized>(self, f: impl FnOnce(&mut T) -> &mut U) -> MappedAsyncPoolGuard<T, U> {
+        let mut guard = NonNull::from(Box::leak(Box::new(self)));
+
+        // SAFETY: The guard stays leaked, and only reached through `value`, until the mapped guard
+        // drops it, so the resource neither moves nor is aliased while `value` points into it.
+        let value = NonNull::from(unsafe { &mut **guard.as_mut() });
+
+        // The whole-resource guard owns the box from here, so a panic in `f` still returns it
+        MappedAsyncPoolGuard { guard, value }.map(f)
+    }
+
+    /// The resource's timings, for hooks
+    fn info(&self) -> hooks::ResourceInfo {
+        self.entry().info(Some(self.reserved))
+    }
+
+    fn entry(&self) -> &Entry<T> {
+        self.inner.as_ref()
+            .expect("Inner value dropped while Guard was active")
+    }
+
+    fn entry_mut(&mut self) -> &mut Entry<T> {
+        self.inner.as_mut()
+            .expect("Inner value dropped while Guard was active")
+    }
+}
+
+impl<T: Send + 'static> Drop for AsyncPoolGuard<T> {
+    fn drop(&mut self) {
+        if let (Some(entry), Some(shared)) = (self.inner.take(), self.shared.upgrade()) {
+            shared.release(entry, self.reserved);
+        }
+    }
+}
+
+impl<T: Send + 'static> Deref for AsyncPoolGuard<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.entry().item
+    }
+}
+
+impl<T: Send + 'static> DerefMut for AsyncPoolGuard<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.entry_mut().item
+    }
+}
+
+impl<T: Send + 'static> AsyncPoolBatchGuard<T> {
+    /// Guard resources that are already counted as reserved by `shared`
+    pub(crate) fn new(shared: Arc<Shared<T>>, entries: Vec<Entry<T>>) -> Self {
+        shared.trace(entries.iter().map(|entry| entry.id));
+        let (inner, entries) = entries.into_iter().map(Entry::split).unzip();
+        let mut guard = Self {
+            inner,
+            entries,
+            reserved: Instant::now(),
+            shared: Arc::downgrade(&shared),
+        };
+
+        let on_acquire = shared.hooks().on_acquire.clone();
+        for (item, entry) in guard.inner.iter_mut().zip(&guard.entries) {
+            hooks::run(&on_acquire, item, entry.info(Some(guard.reserved)));
+            shared.events.emit(PoolEvent::Acquired);
+        }
+
+        guard
+    }
+
+    /// Split the batch into a guard per resource, so each can be returned on its own
+    pub fn into_guards(mut self) -> Vec<AsyncPoolGuard<T>> {
+        let entries = mem::take(&mut self.entries);
+        let reserved = self.reserved;
+
+        mem::take(&mut self.inner)
+            .into_iter()
+            .zip(entries)
+            .map(|(item, entry)| AsyncPoolGuard::reserved(self.shared.clone(), entry.join(item), reserved))
+            .collect()
+    }
+}
+
+impl<T: Send + 'static> Drop for AsyncPoolBatchGuard<T> {
+    fn drop(&mut self) {
+        let shared = match self.shared.upgrade() {
+            Some(shared) => shared,
+            None => return,
+        };
+
+        for (i, entry) in self.inner.drain(..).zip(self.entries.drain(..)) {
+            shared.release(entry.join(i), self.reserved);
+        }
+    }
+}
+
+impl<T: Send + 'static> Deref for AsyncPoolBatchGuard<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+impl<T: Send + 'static> DerefMut for AsyncPoolBatchGuard<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.inner
+    }
+}
+
+impl<T: Send + 'static, U: ?Sized> MappedAsyncPoolGuard<T, U> {
+    /// Narrow the guard down further, to part of the part it already guards
+    pub fn map<V: ?Sized>(self, f: impl FnOnce(&mut U) -> &mut V) -> MappedAsyncPoolGuard<T, V> {
+        // SAFETY: `value` is only reborrowed for the projection. `self` keeps owning the leaked guard
+        // until `f` returns, so it's still dropped if `f` panics.
+        let value = NonNull::from(f(unsafe { &mut *self.value.as_ptr() }));
+        let this = ManuallyDrop::new(self);
+
+        MappedAsyncPoolGuard { guard: this.guard, value }
+    }
+}
+
+impl<T: Send + 'static, U: ?Sized> Drop for MappedAsyncPoolGuard<T, U> {
+    fn drop(&mut self) {
+        // SAFETY: The guard was leaked from a box by `AsyncPoolGuard::map`, and only this drops it
+        drop(unsafe { Box::from_raw(self.guard.as_ptr()) });
+    }
+}
+
+impl<T: Send + 'static, U: ?Sized> Deref for MappedAsyncPoolGuard<T, U> {
+    type Target = U;
+
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: `value` points into the leaked guard's resource, which lives as long as `self`
+        unsafe { self.value.as_ref() }
+    }
+}
+
+impl<T: Send + 'static, U: ?Sized> DerefMut for MappedAsyncPoolGuard<T, U> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // SAFETY: As for `deref`, and `&mut self` makes this the only access
+        unsafe { self.value.as_mut() }
+    }
+}
+
+// SAFETY: The mapped guard owns the guard and gives access to the `U` in the resource, just like a
+// `Box<AsyncPoolGuard<T>>` and a `&mut U` would.
+unsafe impl<T: Send + 'static, U: ?Sized + Send> Send for MappedAsyncPoolGuard<T, U> {}
+unsafe impl<T: Send + 'static, U: ?Sized + Sync> Sync for MappedAsyncPoolGuard<T, U> {}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        cell::Cell,
+        panic::{self, AssertUnwindSafe},
+        sync::mpsc,
+        thread,
+    };
+
+    use futures_executor::block_on;
+    use futures_util::poll;
+
+    use crate::AsyncPool;
+
+    use super::*;
+
+    fn assert_send<T: Send>() {}
+
+    #[test]
+    fn guard_is_send_when_resource_is() {
+        assert_send::<AsyncPoolGuard<Vec<u8>>>();
+        assert_send::<AsyncPoolGuard<Cell<u8>>>();
+    }
+
+    #[test]
+    fn mapped_guards_return_the_whole_resource() {
+        let pool = block_on(AsyncPool::new_with(vec![(String::from("conn"), 0)]));
+        assert_send::<MappedAsyncPoolGuard<(String, u8), str>>();
+
+        let mut conn = pool.try_rsvp().unwrap().map(|(conn, _)| conn);
+        conn.push_str("ection");
+        let mut conn = conn.map(String::as_mut_str);
+        conn.make_ascii_uppercase();
+
+        assert!(pool.try_rsvp().is_none());
+        drop(conn);
+        assert_eq!(*pool.try_rsvp().unwrap(), (String::from("CONNECTION"), 0));
+    }
+
+    #[test]
+    fn discarded_resources_leave_the_pool_and_free_their_slot() {
+        block_on(async {
+            let pool = AsyncPool::new_with(vec![1]).await.with_max_size(1);
+            let guard = pool.rsvp().await.unwrap();
+
+            let mut add = Box::pin(pool.add(2));
+            assert!(futures_util::poll!(add.as_mut()).is_pending());
+
+            guard.discard();
+            assert!(add.await.is_ok());
+            assert_eq!((pool.len(), *pool.try_rsvp().unwrap()), (1, 2));
+
+            // With the pool gone, the resource is just dropped
+            let guard = pool.try_rsvp().unwrap();
+            drop(pool);
+            guard.discard();
+        });
+    }
+
+    #[test]
+    fn panicking_maps_still_return_the_resource() {
+        let pool = block_on(AsyncPool::new_with(vec![(String::from("conn"), 0)]));
+
+        let guard = pool.try_rsvp().unwrap();
+        assert!(panic::catch_unwind(AssertUnwindSafe(|| guard.map(|_| -> &mut u8 { panic!("no") }))).is_err());
+        assert_eq!((pool.in_use(), pool.available()), (0, 1));
+
+        let mapped = pool.try_rsvp().unwrap().map(|(conn, _)| conn);
+        assert!(panic::catch_unwind(AssertUnwindSafe(|| mapped.map(|_| -> &mut str { panic!("no") }))).is_err());
+        assert_eq!((pool.in_use(), pool.available()), (0, 1));
+    }
+
+    #[test]
+    fn guards_outliving_the_pool_drop_their_resource() {
+        let pool = block_on(AsyncPool::new_with(vec![1, 2, 3]));
+        let (one, batch) = (pool.try_rsvp().unwrap(), block_on(pool.rsvp_many(2)).unwrap());
+        let weak = pool.downgrade();
+
+        drop(pool);
+        assert!(weak.upgrade().is_none());
+        drop((one, batch));
+    }
+
+    #[test]
+    fn replaced_resources_return_in_place_of_the_original() {
+        let pool = block_on(AsyncPool::new_with(vec![1]));
+        let mut guard = pool.try_rsvp().unwrap();
+        let id = guard.id();
+
+        assert_eq!(guard.replace(2), 1);
+        assert_ne!(guard.id(), id);
+        assert_eq!((pool.len(), pool.in_use()), (1, 1));
+
+        drop(guard);
+        assert_eq!(*pool.try_rsvp().unwrap(), 2);
+    }
+
+    #[test]
+    fn guards_know_how_long_they_waited_and_for_what() {
+        block_on(async {
+            let pool = AsyncPool::new_with(vec![1]).await;
+            let held = pool.try_rsvp().unwrap();
+            let id = held.id();
+
+            let (release, released) = mpsc::channel();
+            let returner = thread::spawn(move || {
+                released.recv().unwrap();
+                thread::sleep(Duration::from_millis(10));
+                drop(held);
+            });
+
+            // The returner only starts its delay once the reservation is waiting
+            let before = Instant::now();
+            let mut rsvp = Box::pin(pool.rsvp());
+            assert!(poll!(rsvp.as_mut()).is_pending());
+            release.send(()).unwrap();
+
+            let guard = rsvp.await.unwrap();
+            returner.join().unwrap();
+
+            assert_eq!(guard.id(), id);
+            assert!(guard.wait_duration() >= Duration::from_millis(10));
+            assert!(guard.wait_duration() <= before.elapsed());
+            assert!(guard.reserved_at() >= before);
+        });
+    }
+
+    #[test]
+    fn guard_returns_from_another_thread() {
+        let pool = block_on(AsyncPool::new_with(vec![1]));
+        let guard = pool.try_rsvp().unwrap();
+
+        thread::spawn(move || assert_eq!(*guard, 1)).join().unwrap();
+        assert!(pool.try_rsvp().is_some());
+    }
+}
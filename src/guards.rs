@@ -0,0 +1,73 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures_util::{future::BoxFuture, stream::Stream};
+
+use crate::{AsyncPool, AsyncPoolGuard};
+
+/// A stream of guards, each reserved as soon as the one before is taken
+///
+/// Created by `AsyncPool::guards`. Each guard is awaited like `rsvp`, but without the pool's
+/// timeout, and the stream ends once the pool is closed.
+pub struct Guards<T: Send + 'static> {
+    pool: AsyncPool<T>,
+    /// The reservation of the next guard, once it was polled for.
+    next: Option<BoxFuture<'static, Option<AsyncPoolGuard<T>>>>,
+}
+
+impl<T: Send + 'static> Guards<T> {
+    pub(crate) fn new(pool: AsyncPool<T>) -> Self {
+        Self { pool, next: None }
+    }
+}
+
+impl<T: Send + 'static> Stream for Guards<T> {
+    type Item = AsyncPoolGuard<T>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        let pool = &this.pool;
+        let next = this.next.get_or_insert_with(|| Box::pin(pool.clone().rsvp_untimed()));
+
+        let guard = match next.as_mut().poll(cx) {
+            Poll::Ready(guard) => guard,
+            Poll::Pending => return Poll::Pending,
+        };
+
+        this.next = None;
+        Poll::Ready(guard)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_executor::block_on;
+    use futures_util::stream::StreamExt;
+
+    use super::*;
+
+    #[test]
+    fn guards_stream_until_closed() {
+        block_on(async {
+            let pool = AsyncPool::new_with(vec![1, 2]).await;
+            let mut guards = pool.guards();
+
+            let first = guards.next().await.unwrap();
+            let second = guards.next().await.unwrap();
+            assert_eq!((*first, *second), (1, 2));
+
+            let mut next = guards.next();
+            assert!(futures_util::poll!(&mut next).is_pending());
+
+            drop(first);
+            assert_eq!(*next.await.unwrap(), 1);
+
+            let close = pool.close();
+            futures_util::pin_mut!(close);
+            assert!(futures_util::poll!(close.as_mut()).is_pending());
+            assert!(guards.next().await.is_none());
+        });
+    }
+}
@@ -0,0 +1,316 @@
+use std::{fmt, future::Future, pin::Pin, sync::Arc};
+
+use crate::{time::Instant, Spawner, Task};
+
+/// Callbacks the pool runs at each point in a resource's life
+///
+/// Hooks run outside the pool's lock, but inside `Drop` for releases and discards, so they must not
+/// block.
+pub struct Hooks<T> {
+    pub(crate) on_create: Option<Arc<Hook<T>>>,
+    pub(crate) on_acquire: Option<Arc<Hook<T>>>,
+    pub(crate) on_release: Option<Arc<Hook<T>>>,
+    pub(crate) on_destroy: Option<Arc<Hook<T>>>,
+}
+
+/// A lifecycle callback, given the resource and its timings
+type Hook<T> = dyn Fn(&mut T, &ResourceInfo) + Send + Sync;
+
+/// An async hook's future, giving back the resource if it's still fit for use
+type Returned<T> = Pin<Box<dyn Future<Output = Option<T>> + Send>>;
+
+/// An async destructor for the resources a pool drops, and the spawner to run it on
+pub(crate) struct AsyncDestroy<T> {
+    close: Box<dyn Fn(T) -> Task + Send + Sync>,
+    spawner: Box<dyn Spawner + Send + Sync>,
+}
+
+/// An async hook for returned resources, and the spawner to run it on
+pub(crate) struct AsyncReturn<T> {
+    hook: Box<dyn Fn(T) -> Returned<T> + Send + Sync>,
+    spawner: Box<dyn Spawner + Send + Sync>,
+}
+
+/// When a resource passed through the points of its life, as given to `Hooks`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResourceInfo {
+    /// The resource's ID, as found in `AsyncPool::resource_stats`
+    pub id: u64,
+    /// When the resource was created, or first added to the pool
+    pub created: Instant,
+    /// When the resource was last reserved, if it's reserved now
+    pub reserved: Option<Instant>,
+}
+
+impl<T> Hooks<T> {
+    /// Create a set of hooks that do nothing
+    pub fn new() -> Self {
+        Self {
+            on_create: None,
+            on_acquire: None,
+            on_release: None,
+            on_destroy: None,
+        }
+    }
+
+    /// Run `hook` on each resource as it enters the pool, whether added or created by a `Manager`
+    pub fn on_create(mut self, hook: impl Fn(&mut T, &ResourceInfo) + Send + Sync + 'static) -> Self {
+        self.on_create = Some(Arc::new(hook));
+        self
+    }
+
+    /// Run `hook` on each resource as it's reserved, before its guard is handed out
+    pub fn on_acquire(mut self, hook: impl Fn(&mut T, &ResourceInfo) + Send + Sync + 'static) -> Self {
+        self.on_acquire = Some(Arc::new(hook));
+        self
+    }
+
+    /// Run `hook` on each resource as its guard drops, before it re-enters the pool
+    pub fn on_release(mut self, hook: impl Fn(&mut T, &ResourceInfo) + Send + Sync + 'static) -> Self {
+        self.on_release = Some(Arc::new(hook));
+        self
+    }
+
+    /// Run `hook` on each resource the pool drops, whether discarded, evicted, retired or closed
+    pub fn on_destroy(mut self, hook: impl Fn(&mut T, &ResourceInfo) + Send + Sync + 'static) -> Self {
+        self.on_destroy = Some(Arc::new(hook));
+        self
+    }
+}
+
+impl<T> Clone for Hooks<T> {
+    fn clone(&self) -> Self {
+        Self {
+            on_create: self.on_create.clone(),
+            on_acquire: self.on_acquire.clone(),
+            on_release: self.on_release.clone(),
+            on_destroy: self.on_destroy.clone(),
+        }
+    }
+}
+
+impl<T> Default for Hooks<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> fmt::Debug for Hooks<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Hooks")
+            .field("on_create", &self.on_create.is_some())
+            .field("on_acquire", &self.on_acquire.is_some())
+            .field("on_release", &self.on_release.is_some())
+            .field("on_destroy", &self.on_destroy.is_some())
+            .finish()
+    }
+}
+
+impl<T> AsyncDestroy<T> {
+    pub(crate) fn new<F>(
+        spawner: impl Spawner + Send + Sync + 'static,
+        close: impl Fn(T) -> F + Send + Sync + 'static,
+    ) -> Self
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        Self {
+            close: Box::new(move |item| Box::pin(close(item))),
+            spawner: Box::new(spawner),
+        }
+    }
+
+    /// The task that closes `item`
+    pub(crate) fn close(&self, item: T) -> Task {
+        (self.close)(item)
+    }
+
+    /// Close `item` in the background
+    pub(crate) fn spawn(&self, item: T) {
+        self.spawner.spawn(self.close(item));
+    }
+}
+
+impl<T> AsyncReturn<T> {
+    pub(crate) fn new<F>(
+        spawner: impl Spawner + Send + Sync + 'static,
+        hook: impl Fn(T) -> F + Send + Sync + 'static,
+    ) -> Self
+    where
+        F: Future<Output = Option<T>> + Send + 'static,
+    {
+        Self {
+            hook: Box::new(move |item| Box::pin(hook(item))),
+            spawner: Box::new(spawner),
+        }
+    }
+
+    /// The future that readies `item` to re-enter the pool, or drops it
+    pub(crate) fn run(&self, item: T) -> Returned<T> {
+        (self.hook)(item)
+    }
+
+    pub(crate) fn spawn(&self, task: Task) {
+        self.spawner.spawn(task);
+    }
+}
+
+/// Run `hook`, if there is one
+pub(crate) fn run<T>(hook: &Option<Arc<Hook<T>>>, item: &mut T, info: ResourceInfo) {
+    if let Some(hook) = hook {
+        hook(item, &info);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Mutex, OnceLock},
+        thread,
+    };
+
+    use futures_executor::block_on;
+    use futures_util::FutureExt;
+
+    use crate::{AsyncPool, Task, WeakPool};
+
+    use super::*;
+
+    #[test]
+    fn hooks_run_through_the_lifecycle() {
+        block_on(async {
+            let calls = Arc::new(Mutex::new(Vec::new()));
+            let hook = |name: &'static str| {
+                let calls = calls.clone();
+                move |item: &mut u8, info: &ResourceInfo| {
+                    calls.lock().unwrap().push((name, *item, info.reserved.is_some()));
+                }
+            };
+
+            let pool = AsyncPool::new()
+                .with_hooks(Hooks::new()
+                    .on_create(hook("create"))
+                    .on_acquire(hook("acquire"))
+                    .on_release(hook("release"))
+                    .on_destroy(hook("destroy")));
+
+            pool.add(1).await.unwrap();
+            drop(pool.rsvp().await.unwrap());
+            pool.rsvp().await.unwrap().discard();
+            pool.add(2).await.unwrap();
+            pool.close().await.unwrap();
+
+            assert_eq!(*calls.lock().unwrap(), [
+                ("create", 1, false),
+                ("acquire", 1, true),
+                ("release", 1, true),
+                ("acquire", 1, true),
+                ("destroy", 1, true),
+                ("create", 2, false),
+                ("destroy", 2, false),
+            ]);
+        });
+    }
+
+    type Calls = Arc<Mutex<Vec<(&'static str, u8)>>>;
+
+    /// A hook that logs each call as `name`, and starts closing the pool set in `closes`, if any
+    fn logged(
+        calls: &Calls,
+        name: &'static str,
+        closes: Option<&Arc<OnceLock<WeakPool<u8>>>>,
+    ) -> impl Fn(&mut u8, &ResourceInfo) + Send + Sync + 'static {
+        let (calls, closes) = (calls.clone(), closes.cloned());
+        move |item, _| {
+            calls.lock().unwrap().push((name, *item));
+            if let Some(pool) = closes.as_ref().and_then(|pool| pool.get()?.upgrade()) {
+                // Only starts closing, as the pool still owns this resource
+                assert!(pool.close().now_or_never().is_none());
+            }
+        }
+    }
+
+    #[test]
+    fn resources_reaching_a_pool_closed_by_their_hook_are_destroyed() {
+        block_on(async {
+            let (calls, handle) = (Calls::default(), Arc::new(OnceLock::new()));
+            let pool = AsyncPool::new().with_hooks(Hooks::new()
+                .on_create(logged(&calls, "create", Some(&handle)))
+                .on_destroy(logged(&calls, "destroy", None)));
+            let _ = handle.set(pool.downgrade());
+
+            pool.add(1).await.unwrap();
+            assert_eq!((pool.len(), pool.is_closed()), (0, true));
+            assert_eq!(*calls.lock().unwrap(), [("create", 1), ("destroy", 1)]);
+
+            let (calls, handle) = (Calls::default(), Arc::new(OnceLock::new()));
+            let pool = AsyncPool::new_with(vec![2]).await.with_hooks(Hooks::new()
+                .on_release(logged(&calls, "release", Some(&handle)))
+                .on_destroy(logged(&calls, "destroy", None)));
+            let _ = handle.set(pool.downgrade());
+
+            drop(pool.rsvp().await.unwrap());
+            assert_eq!(pool.len(), 0);
+            assert_eq!(*calls.lock().unwrap(), [("release", 2), ("destroy", 2)]);
+        });
+    }
+
+    #[test]
+    fn hooks_see_the_same_id_as_guards_and_stats() {
+        block_on(async {
+            let created = Arc::new(Mutex::new(Vec::new()));
+            let seen = created.clone();
+            let pool = AsyncPool::new()
+                .with_hooks(Hooks::new().on_create(move |_: &mut u8, info: &ResourceInfo| {
+                    seen.lock().unwrap().push(info.id);
+                }));
+
+            pool.add_many(vec![1, 2]).await.unwrap();
+            let guard = pool.rsvp().await.unwrap();
+
+            let created = created.lock().unwrap();
+            assert!(created[0] < created[1]);
+            assert_eq!((guard.id(), pool.resource_stats()[0].id), (created[0], created[0]));
+        });
+    }
+
+    /// Runs each task to completion, on another thread, as soon as it's spawned
+    struct Inline;
+
+    impl Spawner for Inline {
+        fn spawn(&self, task: Task) {
+            thread::spawn(move || block_on(task)).join().unwrap();
+        }
+    }
+
+    #[test]
+    fn async_destructors_run_on_every_drop() {
+        block_on(async {
+            let closed = Arc::new(Mutex::new(Vec::new()));
+            let log = closed.clone();
+            let pool = AsyncPool::new_with(vec![1, 2]).await.with_async_destroy(Inline, move |item: u8| {
+                let log = log.clone();
+                async move { log.lock().unwrap().push(item) }
+            });
+
+            pool.rsvp().await.unwrap().discard();
+            pool.close().await.unwrap();
+            assert_eq!(*closed.lock().unwrap(), [1, 2]);
+        });
+    }
+
+    #[test]
+    fn async_on_return_runs_before_resources_re_enter_the_pool() {
+        block_on(async {
+            let pool = AsyncPool::new_with(vec![1u8, 2]).await.with_async_on_return(Inline, |item: u8| async move {
+                // Pretend the second one failed to roll back
+                (item == 1).then_some(item + 10)
+            });
+
+            drop(pool.rsvp().await.unwrap());
+            drop(pool.rsvp().await.unwrap());
+            assert_eq!(pool.len(), 1);
+            assert_eq!(*pool.rsvp().await.unwrap(), 11);
+        });
+    }
+}
@@ -0,0 +1,320 @@
+use std::{
+    any::Any,
+    cell::RefCell,
+    convert::TryFrom,
+    sync::{
+        atomic::{self, Ordering},
+        Arc, PoisonError,
+    },
+    time::Duration,
+};
+
+use crate::{
+    hooks::{self, Hooks},
+    pool::Entry,
+    sync::{AtomicBool, AtomicU64, AtomicUsize, Mutex, MutexGuard},
+    time::Instant,
+};
+
+thread_local! {
+    /// This thread's slot in each pool's cache, by the cache's ID
+    static SLOTS: RefCell<Vec<(u64, Arc<dyn Any + Send + Sync>)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// The ID of the next cache, so a thread's slots are never mixed up between pools
+static NEXT_ID: atomic::AtomicU64 = atomic::AtomicU64::new(0);
+
+/// The resources parked by the threads that last returned them, for `AsyncPool::with_thread_cache`
+///
+/// A parked resource is still counted as reserved by the pool, so returning it to its slot and
+/// reserving it again on the same thread never lock the pool. Waiters, `try_rsvp` on other threads,
+/// and `close` take parked resources back into the pool.
+pub(crate) struct HotCache<T> {
+    id: u64,
+    enabled: AtomicBool,
+    /// The number of queued waiters, which parked resources must go to instead.
+    pub(crate) waiting: AtomicUsize,
+    /// Whether reserved resources have changes from `map_resources` pending, to be applied once they
+    /// go back through the pool.
+    pub(crate) remapping: AtomicBool,
+    /// The number of calls waiting for resources to become idle, which parked resources never do.
+    pub(crate) watching: AtomicUsize,
+    /// The pool's generation, so resources from before `invalidate_all` are never parked.
+    pub(crate) generation: AtomicU64,
+    /// Whether the pool owns more than its maximum size, so returned resources must be retired.
+    pub(crate) oversized: AtomicBool,
+    /// The pool's maximum lifetime in nanoseconds, or `u64::MAX` if it has none.
+    max_lifetime: AtomicU64,
+    /// Every thread's slot, so parked resources can be taken back from other threads.
+    slots: Mutex<Vec<Arc<HotSlot<T>>>>,
+}
+
+/// A thread's slot in a cache
+struct HotSlot<T> {
+    parked: Mutex<Parked<T>>,
+}
+
+struct Parked<T> {
+    entry: Option<Entry<T>>,
+    /// The pool's hooks, so they can run without locking the pool.
+    hooks: Arc<Hooks<T>>,
+}
+
+impl<T: Send + 'static> HotCache<T> {
+    pub(crate) fn new() -> Self {
+        Self {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            enabled: AtomicBool::new(false),
+            waiting: AtomicUsize::new(0),
+            remapping: AtomicBool::new(false),
+            watching: AtomicUsize::new(0),
+            generation: AtomicU64::new(0),
+            oversized: AtomicBool::new(false),
+            max_lifetime: AtomicU64::new(u64::MAX),
+            slots: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub(crate) fn enable(&self) {
+        self.enabled.store(true, Ordering::SeqCst);
+    }
+
+    /// Stop parking resources, for a closing pool
+    pub(crate) fn disable(&self) {
+        self.enabled.store(false, Ordering::SeqCst);
+    }
+
+    /// Park `entry` in this thread's slot, running the release hook, or hand it back along with
+    /// whether the hook already ran
+    ///
+    /// `hooks` is only called for the thread's first resource.
+    pub(crate) fn park(
+        &self,
+        mut entry: Entry<T>,
+        reserved: Instant,
+        hooks: impl FnOnce() -> Arc<Hooks<T>>,
+    ) -> Result<(), (Entry<T>, bool)> {
+        // Stale resources go through the pool, to be retired
+        if !self.is_open() || self.is_stale(&entry) {
+            return Err((entry, false));
+        }
+
+        let slot = match self.slot(hooks) {
+            Some(slot) => slot,
+            None => return Err((entry, false)),
+        };
+
+        let hooks = {
+            let parked = lock(&slot.parked);
+            match parked.entry {
+                Some(_) => return Err((entry, false)),
+                None => parked.hooks.clone(),
+            }
+        };
+
+        // Don't hold the slot while running user code
+        let info = entry.info(Some(reserved));
+        hooks::run(&hooks.on_release, &mut entry.item, info);
+
+        {
+            let mut parked = lock(&slot.parked);
+            if parked.entry.is_some() || !self.is_open() {
+                return Err((entry, true));
+            }
+            parked.entry = Some(entry);
+        }
+
+        // A waiter, `close` or `invalidate_all` could have checked the slot while the resource was
+        // being parked
+        let mut parked = lock(&slot.parked);
+        if !self.is_open() || parked.entry.as_ref().is_some_and(|entry| self.is_stale(entry)) {
+            if let Some(entry) = parked.entry.take() {
+                return Err((entry, true));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Take the resource parked by this thread, along with the hooks to run on it
+    pub(crate) fn take(&self) -> Option<(Entry<T>, Arc<Hooks<T>>)> {
+        if !self.enabled.load(Ordering::SeqCst) {
+            return None;
+        }
+
+        let slot = self.find()?;
+        let mut parked = lock(&slot.parked);
+
+        Some((parked.entry.take()?, parked.hooks.clone()))
+    }
+
+    /// Take every parked resource back from the threads that parked them
+    pub(crate) fn take_all(&self) -> Vec<Entry<T>> {
+        let mut slots = lock(&self.slots);
+        let entries = slots.iter().filter_map(|slot| lock(&slot.parked).entry.take()).collect();
+
+        // Forget the slots of threads that have since exited, now their resources are taken back
+        slots.retain(|slot| Arc::strong_count(slot) > 1);
+        entries
+    }
+
+    /// Run `hooks` on parked resources from now on
+    pub(crate) fn set_hooks(&self, hooks: &Arc<Hooks<T>>) {
+        for slot in lock(&self.slots).iter() {
+            lock(&slot.parked).hooks = hooks.clone();
+        }
+    }
+
+    /// Whether resources may be parked
+    fn is_open(&self) -> bool {
+        self.enabled.load(Ordering::SeqCst)
+            && self.waiting.load(Ordering::SeqCst) == 0
+            && !self.remapping.load(Ordering::SeqCst)
+            && self.watching.load(Ordering::SeqCst) == 0
+            && !self.oversized.load(Ordering::SeqCst)
+    }
+
+    /// Whether `entry` is from before the pool was invalidated, or past its maximum lifetime
+    pub(crate) fn is_stale(&self, entry: &Entry<T>) -> bool {
+        let max_lifetime = self.max_lifetime.load(Ordering::SeqCst);
+
+        entry.generation < self.generation.load(Ordering::SeqCst)
+            || (max_lifetime != u64::MAX && entry.created.elapsed().as_nanos() >= u128::from(max_lifetime))
+    }
+
+    pub(crate) fn set_max_lifetime(&self, max_lifetime: Duration) {
+        let nanos = u64::try_from(max_lifetime.as_nanos()).unwrap_or(u64::MAX - 1);
+        self.max_lifetime.store(nanos, Ordering::SeqCst);
+    }
+
+    /// This thread's slot, if it has one yet
+    fn find(&self) -> Option<Arc<HotSlot<T>>> {
+        SLOTS.try_with(|slots| {
+            let slots = slots.borrow();
+            let (_, slot) = slots.iter().find(|(id, _)| *id == self.id)?;
+
+            slot.clone().downcast().ok()
+        }).ok().flatten()
+    }
+
+    /// This thread's slot, made with `hooks` if it doesn't have one yet
+    ///
+    /// Returns `None` if the thread is exiting.
+    fn slot(&self, hooks: impl FnOnce() -> Arc<Hooks<T>>) -> Option<Arc<HotSlot<T>>> {
+        if let Some(slot) = self.find() {
+            return Some(slot);
+        }
+
+        let slot = Arc::new(HotSlot {
+            parked: Mutex::new(Parked { entry: None, hooks: hooks() }),
+        });
+
+        SLOTS.try_with(|slots| {
+            let mut slots = slots.borrow_mut();
+
+            // Forget the slots of pools that have since been dropped
+            slots.retain(|(_, slot)| Arc::strong_count(slot) > 1);
+            slots.push((self.id, slot.clone()));
+        }).ok()?;
+
+        // Forget the slots of threads that have since exited, unless they left a resource for `take_all`
+        let mut slots = lock(&self.slots);
+        slots.retain(|slot| Arc::strong_count(slot) > 1 || lock(&slot.parked).entry.is_some());
+        slots.push(slot.clone());
+
+        Some(slot)
+    }
+}
+
+impl<T> Drop for HotCache<T> {
+    fn drop(&mut self) {
+        // Drop the parked resources with the pool, rather than whenever their threads exit
+        for slot in lock(&self.slots).iter() {
+            lock(&slot.parked).entry.take();
+        }
+    }
+}
+
+/// Lock part of the cache, which is never left half-updated
+fn lock<S>(mutex: &Mutex<S>) -> MutexGuard<'_, S> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{thread, time::Duration};
+
+    use futures_executor::block_on;
+
+    use crate::AsyncPool;
+
+    use super::*;
+
+    #[test]
+    fn parked_resources_are_taken_back_by_their_thread_first() {
+        let pool = block_on(AsyncPool::new_with(vec![1])).with_thread_cache();
+        let id = pool.try_rsvp().unwrap().id();
+
+        // Still counted as in use while parked
+        assert_eq!(pool.in_use(), 1);
+        assert_eq!(pool.try_rsvp().unwrap().id(), id);
+
+        let other = pool.clone();
+        thread::spawn(move || assert_eq!(other.try_rsvp().unwrap().id(), id)).join().unwrap();
+
+        let other = pool.clone();
+        let waiting = thread::spawn(move || block_on(other.rsvp()).map(|guard| guard.id()));
+        assert_eq!(waiting.join().unwrap(), Ok(id));
+        assert!(pool.try_rsvp().is_some());
+    }
+
+    #[test]
+    fn stale_resources_are_never_parked() {
+        let pool = block_on(AsyncPool::new_with(vec![1, 2])).with_thread_cache();
+        let guard = pool.try_rsvp().unwrap();
+        let id = guard.id();
+
+        pool.invalidate_all();
+        drop(guard);
+        assert!(pool.try_rsvp().is_none_or(|guard| guard.id() != id));
+        assert_eq!((pool.len(), pool.in_use()), (0, 0));
+
+        let pool = block_on(AsyncPool::new_with(vec![1])).with_thread_cache();
+        drop(pool.try_rsvp().unwrap());
+        let pool = pool.with_max_lifetime(Duration::from_millis(5));
+        thread::sleep(Duration::from_millis(10));
+        assert!(pool.try_rsvp().is_none());
+        assert_eq!(pool.len(), 0);
+
+        let pool = block_on(AsyncPool::new_with(vec![1, 2])).with_thread_cache();
+        let guards = (pool.try_rsvp().unwrap(), pool.try_rsvp().unwrap());
+        pool.resize(1);
+        drop(guards.0);
+        assert_eq!((pool.len(), pool.in_use()), (1, 1));
+    }
+    #[test]
+    fn slots_of_exited_threads_are_forgotten() {
+        let cache = Arc::new(HotCache::new());
+        cache.enable();
+
+        for i in 0..8 {
+            let cache = cache.clone();
+            thread::spawn(move || assert!(cache.park(Entry::new(i), Instant::now(), Default::default).is_ok()))
+                .join()
+                .unwrap();
+        }
+        // Kept until their parked resources are taken back
+        assert_eq!(lock(&cache.slots).len(), 8);
+        assert_eq!(cache.take_all().len(), 8);
+        assert!(lock(&cache.slots).is_empty());
+
+        for i in 0..8 {
+            let cache = cache.clone();
+            thread::spawn(move || {
+                assert!(cache.park(Entry::new(i), Instant::now(), Default::default).is_ok());
+                assert!(cache.take().is_some());
+            }).join().unwrap();
+        }
+        assert_eq!(lock(&cache.slots).len(), 1);
+    }
+}
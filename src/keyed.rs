@@ -0,0 +1,270 @@
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    time::Duration,
+};
+
+use crate::{time::Instant, AddError, AsyncPool, AsyncPoolGuard, PoolError};
+
+/// A collection of independent `AsyncPool`s, one per key
+///
+/// Useful for resources tied to something, like connections to a given host. Sub-pools are created
+/// the first time a key is used, and share the same configuration.
+///
+/// Cloning an `AsyncKeyedPool` is cheap, and gives another handle to the same sub-pools.
+pub struct AsyncKeyedPool<K, T: Send + 'static> {
+    shared: Arc<KeyedShared<K, T>>,
+}
+
+struct KeyedShared<K, T: Send + 'static> {
+    state: Mutex<KeyedState<K, T>>,
+}
+
+struct KeyedState<K, T: Send + 'static> {
+    pools: HashMap<K, SubPool<T>>,
+    /// The most resources each sub-pool may own, if limited.
+    max_size_per_key: Option<usize>,
+    /// How long `rsvp` waits before giving up, if at all.
+    timeout: Option<Duration>,
+}
+
+struct SubPool<T: Send + 'static> {
+    pool: AsyncPool<T>,
+    /// When the sub-pool was last handed out, for `evict_idle`.
+    used: Instant,
+}
+
+impl<K: Eq + Hash + Clone, T: Send + 'static> AsyncKeyedPool<K, T> {
+    /// Create a new `AsyncKeyedPool`
+    pub fn new() -> Self {
+        let state = KeyedState {
+            pools: HashMap::new(),
+            max_size_per_key: None,
+            timeout: None,
+        };
+
+        Self {
+            shared: Arc::new(KeyedShared { state: Mutex::new(state) }),
+        }
+    }
+
+    /// Limit each key's sub-pool to owning at most `max_size` resources
+    ///
+    /// Only applies to sub-pools created after this is set.
+    pub fn with_max_size_per_key(self, max_size: usize) -> Self {
+        self.lock().max_size_per_key = Some(max_size);
+        self
+    }
+
+    /// Make every `rsvp` on this pool give up after `timeout`
+    ///
+    /// Only applies to sub-pools created after this is set.
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        self.lock().timeout = Some(timeout);
+        self
+    }
+
+    /// Add `item` to the sub-pool for `key`
+    pub async fn add(&self, key: K, item: T) -> Result<(), AddError<T>> {
+        self.pool(key).add(item).await
+    }
+
+    /// Add `item` to the sub-pool for `key` if it has room right now, as with `AsyncPool::try_add`
+    pub fn try_add(&self, key: K, item: T) -> Result<(), AddError<T>> {
+        self.pool(key).try_add(item)
+    }
+
+    /// Await the next available resource for `key`
+    ///
+    /// If `key` has no resources yet, this waits for one to be added.
+    pub async fn rsvp(&self, key: &K) -> Result<AsyncPoolGuard<T>, PoolError> {
+        self.pool(key.clone()).rsvp().await
+    }
+
+    /// Reserve a resource for `key` only if one is idle right now, without awaiting
+    pub fn try_rsvp(&self, key: &K) -> Option<AsyncPoolGuard<T>> {
+        self.get(key)?.try_rsvp()
+    }
+
+    /// The sub-pool for `key`, creating it if needed
+    pub fn pool(&self, key: K) -> AsyncPool<T> {
+        let mut state = self.lock();
+        let (max_size, timeout) = (state.max_size_per_key, state.timeout);
+
+        let sub = state.pools
+            .entry(key)
+            .or_insert_with(|| {
+                let mut pool = AsyncPool::new();
+
+                if let Some(max_size) = max_size {
+                    pool = pool.with_max_size(max_size);
+                }
+
+                if let Some(timeout) = timeout {
+                    pool = pool.with_timeout(timeout);
+                }
+
+                SubPool { pool, used: Instant::now() }
+            });
+
+        sub.used = Instant::now();
+        sub.pool.clone()
+    }
+
+    /// The sub-pool for `key`, if it exists
+    pub fn get(&self, key: &K) -> Option<AsyncPool<T>> {
+        let mut state = self.lock();
+        let sub = state.pools.get_mut(key)?;
+
+        sub.used = Instant::now();
+        Some(sub.pool.clone())
+    }
+
+    /// Remove the sub-pool for `key`, returning it
+    ///
+    /// Its guards still return their resources to it, not to any sub-pool created for `key` later.
+    pub fn remove(&self, key: &K) -> Option<AsyncPool<T>> {
+        self.lock().pools.remove(key).map(|sub| sub.pool)
+    }
+
+    /// Remove every sub-pool that has been idle for at least `idle_for`, destroying its resources
+    ///
+    /// A sub-pool is idle while nothing is reserved from it, nobody is waiting on it, and no handle to
+    /// it is held outside this pool, so a `rsvp` that's about to wait keeps its sub-pool. It has been
+    /// idle since it was last handed out or had a resource returned. Returns how many keys were
+    /// evicted.
+    pub fn evict_idle(&self, idle_for: Duration) -> usize {
+        let evicted: Vec<_> = {
+            let pools = &mut self.lock().pools;
+            let keys: Vec<_> = pools.iter()
+                .filter(|(_, sub)| {
+                    let since = sub.pool.last_idle().map_or(sub.used, |idle| idle.max(sub.used));
+                    let idle = sub.pool.handles() == 1 && sub.pool.in_use() == 0 && sub.pool.waiting() == 0;
+
+                    idle && since.elapsed() >= idle_for
+                })
+                .map(|(key, _)| key.clone())
+                .collect();
+
+            keys.iter().filter_map(|key| pools.remove(key)).collect()
+        };
+
+        // Destroyed outside the lock, as their hooks may use this pool. Unlike `clear`, this also
+        // takes the resources set aside by the circuit breaker
+        for sub in &evicted {
+            sub.pool.invalidate_all();
+        }
+
+        evicted.len()
+    }
+
+    /// The keys that currently have a sub-pool
+    pub fn keys(&self) -> Vec<K> {
+        self.lock().pools.keys().cloned().collect()
+    }
+
+    /// The number of keys that currently have a sub-pool
+    pub fn len(&self) -> usize {
+        self.lock().pools.len()
+    }
+
+    /// Whether there are no sub-pools at all
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn lock(&self) -> MutexGuard<'_, KeyedState<K, T>> {
+        // Sub-pools keep their own state, so the map is always safe to use
+        self.shared.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<K, T: Send + 'static> Clone for AsyncKeyedPool<K, T> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<K: Eq + Hash + Clone, T: Send + 'static> Default for AsyncKeyedPool<K, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use futures_executor::block_on;
+
+    use crate::Hooks;
+
+    use super::*;
+
+    #[test]
+    fn keys_have_independent_pools() {
+        block_on(async {
+            let pool = AsyncKeyedPool::new().with_max_size_per_key(1);
+            pool.add("a", 1).await.unwrap();
+            pool.add("b", 2).await.unwrap();
+            assert_eq!(pool.try_add("a", 3).unwrap_err().kind(), PoolError::Full);
+
+            let a = pool.rsvp(&"a").await.unwrap();
+            assert_eq!(*a, 1);
+            assert!(pool.try_rsvp(&"a").is_none());
+            assert_eq!(*pool.try_rsvp(&"b").unwrap(), 2);
+        });
+    }
+
+    #[test]
+    fn evicts_only_idle_keys() {
+        block_on(async {
+            let pool = AsyncKeyedPool::new();
+            pool.add("a", 1).await.unwrap();
+            pool.add("b", 2).await.unwrap();
+
+            let _b = pool.rsvp(&"b").await.unwrap();
+            assert_eq!(pool.evict_idle(Duration::from_secs(60)), 0);
+            assert_eq!(pool.evict_idle(Duration::ZERO), 1);
+            assert_eq!(pool.keys(), vec!["b"]);
+        });
+    }
+
+    #[test]
+    fn evicted_keys_destroy_their_resources() {
+        block_on(async {
+            let pool = AsyncKeyedPool::new();
+            let destroyed = Arc::new(AtomicUsize::new(0));
+            let counted = destroyed.clone();
+            drop(pool.pool("a").with_hooks(Hooks::new().on_destroy(move |_, _| {
+                counted.fetch_add(1, Ordering::SeqCst);
+            })));
+
+            pool.add("a", 1).await.unwrap();
+            pool.add("a", 2).await.unwrap();
+            assert_eq!(pool.evict_idle(Duration::ZERO), 1);
+            assert_eq!(destroyed.load(Ordering::SeqCst), 2);
+        });
+    }
+
+    #[test]
+    fn waiting_rsvps_keep_their_key() {
+        block_on(async {
+            let pool = AsyncKeyedPool::new();
+            let waiting = pool.rsvp(&"a");
+            futures_util::pin_mut!(waiting);
+            assert!(futures_util::poll!(waiting.as_mut()).is_pending());
+
+            let held = pool.pool("b");
+            assert_eq!(pool.evict_idle(Duration::ZERO), 0);
+            drop(held);
+            assert_eq!(pool.evict_idle(Duration::ZERO), 1);
+
+            pool.add("a", 1).await.unwrap();
+            assert_eq!(*waiting.await.unwrap(), 1);
+        });
+    }
+}
@@ -0,0 +1,125 @@
+use std::{
+    backtrace::Backtrace,
+    collections::HashMap,
+    sync::Arc,
+    time::Duration,
+};
+
+use crate::time::Instant;
+
+/// A guard that has been held for longer than the pool's leak threshold, as reported to the callback
+/// given to `AsyncPool::with_leak_detection`
+#[derive(Debug, Clone)]
+pub struct Leak {
+    /// The ID of the held resource, as in `AsyncPool::resource_stats`
+    pub id: u64,
+    /// How long it had been held when it was reported
+    pub held: Duration,
+    /// What the reservation was labelled, by `rsvp_labeled`
+    pub label: Option<&'static str>,
+    /// Where the resource was reserved, if the pool was set up `with_leak_backtraces`
+    pub backtrace: Option<Arc<Backtrace>>,
+}
+
+/// The reservations being watched for leaks
+pub(crate) struct LeakDetector {
+    threshold: Duration,
+    pub(crate) on_leak: Arc<dyn Fn(&Leak) + Send + Sync>,
+    holds: HashMap<u64, Hold>,
+    /// When the reservations are next scanned, as `overdue` is called on every `rsvp`.
+    next_scan: Instant,
+}
+
+/// A reservation, as far as leak detection is concerned
+struct Hold {
+    since: Instant,
+    label: Option<&'static str>,
+    backtrace: Option<Arc<Backtrace>>,
+    /// Whether it was already reported, so it's only reported once.
+    reported: bool,
+}
+
+impl LeakDetector {
+    pub(crate) fn new(threshold: Duration, on_leak: impl Fn(&Leak) + Send + Sync + 'static) -> Self {
+        Self {
+            threshold,
+            on_leak: Arc::new(on_leak),
+            holds: HashMap::new(),
+            next_scan: Instant::now(),
+        }
+    }
+
+    /// Start watching the reservation of the resource with `id`
+    pub(crate) fn track(&mut self, id: u64, label: Option<&'static str>) {
+        self.holds.insert(id, Hold { since: Instant::now(), label, backtrace: None, reported: false });
+    }
+
+    /// Record where the resource with `id` was reserved, captured once the pool was unlocked
+    pub(crate) fn trace(&mut self, id: u64, backtrace: Arc<Backtrace>) {
+        if let Some(hold) = self.holds.get_mut(&id) {
+            hold.backtrace = Some(backtrace);
+        }
+    }
+
+    pub(crate) fn untrack(&mut self, id: u64) {
+        self.holds.remove(&id);
+    }
+
+    /// The reservations past the threshold that weren't reported yet
+    ///
+    /// They're scanned at most every tenth of the threshold, so a leak is reported a little late at
+    /// worst.
+    pub(crate) fn overdue(&mut self) -> Vec<Leak> {
+        let threshold = self.threshold;
+        let now = Instant::now();
+        if now < self.next_scan {
+            return Vec::new();
+        }
+        self.next_scan = now + threshold / 10;
+
+        self.holds
+            .iter_mut()
+            .filter(|(_, hold)| !hold.reported && hold.since.elapsed() >= threshold)
+            .map(|(&id, hold)| {
+                hold.reported = true;
+                Leak { id, held: hold.since.elapsed(), label: hold.label, backtrace: hold.backtrace.clone() }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Mutex, thread};
+
+    use futures_executor::block_on;
+
+    use crate::AsyncPool;
+
+    use super::*;
+
+    #[test]
+    fn guards_held_past_the_threshold_are_reported_once() {
+        block_on(async {
+            let leaks = Arc::new(Mutex::new(Vec::new()));
+            let reported = leaks.clone();
+            let pool = AsyncPool::new_with(vec![1, 2]).await
+                .with_leak_detection(Duration::from_millis(10), move |leak: &Leak| {
+                    reported.lock().unwrap().push((leak.label, leak.held, leak.backtrace.is_some()));
+                })
+                .with_leak_backtraces();
+
+            let _forgotten = pool.rsvp_labeled("forgotten").await.unwrap();
+            drop(pool.rsvp().await.unwrap());
+            assert!(leaks.lock().unwrap().is_empty());
+
+            thread::sleep(Duration::from_millis(20));
+            drop(pool.rsvp().await.unwrap());
+            drop(pool.rsvp().await.unwrap());
+
+            let leaks = leaks.lock().unwrap();
+            assert_eq!(leaks.len(), 1);
+            assert!(leaks[0].0 == Some("forgotten") && leaks[0].1 >= Duration::from_millis(20) && leaks[0].2);
+        });
+    }
+}
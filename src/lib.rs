@@ -1,95 +1,118 @@
-use std::{
-    ops::{Deref, DerefMut},
-    sync::RwLock,
-};
-
-use futures_channel::mpsc::{
-    unbounded, UnboundedReceiver, UnboundedSender,
-};
-use futures_util::SinkExt;
-use futures_util::stream::StreamExt;
-
-/// A collection for asynchronously reserving a resource from a pool
-pub struct AsyncPool<T: Send + 'static> {
-    /// The inbox, where dropped guards send to.
-    rx: RwLock<UnboundedReceiver<T>>,
-    /// The sender to clone for new guards.
-    tx: RwLock<UnboundedSender<T>>,
-}
-
-// TODO: Avoid move-by-value (Issue #1)
-/// The guard on a resource's borrow. Returns the value to the `AsyncPool` on drop.
-pub struct AsyncPoolGuard<T: Send + 'static> {
-    inner: Option<T>,
-    tx: Box<dyn FnMut(T)>,
-}
-
-impl<T: Send + 'static> AsyncPool<T> {
-    /// Create a new `AsyncPool`.
-    pub fn new() -> Self {
-        let (tx, rx) = unbounded();
-
-        Self {
-            rx: RwLock::new(rx),
-            tx: RwLock::new(tx),
-        }
-    }
-
-    /// Create a new `AsyncPool` using an initial set of resources
-    pub async fn new_with(mut initial_resources: Vec<T>) -> Self {
-        let new = Self::new();
-
-        for i in initial_resources.drain(..) {
-            new.add(i).await
-        }
-
-        new
-    }
-
-    /// Await the next available resource
-    pub async fn rsvp(&self) -> AsyncPoolGuard<T> {
-        let tx = self.tx.read().expect("Poisoned sender").clone();
-
-        let mut rx = self.rx.write().expect("Poisoned receiver");
-
-        AsyncPoolGuard {
-            inner: Some(rx.next().await.unwrap()),
-            tx: Box::new(move |x| tx.unbounded_send(x).expect("Pool was dropped before guard")),
-        }
-    }
-
-    /// Add `item` to the current `AsyncPool`.
-    pub async fn add(&self, item: T) {
-        // This unwrap is safe because we have a reference to the owner of the receiver
-        self.tx.write().expect("Poisoned sender").send(item).await.unwrap()
-    }
-}
-
-impl<T: Send + 'static> Drop for AsyncPoolGuard<T> {
-    fn drop(&mut self) {
-        if let Some(i) = self.inner.take() {
-            (self.tx)(i);
-        }
-    }
-}
-
-impl<T: Send + 'static> Deref for AsyncPoolGuard<T> {
-    type Target = T;
-
-    fn deref(&self) -> &Self::Target {
-        self.inner.as_ref()
-            .expect("Inner value dropped while Guard was active")
-    }
-}
-
-impl<T: Send + 'static> DerefMut for AsyncPoolGuard<T> {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        self.inner.as_mut()
-            .expect("Inner value dropped while Guard was active")
-    }
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+// Lets `Reusable` derives inside the crate name it as they would outside it
+#[cfg(all(test, feature = "derive"))]
+extern crate self as async_pool;
+
+/// Declare items that need `std`, leaving only the `alloc` ones without it
+macro_rules! cfg_std {
+    ($($item:item)*) => {
+        $(
+            #[cfg(feature = "std")]
+            $item
+        )*
+    };
 }
 
-#[cfg(test)]
-mod tests {
-    // TODO: Tests
+mod local;
+mod waiters;
+
+pub use local::{LocalAsyncPool, LocalAsyncPoolGuard, LocalRsvp};
+
+cfg_std! {
+    mod array;
+    mod breaker;
+    mod buf;
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    mod blocking;
+    mod builder;
+    #[cfg(any(feature = "bb8", feature = "deadpool"))]
+    mod compat;
+    mod config;
+    mod dynamic;
+    mod error;
+    mod events;
+    mod fallback;
+    #[cfg(test)]
+    mod fixtures;
+    mod guard;
+    mod guards;
+    mod hooks;
+    mod hot;
+    mod keyed;
+    mod leak;
+    mod limit;
+    mod managed;
+    #[cfg(feature = "metrics")]
+    mod metrics;
+    #[cfg(feature = "net")]
+    pub mod net;
+    mod permit;
+    mod pool;
+    mod retry;
+    mod reuse;
+    mod rsvp;
+    mod runtime;
+    mod scale;
+    mod scope;
+    #[cfg(feature = "tower")]
+    mod service;
+    mod sharded;
+    mod sink;
+    mod stats;
+    mod strategy;
+    mod sync;
+    pub mod testing;
+    mod tiered;
+    pub mod time;
+    mod trace;
+    mod trim;
+    mod wait;
+
+    pub use array::{ArrayPool, ArrayPoolGuard, ArrayRsvp};
+    pub use breaker::CircuitBreaker;
+    pub use buf::BufPool;
+    pub use builder::AsyncPoolBuilder;
+    pub use config::PoolConfig;
+    pub use dynamic::{AnyResource, DynPool};
+    pub use error::{AddError, IntoInnerError, PoisonPolicy, PoolError};
+    pub use events::{PoolEvent, PoolEvents};
+    pub use fallback::FallbackPool;
+    pub use guard::{AsyncPoolBatchGuard, AsyncPoolGuard, MappedAsyncPoolGuard};
+    pub use guards::Guards;
+    pub use hooks::{Hooks, ResourceInfo};
+    pub use keyed::AsyncKeyedPool;
+    pub use leak::Leak;
+    pub use limit::RateLimit;
+    pub use managed::{ManagedAsyncPool, ManagedError, Manager};
+    pub use permit::{Permit, PermitPool, Permits};
+    pub use pool::{AsyncPool, WeakPool};
+    pub use retry::RetryPolicy;
+    pub use reuse::Reusable;
+    pub use rsvp::Pool;
+    pub use runtime::{DefaultTimer, FuturesTimer, Sleep, Spawner, Task, Timer};
+    pub use scale::AutoScale;
+    pub use scope::{Scope, ScopedGuard};
+    pub use sharded::ShardedPool;
+    pub use sink::PoolSink;
+    pub use stats::{ResourceStats, WaitHistogram, WaiterInfo};
+    pub use strategy::Strategy;
+    pub use tiered::{TieredPool, Tiering};
+    pub use trim::Trim;
+    #[cfg(feature = "derive")]
+    pub use async_pool_derive::Reusable;
+    #[cfg(feature = "async-std")]
+    pub use runtime::AsyncStdRuntime;
+    #[cfg(feature = "bb8")]
+    pub use compat::Bb8Manager;
+    #[cfg(feature = "deadpool")]
+    pub use compat::DeadpoolManager;
+    #[cfg(feature = "smol")]
+    pub use runtime::SmolRuntime;
+    #[cfg(feature = "tokio")]
+    pub use runtime::TokioRuntime;
+    #[cfg(feature = "tower")]
+    pub use service::{PoolLayer, Pooled, PooledError, PooledService};
 }
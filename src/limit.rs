@@ -0,0 +1,118 @@
+use std::time::Duration;
+
+use crate::{time::Instant, PoolError};
+
+/// How many reservations a pool hands out per second, on top of how many resources it has
+///
+/// This is a token bucket: it starts full with `burst` tokens, refills at `per_second` tokens a
+/// second, and every reservation takes one. A reservation that finds the bucket empty waits for the
+/// next token, and that wait counts against the pool's timeout.
+///
+/// With the `serde` feature this can be deserialized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize), serde(deny_unknown_fields))]
+pub struct RateLimit {
+    /// How many reservations a second to allow, on average
+    pub per_second: u32,
+    /// How many reservations to allow at once, after a lull
+    pub burst: u32,
+}
+
+/// The tokens left under a `RateLimit`
+pub(crate) struct Bucket {
+    limit: RateLimit,
+    /// The tokens left, below zero once reservations are waiting for tokens to come in.
+    tokens: f64,
+    /// When `tokens` was last refilled.
+    updated: Instant,
+}
+
+impl RateLimit {
+    /// Allow `per_second` reservations a second, with bursts of up to `burst` at once
+    pub fn new(per_second: u32, burst: u32) -> Self {
+        Self { per_second, burst }
+    }
+}
+
+impl Bucket {
+    pub(crate) fn new(limit: RateLimit) -> Self {
+        Self {
+            limit,
+            tokens: f64::from(limit.burst.max(1)),
+            updated: Instant::now(),
+        }
+    }
+
+    pub(crate) fn limit(&self) -> RateLimit {
+        self.limit
+    }
+
+    /// Take a token, returning how long to wait before it's due
+    ///
+    /// Fails with `PoolError::Timeout`, taking nothing, if that's longer than `timeout`.
+    pub(crate) fn take(&mut self, timeout: Option<Duration>) -> Result<Duration, PoolError> {
+        self.refill();
+
+        let missing = (1.0 - self.tokens).max(0.0);
+        let delay = match self.limit.per_second {
+            0 => return Err(PoolError::Timeout),
+            rate => Duration::from_secs_f64(missing / f64::from(rate)),
+        };
+
+        if timeout.is_some_and(|timeout| delay > timeout) {
+            return Err(PoolError::Timeout);
+        }
+
+        self.tokens -= 1.0;
+        Ok(delay)
+    }
+
+    /// Take a token if one is ready now
+    pub(crate) fn try_take(&mut self) -> bool {
+        self.refill();
+
+        if self.tokens < 1.0 {
+            return false;
+        }
+
+        self.tokens -= 1.0;
+        true
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let earned = now.duration_since(self.updated).as_secs_f64() * f64::from(self.limit.per_second);
+
+        self.tokens = (self.tokens + earned).min(f64::from(self.limit.burst.max(1)));
+        self.updated = now;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_executor::block_on;
+
+    use crate::AsyncPool;
+
+    use super::*;
+
+    #[test]
+    fn reservations_past_the_burst_wait_for_tokens() {
+        block_on(async {
+            let pool = AsyncPool::new_with(vec![1]).await.with_rate_limit(RateLimit::new(50, 2));
+
+            drop(pool.rsvp().await.unwrap());
+            drop(pool.try_rsvp().unwrap());
+            assert!(pool.try_rsvp().is_none());
+
+            let started = Instant::now();
+            drop(pool.rsvp().await.unwrap());
+            assert!(started.elapsed() >= Duration::from_millis(15));
+
+            // A wait longer than the timeout fails straight away
+            let pool = pool.with_rate_limit(RateLimit::new(1, 1));
+            drop(pool.rsvp().await.unwrap());
+            assert_eq!(pool.rsvp_timeout(Duration::from_millis(10)).await.err(), Some(PoolError::Timeout));
+        });
+    }
+}
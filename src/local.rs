@@ -0,0 +1,219 @@
+use alloc::{
+    collections::VecDeque,
+    rc::{Rc, Weak},
+};
+use core::{
+    cell::RefCell,
+    future::Future,
+    iter::FromIterator,
+    ops::{Deref, DerefMut},
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::waiters::Waiters;
+
+/// A pool for resources that can't leave their thread, like `Rc`s or GUI handles
+///
+/// This is a pared-down `AsyncPool` without the `Send` bound, for single-threaded executors such as
+/// a tokio `LocalSet` or the browser's. Nothing is locked: the pool, its guards and its futures all
+/// stay on the thread that created them.
+///
+/// Cloning a `LocalAsyncPool` is cheap, and gives another handle to the same resources.
+pub struct LocalAsyncPool<T> {
+    shared: Rc<RefCell<LocalState<T>>>,
+}
+
+/// The guard on a resource from a `LocalAsyncPool`. Returns it to the pool on drop.
+///
+/// Like `AsyncPoolGuard`, it doesn't keep the pool alive.
+pub struct LocalAsyncPoolGuard<T> {
+    item: Option<T>,
+    shared: Weak<RefCell<LocalState<T>>>,
+}
+
+/// The future of `LocalAsyncPool::rsvp`
+pub struct LocalRsvp<'a, T> {
+    pool: &'a LocalAsyncPool<T>,
+    /// This waiter's place in the queue, once it's waiting.
+    ticket: Option<u64>,
+}
+
+struct LocalState<T> {
+    /// The resources that aren't reserved, the first being the next handed out.
+    idle: VecDeque<T>,
+    /// The number of resources owned by the pool, reserved or not.
+    size: usize,
+    /// The waiting `rsvp`s.
+    waiters: Waiters,
+}
+
+impl<T> LocalAsyncPool<T> {
+    /// Create an empty `LocalAsyncPool`
+    pub fn new() -> Self {
+        Self {
+            shared: Rc::new(RefCell::new(LocalState {
+                idle: VecDeque::new(),
+                size: 0,
+                waiters: Waiters::with_capacity(0),
+            })),
+        }
+    }
+
+    /// Add `item` to the pool
+    pub fn add(&self, item: T) {
+        let mut state = self.shared.borrow_mut();
+        state.size += 1;
+        state.put_back(item);
+    }
+
+    /// Await the next available resource
+    pub fn rsvp(&self) -> LocalRsvp<'_, T> {
+        LocalRsvp { pool: self, ticket: None }
+    }
+
+    /// Reserve a resource only if one is idle right now, without awaiting
+    pub fn try_rsvp(&self) -> Option<LocalAsyncPoolGuard<T>> {
+        let item = self.shared.borrow_mut().idle.pop_front()?;
+
+        Some(self.guard(item))
+    }
+
+    /// The number of resources owned by the pool, reserved or not
+    pub fn len(&self) -> usize {
+        self.shared.borrow().size
+    }
+
+    /// Whether the pool owns no resources at all
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The number of unreserved resources
+    pub fn available(&self) -> usize {
+        self.shared.borrow().idle.len()
+    }
+
+    /// The number of `rsvp` calls waiting for a resource
+    pub fn waiting(&self) -> usize {
+        self.shared.borrow().waiters.len()
+    }
+
+    fn guard(&self, item: T) -> LocalAsyncPoolGuard<T> {
+        LocalAsyncPoolGuard { item: Some(item), shared: Rc::downgrade(&self.shared) }
+    }
+}
+
+impl<T> LocalState<T> {
+    /// Make `item` idle, waking the first waiter to take it
+    fn put_back(&mut self, item: T) {
+        self.idle.push_back(item);
+        self.waiters.wake_next();
+    }
+}
+
+impl<T> Default for LocalAsyncPool<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Clone for LocalAsyncPool<T> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T> FromIterator<T> for LocalAsyncPool<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let new = Self::new();
+        iter.into_iter().for_each(|item| new.add(item));
+
+        new
+    }
+}
+
+impl<'a, T> Future for LocalRsvp<'a, T> {
+    type Output = LocalAsyncPoolGuard<T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let pool = self.pool;
+        let mut state = pool.shared.borrow_mut();
+        let item = state.idle.pop_front();
+        let polled = state.waiters.poll(&mut self.ticket, cx, item);
+        drop(state);
+
+        polled.map(|item| pool.guard(item))
+    }
+}
+
+impl<T> Drop for LocalRsvp<'_, T> {
+    fn drop(&mut self) {
+        if self.ticket.is_some() {
+            let mut state = self.pool.shared.borrow_mut();
+            let available = !state.idle.is_empty();
+            state.waiters.cancel(self.ticket, available);
+        }
+    }
+}
+
+impl<T> LocalAsyncPoolGuard<T> {
+    /// Take the resource out of the pool for good, shrinking the pool
+    pub fn take(mut self) -> T {
+        let item = self.item.take().expect("Inner value dropped while Guard was active");
+
+        if let Some(shared) = self.shared.upgrade() {
+            shared.borrow_mut().size -= 1;
+        }
+
+        item
+    }
+}
+
+impl<T> Drop for LocalAsyncPoolGuard<T> {
+    fn drop(&mut self) {
+        if let (Some(item), Some(shared)) = (self.item.take(), self.shared.upgrade()) {
+            shared.borrow_mut().put_back(item);
+        }
+    }
+}
+
+impl<T> Deref for LocalAsyncPoolGuard<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        self.item.as_ref().expect("Inner value dropped while Guard was active")
+    }
+}
+
+impl<T> DerefMut for LocalAsyncPoolGuard<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.item.as_mut().expect("Inner value dropped while Guard was active")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_executor::block_on;
+
+    use super::*;
+
+    #[test]
+    fn local_pools_hold_resources_that_are_not_send() {
+        block_on(async {
+            let pool: LocalAsyncPool<_> = vec![Rc::new(1), Rc::new(2)].into_iter().collect();
+            let one = pool.rsvp().await;
+            let _two = pool.try_rsvp().unwrap();
+
+            let mut waiting = Box::pin(pool.rsvp());
+            assert!(futures_util::poll!(waiting.as_mut()).is_pending());
+            assert_eq!(pool.waiting(), 1);
+
+            drop(one);
+            assert_eq!(*waiting.await.take(), 1);
+            assert_eq!((pool.len(), pool.available()), (1, 0));
+        });
+    }
+}
@@ -0,0 +1,746 @@
+use std::{
+    error::Error,
+    fmt,
+    future::Future,
+    sync::Arc,
+    time::Duration,
+};
+
+use futures_util::stream::{self, StreamExt};
+
+use crate::{
+    time::Instant,
+    trace::event, AsyncPool, AsyncPoolGuard, CircuitBreaker, Hooks, Leak, PoisonPolicy, PoolError, PoolEvent,
+    RateLimit, RetryPolicy, Spawner, Strategy,
+};
+
+/// A factory for the resources of a `ManagedAsyncPool`
+pub trait Manager: Send + Sync + 'static {
+    /// The resource this manager creates
+    type Resource: Send + 'static;
+    /// The error returned when a resource could not be created
+    type Error;
+
+    /// Create a new resource
+    fn create(&self) -> impl Future<Output = Result<Self::Resource, Self::Error>> + Send;
+
+    /// Check that an idle resource is still usable before it's handed out
+    ///
+    /// Resources that fail validation are dropped, and the pool moves on to the next one. By default,
+    /// every resource is considered valid.
+    fn validate(&self, _resource: &mut Self::Resource) -> impl Future<Output = bool> + Send {
+        async { true }
+    }
+}
+
+/// An `AsyncPool` that creates its resources on demand using a `Manager`
+///
+/// Like `AsyncPool`, cloning is cheap and the clones share their resources and manager.
+pub struct ManagedAsyncPool<M: Manager> {
+    /// The pool holding the created resources.
+    pool: AsyncPool<M::Resource>,
+    /// The factory for new resources.
+    manager: Arc<M>,
+    /// How to retry failed creations, if at all.
+    retry: Option<RetryPolicy>,
+    /// How many unreserved resources `replenish` keeps ready.
+    min_idle: usize,
+    /// How many resources `keep_size` replaces dropped ones to keep.
+    target_size: usize,
+    /// How many resources `warm_up` creates at once.
+    warm_up_concurrency: usize,
+}
+
+/// The error returned when a `ManagedAsyncPool` could not reserve a resource
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ManagedError<E> {
+    /// The pool could not reserve a resource
+    Pool(PoolError),
+    /// The manager failed to create a new resource
+    Create(E),
+}
+
+/// A slot claimed for a resource under creation. Released unless the creation succeeds.
+pub(crate) struct PendingSlot<'a, T: Send + 'static> {
+    pub(crate) pool: Option<&'a AsyncPool<T>>,
+}
+
+impl<M: Manager> ManagedAsyncPool<M> {
+    /// Create a new, empty `ManagedAsyncPool`
+    pub fn new(manager: M) -> Self {
+        Self {
+            pool: AsyncPool::new(),
+            manager: Arc::new(manager),
+            retry: None,
+            min_idle: 0,
+            target_size: 0,
+            warm_up_concurrency: 4,
+        }
+    }
+
+    /// Create a `ManagedAsyncPool` around an already configured pool
+    pub(crate) fn with_pool(manager: M, pool: AsyncPool<M::Resource>) -> Self {
+        Self { pool, ..Self::new(manager) }
+    }
+
+    /// Limit the pool to owning at most `max_size` resources
+    pub fn with_max_size(mut self, max_size: usize) -> Self {
+        self.pool = self.pool.with_max_size(max_size);
+        self
+    }
+
+    /// Change the pool's maximum size while it's in use, as with `AsyncPool::resize`
+    ///
+    /// Growing lets waiting `rsvp` calls create resources right away; call `replenish` to create idle
+    /// ones ahead of time.
+    pub fn resize(&self, max_size: usize) -> usize {
+        self.pool.resize(max_size)
+    }
+
+    /// Keep at least `min_idle` unreserved resources ready whenever `replenish` is called
+    pub fn with_min_idle(mut self, min_idle: usize) -> Self {
+        self.min_idle = min_idle;
+        self
+    }
+
+    /// How many unreserved resources `replenish` keeps ready
+    pub fn min_idle(&self) -> usize {
+        self.min_idle
+    }
+
+    /// Replace the resources the pool drops to keep it owning at least `target_size`, once `keep_size`
+    /// or `maintain` runs
+    pub fn with_target_size(mut self, target_size: usize) -> Self {
+        self.target_size = target_size;
+        self
+    }
+
+    /// How many resources `keep_size` keeps the pool owning
+    pub fn target_size(&self) -> usize {
+        self.target_size
+    }
+
+    /// Let `warm_up` create up to `concurrency` resources at once, instead of the default of 4
+    pub fn with_warm_up_concurrency(mut self, concurrency: usize) -> Self {
+        self.warm_up_concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Make every `rsvp` that waits for a returned resource give up after `timeout`
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.pool = self.pool.with_timeout(timeout);
+        self
+    }
+
+    /// Drop resources that have been idle for longer than `idle_timeout`, creating fresh ones instead
+    pub fn with_idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.pool = self.pool.with_idle_timeout(idle_timeout);
+        self
+    }
+
+    /// Handle a panic that poisoned the pool's lock according to `policy`
+    pub fn with_poison_policy(mut self, policy: PoisonPolicy) -> Self {
+        self.pool = self.pool.with_poison_policy(policy);
+        self
+    }
+
+    /// Close each resource the pool drops with `close`, as with `AsyncPool::with_async_destroy`
+    pub fn with_async_destroy<F>(
+        mut self,
+        spawner: impl Spawner + Send + Sync + 'static,
+        close: impl Fn(M::Resource) -> F + Send + Sync + 'static,
+    ) -> Self
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.pool = self.pool.with_async_destroy(spawner, close);
+        self
+    }
+
+    /// Await `on_return` on each returned resource before it re-enters the pool, as with
+    /// `AsyncPool::with_async_on_return`
+    pub fn with_async_on_return<F>(
+        mut self,
+        spawner: impl Spawner + Send + Sync + 'static,
+        on_return: impl Fn(M::Resource) -> F + Send + Sync + 'static,
+    ) -> Self
+    where
+        F: Future<Output = Option<M::Resource>> + Send + 'static,
+    {
+        self.pool = self.pool.with_async_on_return(spawner, on_return);
+        self
+    }
+
+    /// Set aside resources that keep failing, according to `breaker`
+    pub fn with_circuit_breaker(mut self, breaker: CircuitBreaker) -> Self {
+        self.pool = self.pool.with_circuit_breaker(breaker);
+        self
+    }
+
+    /// Report every guard held for longer than `threshold` to `on_leak`, as with
+    /// `AsyncPool::with_leak_detection`
+    pub fn with_leak_detection(mut self, threshold: Duration, on_leak: impl Fn(&Leak) + Send + Sync + 'static) -> Self {
+        self.pool = self.pool.with_leak_detection(threshold, on_leak);
+        self
+    }
+
+    /// Capture where each guard is reserved, for leak reports, as with `AsyncPool::with_leak_backtraces`
+    pub fn with_leak_backtraces(mut self) -> Self {
+        self.pool = self.pool.with_leak_backtraces();
+        self
+    }
+
+    /// Report the pool's metrics to the `metrics` facade, as with `AsyncPool::with_metrics`
+    ///
+    /// Failures to create a resource are counted too, including each one that's retried.
+    #[cfg(feature = "metrics")]
+    pub fn with_metrics(mut self, name: &'static str) -> Self {
+        self.pool = self.pool.with_metrics(name);
+        self
+    }
+
+    /// Limit how many reservations are handed out a second, as with `AsyncPool::with_rate_limit`
+    pub fn with_rate_limit(mut self, limit: RateLimit) -> Self {
+        self.pool = self.pool.with_rate_limit(limit);
+        self
+    }
+
+    /// Hand out idle resources in the order given by `strategy`
+    pub fn with_strategy(mut self, strategy: Strategy) -> Self {
+        self.pool = self.pool.with_strategy(strategy);
+        self
+    }
+
+    /// Retire resources once they're older than `max_lifetime`, creating fresh ones instead
+    pub fn with_max_lifetime(mut self, max_lifetime: Duration) -> Self {
+        self.pool = self.pool.with_max_lifetime(max_lifetime);
+        self
+    }
+
+    /// Call `on_return` on each resource when its guard drops, before it re-enters the pool
+    pub fn with_on_return(mut self, on_return: impl Fn(&mut M::Resource) + Send + Sync + 'static) -> Self {
+        self.pool = self.pool.with_on_return(on_return);
+        self
+    }
+
+    /// Run `hooks` at each point in the resources' lives, replacing any hooks set before
+    pub fn with_hooks(mut self, hooks: Hooks<M::Resource>) -> Self {
+        self.pool = self.pool.with_hooks(hooks);
+        self
+    }
+
+    /// Retry failed creations according to `retry`, instead of failing right away
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = Some(retry);
+        self
+    }
+
+    /// The pool holding the created resources
+    pub fn pool(&self) -> &AsyncPool<M::Resource> {
+        &self.pool
+    }
+
+    /// The factory for new resources
+    pub fn manager(&self) -> &M {
+        &self.manager
+    }
+
+    /// Reserve an idle resource, creating a new one if none are idle and the pool is under capacity
+    ///
+    /// If the pool is at capacity, this awaits either a returned resource or a freed slot to create
+    /// one in. Idle resources that fail `Manager::validate` are dropped, freeing their slot, and the
+    /// next one is tried, so a timeout applies to each wait rather than to the whole call.
+    pub async fn rsvp(&self) -> Result<AsyncPoolGuard<M::Resource>, ManagedError<M::Error>> {
+        let started = Instant::now();
+
+        loop {
+            let mut guard = match self.pool.rsvp_or_claim().await? {
+                Some(guard) => guard,
+                None => {
+                    let slot = PendingSlot { pool: Some(&self.pool) };
+                    let item = self.create().await.map_err(ManagedError::Create)?;
+                    slot.fill();
+
+                    return Ok(self.pool.guard(item).waited_for(started.elapsed()));
+                }
+            };
+
+            if self.manager.validate(&mut guard).await {
+                return Ok(guard.waited_for(started.elapsed()));
+            }
+
+            event!("Discarding a resource that failed validation");
+            guard.discard();
+        }
+    }
+
+    /// Create resources until at least `min_idle` are unreserved, returning how many were created
+    ///
+    /// Stops early once the pool is at capacity, or with the error of the first failed creation.
+    pub async fn replenish(&self) -> Result<usize, ManagedError<M::Error>> {
+        let mut created = 0;
+
+        while self.pool.available() < self.min_idle && self.create_idle().await? {
+            created += 1;
+        }
+
+        Ok(created)
+    }
+
+    /// Create resources until the pool owns at least its target size, returning how many were created
+    ///
+    /// Stops early once the pool is at capacity, or with the error of the first failed creation.
+    pub async fn fill_to_target(&self) -> Result<usize, ManagedError<M::Error>> {
+        let mut created = 0;
+
+        while self.pool.len() < self.target_size && self.create_idle().await? {
+            created += 1;
+        }
+
+        Ok(created)
+    }
+
+    /// Create a replacement for each resource the pool drops, whether it was discarded, evicted or
+    /// taken, to keep the pool at its target size
+    ///
+    /// This fills the pool to its target size first, then again each time it shrinks below it. It
+    /// runs until the pool is closed or dropped, and doesn't keep it alive. A failed creation is tried
+    /// again the next time the pool shrinks, or by `maintain`.
+    pub async fn keep_size(self) {
+        let Self { pool, manager, retry, min_idle, target_size, warm_up_concurrency } = self;
+        let mut events = pool.events();
+        // Only hold on to the pool while filling it, so it can still be dropped
+        let weak = pool.downgrade();
+        drop(pool);
+
+        loop {
+            let filled = match weak.upgrade() {
+                Some(pool) if !pool.is_closed() => {
+                    let (manager, retry) = (manager.clone(), retry.clone());
+                    let this = Self { pool, manager, retry, min_idle, target_size, warm_up_concurrency };
+                    this.fill_to_target().await.is_ok()
+                }
+                _ => return,
+            };
+
+            if !filled {
+                // Skip the shrinking of the failed creation's own slot
+                events = match weak.upgrade() {
+                    Some(pool) => pool.events(),
+                    None => return,
+                };
+            }
+
+            loop {
+                match events.next().await {
+                    Some(PoolEvent::Resized { size }) if size < target_size => break,
+                    Some(_) => {}
+                    None => return,
+                }
+            }
+        }
+    }
+
+    /// Run `keep_size` in the background on `spawner`
+    pub fn spawn_keep_size(&self, spawner: &impl Spawner)
+    where
+        M::Error: Send,
+    {
+        spawner.spawn(Box::pin(self.clone().keep_size()));
+    }
+
+    /// Create up to `n` unreserved resources ahead of time, returning how many were created
+    ///
+    /// Creations run concurrently, up to the warm-up concurrency, so slow handshakes don't add up.
+    /// Stops early once the pool is at capacity, or with the error of the first failed creation.
+    pub async fn warm_up(&self, n: usize) -> Result<usize, ManagedError<M::Error>> {
+        let mut creations = stream::iter(0..n)
+            .map(|_| self.create_idle())
+            .buffer_unordered(self.warm_up_concurrency);
+        let mut created = 0;
+
+        while let Some(result) = creations.next().await {
+            if result? {
+                created += 1;
+            }
+        }
+
+        Ok(created)
+    }
+
+    /// Create an unreserved resource, if the pool has room for it. Returns whether it did.
+    async fn create_idle(&self) -> Result<bool, ManagedError<M::Error>> {
+        if !self.pool.claim_slot() {
+            return Ok(false);
+        }
+
+        let slot = PendingSlot { pool: Some(&self.pool) };
+        let item = self.create().await.map_err(ManagedError::Create)?;
+        slot.fill();
+
+        self.pool.fill_slot(item);
+        Ok(true)
+    }
+
+    /// Keep the pool healthy every `interval`, until the pool is closed
+    ///
+    /// Each round evicts stale resources, drops idle resources that fail `Manager::validate`, and tops
+    /// the pool back up to `min_idle` and its target size. Failed creations are tried again the next
+    /// round. Spawn the returned future on any executor to enforce all this in the background, rather
+    /// than only as resources are handed out.
+    pub async fn maintain(self, interval: Duration) {
+        while !self.pool.is_closed() {
+            self.pool.sleep(interval).await;
+
+            self.pool.evict_stale();
+            self.check_idle().await;
+            let _ = self.replenish().await;
+            let _ = self.fill_to_target().await;
+        }
+    }
+
+    /// Run `maintain` in the background on `spawner`
+    pub fn spawn_maintenance(&self, spawner: &impl Spawner, interval: Duration)
+    where
+        M::Error: Send,
+    {
+        spawner.spawn(Box::pin(self.clone().maintain(interval)));
+    }
+
+    /// Validate each idle resource, dropping those that fail
+    async fn check_idle(&self) {
+        for id in self.pool.idle_ids() {
+            if let Some(mut checking) = self.pool.check_idle(id) {
+                if !self.manager.validate(checking.item_mut()).await {
+                    checking.fail();
+                }
+            }
+        }
+    }
+
+    /// Create a resource with the manager, retrying per the retry policy. Returns the last error.
+    async fn create(&self) -> Result<M::Resource, M::Error> {
+        let mut retry = 0;
+
+        loop {
+            let err = match self.manager.create().await {
+                Ok(item) => {
+                    event!(retries = retry, "Created a resource");
+                    return Ok(item);
+                }
+                Err(e) => e,
+            };
+            self.pool.failed_to_create();
+
+            match &self.retry {
+                Some(policy) if retry < policy.max_retries => {
+                    event!(retries = retry, "Failed to create a resource, retrying");
+                    self.pool.sleep(policy.delay(retry)).await;
+                    retry += 1;
+                }
+                _ => {
+                    event!(retries = retry, "Failed to create a resource");
+                    return Err(err);
+                }
+            }
+        }
+    }
+}
+
+impl<M: Manager> Clone for ManagedAsyncPool<M> {
+    fn clone(&self) -> Self {
+        Self {
+            pool: self.pool.clone(),
+            manager: self.manager.clone(),
+            retry: self.retry.clone(),
+            min_idle: self.min_idle,
+            target_size: self.target_size,
+            warm_up_concurrency: self.warm_up_concurrency,
+        }
+    }
+}
+
+impl<T: Send + 'static> PendingSlot<'_, T> {
+    /// Keep the slot, as its resource was created
+    pub(crate) fn fill(mut self) {
+        self.pool = None;
+    }
+}
+
+impl<T: Send + 'static> Drop for PendingSlot<'_, T> {
+    fn drop(&mut self) {
+        if let Some(pool) = self.pool.take() {
+            pool.release_slot();
+        }
+    }
+}
+
+impl<E> From<PoolError> for ManagedError<E> {
+    fn from(e: PoolError) -> Self {
+        ManagedError::Pool(e)
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for ManagedError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ManagedError::Pool(e) => e.fmt(f),
+            ManagedError::Create(e) => write!(f, "Failed to create a resource: {}", e),
+        }
+    }
+}
+
+impl<E: Error + 'static> Error for ManagedError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ManagedError::Pool(e) => Some(e),
+            ManagedError::Create(e) => Some(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use futures_executor::block_on;
+    use futures_timer::Delay;
+
+    use crate::fixtures::Counter;
+
+    use super::*;
+
+    /// Fails until it has been asked `failures` times
+    struct Flaky {
+        attempts: AtomicUsize,
+        failures: usize,
+    }
+
+    impl Manager for Flaky {
+        type Resource = ();
+        type Error = usize;
+
+        async fn create(&self) -> Result<(), usize> {
+            let attempt = self.attempts.fetch_add(1, Ordering::SeqCst);
+
+            if attempt < self.failures { Err(attempt) } else { Ok(()) }
+        }
+    }
+
+    #[test]
+    fn creates_only_when_empty_and_under_capacity() {
+        block_on(async {
+            let pool = ManagedAsyncPool::new(Counter::default())
+                .with_max_size(2)
+                .with_timeout(Duration::from_millis(10));
+
+            let first = pool.rsvp().await.unwrap();
+            let second = pool.rsvp().await.unwrap();
+            assert_eq!((*first, *second), (0, 1));
+            assert_eq!(pool.rsvp().await.err(), Some(ManagedError::Pool(PoolError::Timeout)));
+
+            drop(first);
+            assert_eq!(*pool.rsvp().await.unwrap(), 0);
+            assert_eq!(pool.manager().created(), 2);
+        });
+    }
+
+    #[test]
+    fn stale_resources_are_replaced() {
+        block_on(async {
+            let pool = ManagedAsyncPool::new(Counter::default())
+                .with_max_size(1)
+                .with_idle_timeout(Duration::from_millis(10));
+
+            drop(pool.rsvp().await.unwrap());
+            Delay::new(Duration::from_millis(20)).await;
+            assert_eq!(*pool.rsvp().await.unwrap(), 1);
+        });
+    }
+
+    #[test]
+    fn waiters_create_in_freed_slots() {
+        block_on(async {
+            let pool = ManagedAsyncPool::new(Counter::default()).with_max_size(1);
+            let guard = pool.rsvp().await.unwrap();
+
+            let mut waiting = Box::pin(pool.rsvp());
+            assert!(futures_util::poll!(waiting.as_mut()).is_pending());
+
+            guard.discard();
+            assert_eq!(*waiting.await.unwrap(), 1);
+        });
+    }
+
+    #[test]
+    fn replenish_keeps_min_idle_ready() {
+        block_on(async {
+            let pool = ManagedAsyncPool::new(Counter::default())
+                .with_max_size(3)
+                .with_min_idle(2);
+
+            assert_eq!(pool.replenish().await, Ok(2));
+            let _guard = pool.rsvp().await.unwrap();
+            assert_eq!(pool.replenish().await, Ok(1));
+            assert_eq!(pool.replenish().await, Ok(0));
+            assert_eq!(pool.pool().available(), 2);
+
+            let _guard = pool.rsvp().await.unwrap();
+            assert_eq!(pool.replenish().await, Ok(0));
+        });
+    }
+
+    /// Takes a while to create each resource, keeping track of how many it created at once
+    struct Slow {
+        creating: AtomicUsize,
+        peak: AtomicUsize,
+    }
+
+    impl Manager for Slow {
+        type Resource = ();
+        type Error = ();
+
+        async fn create(&self) -> Result<(), ()> {
+            let creating = self.creating.fetch_add(1, Ordering::SeqCst) + 1;
+            self.peak.fetch_max(creating, Ordering::SeqCst);
+
+            Delay::new(Duration::from_millis(5)).await;
+            self.creating.fetch_sub(1, Ordering::SeqCst);
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn warm_up_creates_concurrently_up_to_the_limit() {
+        block_on(async {
+            let pool = ManagedAsyncPool::new(Slow { creating: AtomicUsize::new(0), peak: AtomicUsize::new(0) })
+                .with_max_size(5)
+                .with_warm_up_concurrency(2);
+
+            assert_eq!(pool.warm_up(6).await, Ok(5));
+            assert_eq!(pool.pool().available(), 5);
+            assert_eq!(pool.manager().peak.load(Ordering::SeqCst), 2);
+        });
+    }
+
+    #[test]
+    fn failed_creation_releases_its_slot() {
+        block_on(async {
+            let pool = ManagedAsyncPool::new(Flaky { attempts: AtomicUsize::new(0), failures: 1 })
+                .with_max_size(1);
+
+            assert_eq!(pool.rsvp().await.err(), Some(ManagedError::Create(0)));
+            assert!(pool.rsvp().await.is_ok());
+        });
+    }
+
+    #[test]
+    fn retries_failed_creations() {
+        block_on(async {
+            let retry = RetryPolicy { initial_delay: Duration::from_millis(1), ..RetryPolicy::new(2) };
+
+            let pool = ManagedAsyncPool::new(Flaky { attempts: AtomicUsize::new(0), failures: 2 })
+                .with_retry(retry.clone());
+            assert!(pool.rsvp().await.is_ok());
+
+            let pool = ManagedAsyncPool::new(Flaky { attempts: AtomicUsize::new(0), failures: 3 })
+                .with_retry(retry);
+            assert_eq!(pool.rsvp().await.err(), Some(ManagedError::Create(2)));
+        });
+    }
+
+    /// Hands out fresh numbers, and rejects odd ones once they've been idle
+    struct Evens(AtomicUsize);
+
+    impl Manager for Evens {
+        type Resource = usize;
+        type Error = ();
+
+        async fn create(&self) -> Result<usize, ()> {
+            Ok(self.0.fetch_add(1, Ordering::SeqCst))
+        }
+
+        async fn validate(&self, resource: &mut usize) -> bool {
+            resource.is_multiple_of(2)
+        }
+    }
+
+    #[test]
+    fn maintain_replaces_invalid_resources() {
+        block_on(async {
+            let pool = ManagedAsyncPool::new(Evens(AtomicUsize::new(0))).with_min_idle(2);
+            let maintain = pool.clone().maintain(Duration::from_millis(1));
+            futures_util::pin_mut!(maintain);
+
+            assert!(futures_util::poll!(maintain.as_mut()).is_pending());
+            Delay::new(Duration::from_millis(5)).await;
+            assert!(futures_util::poll!(maintain.as_mut()).is_pending());
+            Delay::new(Duration::from_millis(5)).await;
+            assert!(futures_util::poll!(maintain.as_mut()).is_pending());
+
+            // `0` and `1` were created, then `1` failed validation and was replaced by `2`
+            assert_eq!(pool.pool().available(), 2);
+            assert_eq!(pool.manager().0.load(Ordering::SeqCst), 3);
+        });
+    }
+
+    #[test]
+    fn validating_idle_resources_leaves_them_in_place_and_unreserved() {
+        block_on(async {
+            let acquired = Arc::new(AtomicUsize::new(0));
+            let counted = acquired.clone();
+            let pool = ManagedAsyncPool::new(Evens(AtomicUsize::new(0)))
+                .with_strategy(Strategy::Lifo)
+                .with_hooks(Hooks::new().on_acquire(move |_, _| {
+                    counted.fetch_add(1, Ordering::SeqCst);
+                }));
+            for n in [2, 3, 4] {
+                pool.pool().add(n).await.unwrap();
+            }
+
+            pool.check_idle().await;
+            assert_eq!(acquired.load(Ordering::SeqCst), 0);
+            assert!(pool.pool().resource_stats().iter().all(|stats| stats.checkouts == 0));
+            assert_eq!(pool.pool().drain(), vec![4, 2]);
+        });
+    }
+
+    #[test]
+    fn keep_size_replaces_dropped_resources() {
+        block_on(async {
+            let pool = ManagedAsyncPool::new(Counter::default())
+                .with_max_size(3)
+                .with_target_size(2);
+            let keep = pool.clone().keep_size();
+            futures_util::pin_mut!(keep);
+
+            assert!(futures_util::poll!(keep.as_mut()).is_pending());
+            assert_eq!((pool.pool().len(), pool.pool().available()), (2, 2));
+
+            pool.rsvp().await.unwrap().discard();
+            assert_eq!(pool.rsvp().await.unwrap().take(), 1);
+            assert!(futures_util::poll!(keep.as_mut()).is_pending());
+            assert_eq!((pool.pool().len(), pool.manager().created()), (2, 4));
+
+            drop(pool);
+            assert!(futures_util::poll!(keep.as_mut()).is_ready());
+        });
+    }
+
+    #[test]
+    fn invalid_resources_are_dropped_and_replaced() {
+        block_on(async {
+            let pool = ManagedAsyncPool::new(Evens(AtomicUsize::new(0))).with_max_size(2);
+
+            let zero = pool.rsvp().await.unwrap();
+            let one = pool.rsvp().await.unwrap();
+            drop((zero, one));
+
+            // `1` fails validation, `0` is reused, then the freed slot is used for `2`
+            let zero = pool.rsvp().await.unwrap();
+            let two = pool.rsvp().await.unwrap();
+            assert_eq!((*zero, *two), (0, 2));
+        });
+    }
+}
@@ -0,0 +1,217 @@
+use std::{
+    collections::HashMap,
+    sync::{Mutex, PoisonError},
+    time::Duration,
+};
+
+use ::metrics::{
+    counter, describe_counter, describe_gauge, describe_histogram, gauge, histogram, Counter, Gauge, Histogram, Unit,
+};
+
+use crate::PoolEvent;
+
+/// A pool's handles into the `metrics` facade, labelled with the pool's name
+pub(crate) struct Metrics {
+    name: &'static str,
+    size: Gauge,
+    in_use: Gauge,
+    idle: Gauge,
+    acquired: Counter,
+    returned: Counter,
+    discarded: Counter,
+    quarantined: Counter,
+    exhausted: Counter,
+    create_errors: Counter,
+    wait: Histogram,
+    /// The wait histogram of each label reservations were made with, registered on the label's first
+    /// wait, so later ones don't go through the recorder again.
+    labelled_waits: Mutex<HashMap<&'static str, Histogram>>,
+}
+
+impl Metrics {
+    /// Register the pool's metrics with the installed recorder, under the label `pool = name`
+    pub(crate) fn new(name: &'static str) -> Self {
+        describe_gauge!("async_pool_size", "The number of resources owned by the pool");
+        describe_gauge!("async_pool_in_use", "The number of reserved resources");
+        describe_gauge!("async_pool_idle", "The number of resources owned by the pool but not reserved");
+        describe_counter!("async_pool_acquired_total", "Reservations of a resource");
+        describe_counter!("async_pool_returned_total", "Resources returned to the pool");
+        describe_counter!("async_pool_discarded_total", "Resources dropped by the pool");
+        describe_counter!("async_pool_quarantined_total", "Resources set aside by the circuit breaker");
+        describe_counter!("async_pool_exhausted_total", "Reservations that found every resource reserved");
+        describe_counter!("async_pool_create_errors_total", "Failures of a manager to create a resource");
+        describe_histogram!("async_pool_wait_seconds", Unit::Seconds, "How long reservations waited");
+
+        Self {
+            name,
+            size: gauge!("async_pool_size", "pool" => name),
+            in_use: gauge!("async_pool_in_use", "pool" => name),
+            idle: gauge!("async_pool_idle", "pool" => name),
+            acquired: counter!("async_pool_acquired_total", "pool" => name),
+            returned: counter!("async_pool_returned_total", "pool" => name),
+            discarded: counter!("async_pool_discarded_total", "pool" => name),
+            quarantined: counter!("async_pool_quarantined_total", "pool" => name),
+            exhausted: counter!("async_pool_exhausted_total", "pool" => name),
+            create_errors: counter!("async_pool_create_errors_total", "pool" => name),
+            wait: histogram!("async_pool_wait_seconds", "pool" => name),
+            labelled_waits: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub(crate) fn event(&self, event: PoolEvent) {
+        match event {
+            PoolEvent::Acquired => self.acquired.increment(1),
+            PoolEvent::Returned => self.returned.increment(1),
+            PoolEvent::Discarded => self.discarded.increment(1),
+            PoolEvent::Quarantined => self.quarantined.increment(1),
+            PoolEvent::Exhausted { .. } => self.exhausted.increment(1),
+            PoolEvent::Resized { .. } => {}
+        }
+    }
+
+    /// Set the gauges to the pool owning `size` resources, `in_use` of them reserved
+    pub(crate) fn occupancy(&self, size: usize, in_use: usize) {
+        self.size.set(size as f64);
+        self.in_use.set(in_use as f64);
+        self.idle.set(size.saturating_sub(in_use) as f64);
+    }
+
+    /// Record a wait for a reservation, labelled by the label it was made with, if any
+    pub(crate) fn waited(&self, waited: Duration, label: Option<&'static str>) {
+        let label = match label {
+            Some(label) => label,
+            None => {
+                self.wait.record(waited.as_secs_f64());
+                return;
+            }
+        };
+
+        // Histograms are only ever added, so the map is always safe to use
+        let mut waits = self.labelled_waits.lock().unwrap_or_else(PoisonError::into_inner);
+        waits.entry(label)
+            .or_insert_with(|| histogram!("async_pool_wait_seconds", "pool" => self.name, "label" => label))
+            .record(waited.as_secs_f64());
+    }
+
+    pub(crate) fn failed_to_create(&self) {
+        self.create_errors.increment(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use ::metrics::{CounterFn, GaugeFn, HistogramFn, Key, KeyName, Metadata, Recorder, SharedString};
+    use futures_executor::block_on;
+
+    use crate::AsyncPool;
+
+    use super::*;
+
+    /// A recorder keeping the last value of each metric, by name, and counting the histograms registered
+    #[derive(Default)]
+    struct Values {
+        values: Mutex<HashMap<String, Arc<Value>>>,
+        histograms: AtomicUsize,
+    }
+
+    #[derive(Default)]
+    struct Value(Mutex<f64>);
+
+    impl Values {
+        fn get(&self, name: &str) -> f64 {
+            *self.values.lock().unwrap()[name].0.lock().unwrap()
+        }
+
+        fn register(&self, key: &Key) -> Arc<Value> {
+            assert_eq!(key.labels().next().map(|label| label.value().to_owned()), Some("db".to_owned()));
+            self.values.lock().unwrap().entry(key.name().to_owned()).or_default().clone()
+        }
+    }
+
+    impl Recorder for Values {
+        fn describe_counter(&self, _: KeyName, _: Option<::metrics::Unit>, _: SharedString) {}
+        fn describe_gauge(&self, _: KeyName, _: Option<::metrics::Unit>, _: SharedString) {}
+        fn describe_histogram(&self, _: KeyName, _: Option<::metrics::Unit>, _: SharedString) {}
+
+        fn register_counter(&self, key: &Key, _: &Metadata<'_>) -> Counter {
+            Counter::from_arc(self.register(key))
+        }
+
+        fn register_gauge(&self, key: &Key, _: &Metadata<'_>) -> Gauge {
+            Gauge::from_arc(self.register(key))
+        }
+
+        fn register_histogram(&self, key: &Key, _: &Metadata<'_>) -> Histogram {
+            self.histograms.fetch_add(1, Ordering::SeqCst);
+            Histogram::from_arc(self.register(key))
+        }
+    }
+
+    impl CounterFn for Value {
+        fn increment(&self, value: u64) {
+            *self.0.lock().unwrap() += value as f64;
+        }
+
+        fn absolute(&self, value: u64) {
+            *self.0.lock().unwrap() = value as f64;
+        }
+    }
+
+    impl GaugeFn for Value {
+        fn increment(&self, value: f64) {
+            *self.0.lock().unwrap() += value;
+        }
+
+        fn decrement(&self, value: f64) {
+            *self.0.lock().unwrap() -= value;
+        }
+
+        fn set(&self, value: f64) {
+            *self.0.lock().unwrap() = value;
+        }
+    }
+
+    impl HistogramFn for Value {
+        fn record(&self, value: f64) {
+            *self.0.lock().unwrap() = value;
+        }
+    }
+
+    #[test]
+    fn pools_report_to_the_installed_recorder() {
+        block_on(async {
+            let values = Values::default();
+            let pool = AsyncPool::new_with(vec![1, 2]).await;
+            let pool = ::metrics::with_local_recorder(&values, || pool.with_metrics("db"));
+
+            let guard = pool.rsvp().await.unwrap();
+            assert_eq!((values.get("async_pool_size"), values.get("async_pool_in_use")), (2.0, 1.0));
+            assert_eq!(values.get("async_pool_idle"), 1.0);
+
+            guard.discard();
+            pool.rsvp().await.unwrap();
+            assert_eq!((values.get("async_pool_acquired_total"), values.get("async_pool_discarded_total")), (2.0, 1.0));
+            assert_eq!(values.get("async_pool_size"), 1.0);
+        });
+    }
+    #[test]
+    fn labelled_waits_register_their_histogram_once() {
+        let values = Values::default();
+        ::metrics::with_local_recorder(&values, || {
+            block_on(async {
+                let pool = AsyncPool::new_with(vec![1]).await.with_metrics("db");
+                for _ in 0..3 {
+                    drop(pool.rsvp_labeled("query").await.unwrap());
+                }
+            })
+        });
+
+        // The pool's own wait histogram, then one for the label
+        assert_eq!(values.histograms.load(Ordering::SeqCst), 2);
+    }
+}
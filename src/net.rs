@@ -0,0 +1,118 @@
+//! A ready-made `Manager` for pools of TCP connections, with the `net` feature
+
+use std::{
+    io,
+    net::SocketAddr,
+    task::Poll,
+    time::Duration,
+};
+
+use futures_util::future::poll_fn;
+use tokio::{io::ReadBuf, net::TcpStream, time};
+
+use crate::Manager;
+
+/// A `Manager` that dials a TCP address, and reconnects once a connection drops
+///
+/// Idle connections are checked before they're handed out, without reading from them, and those
+/// the peer closed or that errored are dropped, so the pool dials a fresh one in their place. A
+/// connection that breaks while reserved should be given back with `AsyncPoolGuard::discard`.
+#[derive(Debug, Clone)]
+pub struct TcpManager {
+    /// The address to dial, as a socket address or a `host:port` to resolve on each dial.
+    addr: String,
+    /// How long each dial may take, if limited.
+    connect_timeout: Option<Duration>,
+    nodelay: bool,
+}
+
+impl TcpManager {
+    /// Dial `addr`, a `host:port` that's resolved again on each dial
+    pub fn new(addr: impl Into<String>) -> Self {
+        Self {
+            addr: addr.into(),
+            connect_timeout: None,
+            nodelay: false,
+        }
+    }
+
+    /// Dial `addr`, without resolving anything
+    pub fn from_addr(addr: SocketAddr) -> Self {
+        Self::new(addr.to_string())
+    }
+
+    /// Give up on each dial after `timeout`, failing with `io::ErrorKind::TimedOut`
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Set `TCP_NODELAY` on each connection, disabling Nagle's algorithm
+    pub fn with_nodelay(mut self, nodelay: bool) -> Self {
+        self.nodelay = nodelay;
+        self
+    }
+
+    /// The address dialed
+    pub fn addr(&self) -> &str {
+        &self.addr
+    }
+}
+
+impl Manager for TcpManager {
+    type Resource = TcpStream;
+    type Error = io::Error;
+
+    async fn create(&self) -> Result<TcpStream, io::Error> {
+        let stream = match self.connect_timeout {
+            Some(timeout) => time::timeout(timeout, TcpStream::connect(&self.addr)).await
+                .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "Timed out connecting"))??,
+            None => TcpStream::connect(&self.addr).await?,
+        };
+        stream.set_nodelay(self.nodelay)?;
+
+        Ok(stream)
+    }
+
+    async fn validate(&self, stream: &mut TcpStream) -> bool {
+        let mut byte = [0];
+        let mut buf = ReadBuf::new(&mut byte);
+
+        // Peeking never consumes anything, and nothing to read yet means the connection is quiet
+        poll_fn(|cx| match stream.poll_peek(cx, &mut buf) {
+            Poll::Ready(Ok(read)) => Poll::Ready(read > 0),
+            Poll::Ready(Err(_)) => Poll::Ready(false),
+            Poll::Pending => Poll::Ready(true),
+        }).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::{io::AsyncWriteExt, net::TcpListener, runtime::Builder};
+
+    use crate::ManagedAsyncPool;
+
+    use super::*;
+
+    #[test]
+    fn tcp_managers_redial_closed_connections() {
+        Builder::new_current_thread().enable_all().build().unwrap().block_on(async {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let pool = ManagedAsyncPool::new(TcpManager::from_addr(listener.local_addr().unwrap()));
+
+            let first = pool.rsvp().await.unwrap();
+            let port = first.local_addr().unwrap().port();
+            let (mut accepted, _) = listener.accept().await.unwrap();
+            drop(first);
+
+            accepted.shutdown().await.unwrap();
+            drop(accepted);
+            time::sleep(Duration::from_millis(50)).await;
+
+            let second = pool.rsvp().await.unwrap();
+            assert_ne!(second.local_addr().unwrap().port(), port);
+            assert_eq!(pool.pool().len(), 1);
+        });
+    }
+}
@@ -0,0 +1,383 @@
+use std::{
+    cmp::Reverse,
+    collections::BTreeMap,
+    future::Future,
+    mem,
+    pin::Pin,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    task::{Context, Poll, Waker},
+    time::Duration,
+};
+
+use futures_util::future::{poll_fn, select, Either};
+
+use crate::{
+    runtime::{DefaultTimer, Timer},
+    wait::WaiterKey,
+    PoolError,
+};
+
+/// A fair async semaphore, handing out permits the way an `AsyncPool` hands out resources
+///
+/// This is for limiting concurrency when there's nothing to pool. Waiters are served in order, by
+/// priority, and with the same timeouts and closing as any pool. Permits are only counted, so taking
+/// and returning one costs no more than a lock.
+///
+/// Cloning a `PermitPool` is cheap, and gives another handle to the same permits.
+pub struct PermitPool {
+    shared: Arc<Shared>,
+}
+
+/// A permit from a `PermitPool`. Returns it on drop.
+pub struct Permit {
+    permits: Permits,
+}
+
+/// A batch of permits from `PermitPool::acquire_many`. Returns them all on drop.
+pub struct Permits {
+    shared: Arc<Shared>,
+    /// How many permits are held.
+    n: usize,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    /// The source of delays for timeouts.
+    timer: DefaultTimer,
+}
+
+/// The permits and their waiters, behind the pool's lock
+struct State {
+    /// The number of permits, taken or not.
+    permits: usize,
+    /// The number of permits nobody holds.
+    available: usize,
+    /// How long an `acquire` waits before giving up, if it does.
+    timeout: Option<Duration>,
+    closed: bool,
+    /// The waiting `acquire`s, first in line first, with how many permits each wants.
+    waiters: BTreeMap<WaiterKey, (usize, Waker)>,
+    next_ticket: u64,
+    /// The `close` calls waiting for every permit to be returned.
+    closers: Vec<Waker>,
+}
+
+/// An `acquire` waiting its turn for `wanted` permits
+///
+/// It only takes its permits once no waiter is ahead of it, so a large batch is never starved by
+/// smaller ones, and wakes the next waiter if there are permits left for it.
+struct Acquire<'a> {
+    shared: &'a Shared,
+    priority: u8,
+    wanted: usize,
+    /// This waiter's place in the queue, once it has one.
+    key: Option<WaiterKey>,
+}
+
+impl PermitPool {
+    /// Create a pool of `permits` permits
+    pub fn new(permits: usize) -> Self {
+        let state = State {
+            permits,
+            available: permits,
+            timeout: None,
+            closed: false,
+            waiters: BTreeMap::new(),
+            next_ticket: 0,
+            closers: Vec::new(),
+        };
+
+        Self {
+            shared: Arc::new(Shared { state: Mutex::new(state), timer: DefaultTimer::default() }),
+        }
+    }
+
+    /// Make every `acquire` give up after `timeout`
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        self.shared.lock().timeout = Some(timeout);
+        self
+    }
+
+    /// Await a permit
+    ///
+    /// Fails if the pool is closed, or if it was configured with a timeout and it elapsed.
+    pub async fn acquire(&self) -> Result<Permit, PoolError> {
+        self.acquire_with_priority(0).await
+    }
+
+    /// Await a permit, ahead of any waiters with a lower `priority`
+    pub async fn acquire_with_priority(&self, priority: u8) -> Result<Permit, PoolError> {
+        let permits = self.wait_for(1, priority).await?;
+
+        Ok(Permit { permits })
+    }
+
+    /// Await `n` permits at once, taking none of them until all are available
+    pub async fn acquire_many(&self, n: usize) -> Result<Permits, PoolError> {
+        self.wait_for(n, 0).await
+    }
+
+    /// Take a permit only if one is free right now, without awaiting
+    pub fn try_acquire(&self) -> Option<Permit> {
+        let mut state = self.shared.lock();
+        if state.closed || state.available == 0 || !state.waiters.is_empty() {
+            return None;
+        }
+        state.available -= 1;
+
+        Some(Permit { permits: Permits { shared: self.shared.clone(), n: 1 } })
+    }
+
+    /// Add `n` more permits
+    ///
+    /// Fails with `PoolError::Closed` if the pool is closed, adding none.
+    pub fn add_permits(&self, n: usize) -> Result<(), PoolError> {
+        let mut state = self.shared.lock();
+        if state.closed {
+            return Err(PoolError::Closed);
+        }
+
+        state.permits += n;
+        state.release(n);
+        Ok(())
+    }
+
+    /// The number of permits, taken or not
+    pub fn permits(&self) -> usize {
+        self.shared.lock().permits
+    }
+
+    /// The number of permits free right now
+    pub fn available(&self) -> usize {
+        self.shared.lock().available
+    }
+
+    /// Stop handing out permits, failing every `acquire` still waiting, then wait for every permit to
+    /// be returned
+    ///
+    /// This never fails, and returns a `Result` like `AsyncPool::close`.
+    pub async fn close(&self) -> Result<(), PoolError> {
+        {
+            let mut state = self.shared.lock();
+            state.closed = true;
+            state.permits -= mem::take(&mut state.available);
+            mem::take(&mut state.waiters).into_values().for_each(|(_, waker)| waker.wake());
+        }
+
+        poll_fn(|cx| {
+            let mut state = self.shared.lock();
+            if state.permits == 0 {
+                return Poll::Ready(Ok(()));
+            }
+
+            if !state.closers.iter().any(|w| w.will_wake(cx.waker())) {
+                state.closers.push(cx.waker().clone());
+            }
+
+            Poll::Pending
+        }).await
+    }
+
+    /// Await `n` permits, within the pool's timeout
+    async fn wait_for(&self, n: usize, priority: u8) -> Result<Permits, PoolError> {
+        let acquire = Acquire { shared: &self.shared, priority, wanted: n, key: None };
+        let timeout = self.shared.lock().timeout;
+
+        match timeout {
+            Some(dur) => match select(acquire, self.shared.timer.sleep(dur)).await {
+                Either::Left((acquired, _)) => acquired?,
+                Either::Right(_) => return Err(PoolError::Timeout),
+            },
+            None => acquire.await?,
+        }
+
+        Ok(Permits { shared: self.shared.clone(), n })
+    }
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        // Every update is a few counters, made at once, so the state is always safe to use
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl State {
+    /// Make `n` permits available again, or drop them if the pool is closed
+    fn release(&mut self, n: usize) {
+        if self.closed {
+            self.forget(n);
+            return;
+        }
+
+        self.available += n;
+        self.wake_first();
+    }
+
+    /// Drop `n` permits that were taken, for good
+    fn forget(&mut self, n: usize) {
+        self.permits -= n;
+
+        if self.closed && self.permits == 0 {
+            self.closers.drain(..).for_each(Waker::wake);
+        }
+    }
+
+    /// Wake the first waiter, if there are enough permits for it
+    fn wake_first(&self) {
+        if let Some((wanted, waker)) = self.waiters.values().next() {
+            if *wanted <= self.available {
+                waker.wake_by_ref();
+            }
+        }
+    }
+}
+
+impl Future for Acquire<'_> {
+    type Output = Result<(), PoolError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        let mut state = this.shared.lock();
+
+        if state.closed {
+            this.key = None;
+            return Poll::Ready(Err(PoolError::Closed));
+        }
+
+        let key = match this.key {
+            Some(key) => key,
+            None => {
+                let key = WaiterKey { priority: Reverse(this.priority), ticket: state.next_ticket };
+                state.next_ticket += 1;
+                *this.key.insert(key)
+            }
+        };
+
+        if state.available >= this.wanted && state.waiters.range(..key).next().is_none() {
+            state.waiters.remove(&key);
+            this.key = None;
+            state.available -= this.wanted;
+
+            // What's left may be enough for the next waiter
+            state.wake_first();
+            return Poll::Ready(Ok(()));
+        }
+
+        state.waiters.insert(key, (this.wanted, cx.waker().clone()));
+        Poll::Pending
+    }
+}
+
+impl Drop for Acquire<'_> {
+    fn drop(&mut self) {
+        if let Some(key) = self.key {
+            let mut state = self.shared.lock();
+            state.waiters.remove(&key);
+
+            // It may have been woken for permits it won't take, or been holding up smaller waiters
+            state.wake_first();
+        }
+    }
+}
+
+impl Permit {
+    /// Keep the permit for good, so there's one fewer permit in the pool
+    pub fn forget(mut self) {
+        self.permits.forget();
+    }
+}
+
+impl Permits {
+    /// The number of permits in the batch
+    pub fn len(&self) -> usize {
+        self.n
+    }
+
+    /// Whether the batch has no permits
+    pub fn is_empty(&self) -> bool {
+        self.n == 0
+    }
+
+    /// Split the batch into single permits, so each can be returned on its own
+    pub fn split(mut self) -> Vec<Permit> {
+        let n = mem::take(&mut self.n);
+
+        (0..n).map(|_| Permit { permits: Permits { shared: self.shared.clone(), n: 1 } }).collect()
+    }
+
+    fn forget(&mut self) {
+        let n = mem::take(&mut self.n);
+        self.shared.lock().forget(n);
+    }
+}
+
+impl Drop for Permits {
+    fn drop(&mut self) {
+        if self.n > 0 {
+            self.shared.lock().release(self.n);
+        }
+    }
+}
+
+impl Clone for PermitPool {
+    fn clone(&self) -> Self {
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_executor::block_on;
+    use futures_util::poll;
+
+    use super::*;
+
+    #[test]
+    fn permits_limit_and_return() {
+        block_on(async {
+            let permits = PermitPool::new(2);
+            let batch = permits.acquire_many(2).await.unwrap();
+            assert!(permits.try_acquire().is_none());
+
+            let mut split = batch.split();
+            split.pop();
+            assert_eq!(permits.available(), 1);
+
+            permits.acquire().await.unwrap().forget();
+            permits.add_permits(1).unwrap();
+            assert_eq!((permits.permits(), permits.available()), (2, 1));
+        });
+    }
+
+    #[test]
+    fn waiters_take_permits_in_turn() {
+        block_on(async {
+            let permits = PermitPool::new(1);
+            let held = permits.acquire().await.unwrap();
+
+            let mut batch = Box::pin(permits.acquire_many(2));
+            let mut urgent = Box::pin(permits.acquire_with_priority(1));
+            assert!(poll!(batch.as_mut()).is_pending());
+            assert!(poll!(urgent.as_mut()).is_pending());
+            assert!(permits.try_acquire().is_none());
+
+            drop(held);
+            let held = urgent.await.unwrap();
+            permits.add_permits(1).unwrap();
+            assert!(poll!(batch.as_mut()).is_pending());
+
+            drop(held);
+            assert_eq!(batch.await.unwrap().len(), 2);
+
+            let mut waiting = Box::pin(permits.acquire_many(3));
+            assert!(poll!(waiting.as_mut()).is_pending());
+            permits.close().await.unwrap();
+            assert_eq!(waiting.await.err(), Some(PoolError::Closed));
+            assert_eq!(permits.add_permits(1), Err(PoolError::Closed));
+            assert_eq!(permits.permits(), 0);
+        });
+    }
+}
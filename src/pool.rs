@@ -0,0 +1,3308 @@
+use std::{
+    backtrace::Backtrace,
+    cmp::Reverse,
+    collections::{hash_map::DefaultHasher, BTreeMap, BTreeSet, HashMap, VecDeque},
+    future::Future,
+    hash::{Hash, Hasher},
+    iter::FromIterator,
+    mem,
+    sync::{
+        atomic::{self, Ordering},
+        Arc, PoisonError, Weak,
+    },
+    task::{Poll, Waker},
+    time::Duration,
+};
+
+use futures_util::future::{poll_fn, select, Either};
+
+use crate::{
+    breaker::Quarantined,
+    events::Events,
+    hooks::{self, AsyncDestroy, AsyncReturn, Hooks, ResourceInfo},
+    hot::HotCache,
+    leak::LeakDetector,
+    limit::Bucket,
+    runtime::{DefaultTimer, Sleep, Spawner, Timer},
+    scale::Scaler,
+    stats::WaitTimes,
+    sync::{AtomicBool, Mutex, MutexGuard},
+    time::Instant,
+    trace::event,
+    trim::{self, Trim},
+    wait::{Filter, Grant, Wait, Waiter, WaiterKey},
+    AddError, AsyncPoolBatchGuard, AsyncPoolBuilder, AsyncPoolGuard, AutoScale, CircuitBreaker, Guards, IntoInnerError,
+    Leak, PoisonPolicy, PoolError, PoolEvent, PoolEvents, PoolSink, RateLimit, ResourceStats, Reusable, Scope,
+    Strategy, WaitHistogram, WaiterInfo,
+};
+
+/// Keeps resources from being parked on threads while it lives
+struct Watching<'a, T>(&'a HotCache<T>);
+
+impl<'a, T> Watching<'a, T> {
+    fn new(hot: &'a HotCache<T>) -> Self {
+        hot.watching.fetch_add(1, Ordering::SeqCst);
+        Self(hot)
+    }
+}
+
+impl<T> Drop for Watching<'_, T> {
+    fn drop(&mut self) {
+        self.0.watching.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// A change from `map_resources`
+type Remap<T> = Arc<dyn Fn(&mut T) + Send + Sync>;
+
+/// The ID of the next resource to enter any pool
+static NEXT_ID: atomic::AtomicU64 = atomic::AtomicU64::new(0);
+
+/// A collection for asynchronously reserving a resource from a pool
+///
+/// When every resource is reserved, `rsvp` calls wait in a queue and are served in the order they
+/// started waiting, so none can be starved by later arrivals. `rsvp_with_priority` lets urgent
+/// calls skip ahead of those with a lower priority.
+///
+/// Cloning an `AsyncPool` is cheap, and gives another handle to the same resources.
+pub struct AsyncPool<T: Send + 'static> {
+    /// The state shared with guards, so they can return their resource.
+    shared: Arc<Shared<T>>,
+}
+
+/// A handle to an `AsyncPool` that doesn't keep it alive, from `AsyncPool::downgrade`
+///
+/// This is for caches and background tasks that should stop using the pool once everything else
+/// is done with it.
+pub struct WeakPool<T: Send + 'static> {
+    shared: Weak<Shared<T>>,
+}
+
+/// The part of the pool that guards hold on to
+pub(crate) struct Shared<T: Send + 'static> {
+    state: Mutex<State<T>>,
+    /// The subscribers to the pool's events, also reachable from the state.
+    pub(crate) events: Arc<Events>,
+    /// How long reservations waited.
+    waits: WaitTimes,
+    /// The resources parked on the threads that returned them, also reachable from the state.
+    pub(crate) hot: Arc<HotCache<T>>,
+    /// Whether guards capture where they were reserved, for leak reports.
+    backtraces: AtomicBool,
+}
+
+/// Everything about the pool that changes, behind its lock
+///
+/// The lock is only ever held briefly, and never across an `await`.
+pub(crate) struct State<T: Send + 'static> {
+    /// The resources that aren't reserved, longest idle first. Only non-empty while nobody is waiting.
+    pub(crate) idle: VecDeque<Idle<T>>,
+    /// The waiting `rsvp` calls, ordered so the first is the next to be served.
+    pub(crate) waiters: BTreeMap<WaiterKey, Waiter<T>>,
+    /// Resources handed to a waiter that hasn't picked them up yet, by ticket.
+    pub(crate) granted: HashMap<u64, Grant<T>>,
+    /// The stats of the reserved resources, as of when they were reserved, by ID.
+    reserved: HashMap<u64, ResourceStats>,
+    /// The resources set aside by the circuit breaker, neither idle nor reserved.
+    quarantined: Vec<Quarantined<T>>,
+    /// The ticket for the next waiter.
+    next_ticket: u64,
+    /// The wakers of `close` calls, waiting for every resource to come back.
+    closers: Vec<Waker>,
+    /// The wakers of `wait_for_available` and `notified` calls, waiting for resources to come back.
+    watchers: Vec<Waker>,
+    /// How many times a resource was added or returned to the pool.
+    put_backs: u64,
+    /// The number of resources owned by the pool, reserved or not.
+    pub(crate) size: usize,
+    /// The number of resources currently reserved.
+    pub(crate) in_use: usize,
+    /// Whether the pool has stopped handing out resources.
+    pub(crate) closed: bool,
+    /// How many times every resource was invalidated.
+    generation: u64,
+    /// How long `rsvp` waits before giving up, if at all.
+    timeout: Option<Duration>,
+    /// The most resources the pool may own, if limited.
+    max_size: Option<usize>,
+    /// How long a resource may sit idle before it's dropped, if limited.
+    idle_timeout: Option<Duration>,
+    /// How long a resource may be owned by the pool before it's retired, if limited.
+    max_lifetime: Option<Duration>,
+    /// The order idle resources are handed out in.
+    strategy: Strategy,
+    /// How long a waiter waits for each level its priority is raised by, if waiters age.
+    aging: Option<Duration>,
+    /// What to do once the lock is poisoned.
+    poison_policy: PoisonPolicy,
+    /// When to set aside resources that keep failing, if ever.
+    breaker: Option<CircuitBreaker>,
+    /// The tokens left for reservations, if they're rate limited.
+    limiter: Option<Bucket>,
+    /// The reservations watched for leaks, if they are.
+    leaks: Option<LeakDetector>,
+    /// The pools this one was absorbed into or split between, to hand returned resources to.
+    successors: Option<Successors<T>>,
+    /// The changes from `map_resources` still to be applied to reserved resources, by ID.
+    remaps: HashMap<u64, Vec<Remap<T>>>,
+    /// The callbacks run through each resource's life.
+    hooks: Arc<Hooks<T>>,
+    /// How to close the resources the pool drops, if they need more than `Drop`.
+    destructor: Option<Arc<AsyncDestroy<T>>>,
+    /// What to await on returned resources before they re-enter the pool, if anything.
+    returner: Option<Arc<AsyncReturn<T>>>,
+    /// The source of delays for timeouts and maintenance.
+    timer: Arc<dyn Timer>,
+    /// The subscribers to the pool's events.
+    pub(crate) events: Arc<Events>,
+    /// The resources parked on the threads that returned them.
+    hot: Arc<HotCache<T>>,
+}
+
+/// The pools a pool's resources are handed over to, by `absorb` or `split`
+struct Successors<T: Send + 'static> {
+    pools: Vec<Weak<Shared<T>>>,
+    /// Picks the pool for a resource, by its index in `pools`, modulo their number.
+    pick: Box<dyn FnMut(&T) -> usize + Send>,
+}
+
+/// A resource owned by the pool, and when it was created
+pub(crate) struct Entry<T> {
+    pub(crate) item: T,
+    pub(crate) id: u64,
+    pub(crate) created: Instant,
+    /// When the resource was last reserved, if ever.
+    pub(crate) used: Option<Instant>,
+    /// How many times it was reserved.
+    checkouts: u64,
+    /// How long it was reserved for in total.
+    held: Duration,
+    /// The affinity key of its last reservation, if it had one.
+    pub(crate) affinity: Option<u64>,
+    /// The labels it was added with.
+    pub(crate) tags: BTreeSet<String>,
+    /// How much capacity it has, for `rsvp_weight`.
+    pub(crate) weight: usize,
+    /// How many failures in a row were reported against it.
+    pub(crate) failures: u32,
+    /// Whether it's on probation, after being set aside by the circuit breaker.
+    pub(crate) probing: bool,
+    /// The pool's generation when it was created, to retire it once the pool is invalidated.
+    pub(crate) generation: u64,
+}
+
+/// A resource that isn't reserved, and when it was last put back
+pub(crate) struct Idle<T> {
+    pub(crate) entry: Entry<T>,
+    since: Instant,
+}
+
+/// An idle resource taken out of the queue by `AsyncPool::check_idle`, which goes back in its place on
+/// drop
+pub(crate) struct Checking<'a, T: Send + 'static> {
+    shared: &'a Arc<Shared<T>>,
+    idle: Option<Idle<T>>,
+    /// Where in the queue it was.
+    at: usize,
+}
+
+impl<T: Send + 'static> AsyncPool<T> {
+    /// Create a new `AsyncPool`.
+    pub fn new() -> Self {
+        let events = Arc::new(Events::new());
+        let hot = Arc::new(HotCache::new());
+        let state = State {
+            idle: VecDeque::new(),
+            waiters: BTreeMap::new(),
+            granted: HashMap::new(),
+            reserved: HashMap::new(),
+            quarantined: Vec::new(),
+            next_ticket: 0,
+            closers: Vec::new(),
+            watchers: Vec::new(),
+            put_backs: 0,
+            size: 0,
+            in_use: 0,
+            closed: false,
+            generation: 0,
+            timeout: None,
+            max_size: None,
+            idle_timeout: None,
+            max_lifetime: None,
+            strategy: Strategy::Fifo,
+            aging: None,
+            poison_policy: PoisonPolicy::Error,
+            breaker: None,
+            limiter: None,
+            leaks: None,
+            successors: None,
+            remaps: HashMap::new(),
+            hooks: Arc::new(Hooks::new()),
+            destructor: None,
+            returner: None,
+            timer: Arc::new(DefaultTimer::default()),
+            events: events.clone(),
+            hot: hot.clone(),
+        };
+
+        Self {
+            shared: Arc::new(Shared {
+                state: Mutex::new(state),
+                events,
+                waits: WaitTimes::new(),
+                hot,
+                backtraces: AtomicBool::new(false),
+            }),
+        }
+    }
+
+    /// Start building a pool with more options than `new` takes
+    pub fn builder() -> AsyncPoolBuilder<T> {
+        AsyncPoolBuilder::new()
+    }
+
+    /// Call `on_return` on each resource when its guard drops, before it re-enters the pool
+    ///
+    /// As it runs inside `Drop`, the hook must not block.
+    pub fn with_on_return(self, on_return: impl Fn(&mut T) + Send + Sync + 'static) -> Self {
+        {
+            let mut state = self.shared.lock_recovered();
+            let hooks = Arc::make_mut(&mut state.hooks);
+            hooks.on_release = Some(Arc::new(move |item: &mut T, _: &ResourceInfo| on_return(item)));
+            self.shared.hot.set_hooks(&state.hooks);
+        }
+
+        self
+    }
+
+    /// Shrink each returned resource's capacity back to `max_capacity` if it grew past it, before it
+    /// re-enters the pool
+    ///
+    /// This keeps one oversized request from pinning a huge buffer in the pool for good. It runs after
+    /// the `on_return` hook set before it, so a hook that clears the resource frees all it can, but
+    /// setting `on_return` or `with_hooks` afterwards replaces it.
+    pub fn with_capacity_limit(self, max_capacity: usize) -> Self
+    where
+        T: Trim,
+    {
+        self.then_on_release(move |item| trim::trim(item, max_capacity))
+    }
+
+    /// Call `Reusable::reset` on each resource when its guard drops, before it re-enters the pool
+    ///
+    /// Like `with_capacity_limit`, it runs after the `on_return` hook set before it, and setting
+    /// `on_return` or `with_hooks` afterwards replaces it.
+    pub fn with_reset(self) -> Self
+    where
+        T: Reusable,
+    {
+        self.then_on_release(T::reset)
+    }
+
+    /// Run `then` on each returned resource, after the `on_release` hook that's already set
+    fn then_on_release(self, then: impl Fn(&mut T) + Send + Sync + 'static) -> Self {
+        {
+            let mut state = self.shared.lock_recovered();
+            let hooks = Arc::make_mut(&mut state.hooks);
+            let on_release = hooks.on_release.take();
+            hooks.on_release = Some(Arc::new(move |item: &mut T, info: &ResourceInfo| {
+                if let Some(on_release) = &on_release {
+                    on_release(item, info);
+                }
+                then(item);
+            }));
+            self.shared.hot.set_hooks(&state.hooks);
+        }
+
+        self
+    }
+
+    /// Run `hooks` at each point in the resources' lives, replacing any hooks set before
+    pub fn with_hooks(self, hooks: Hooks<T>) -> Self {
+        let hooks = Arc::new(hooks);
+        self.shared.hot.set_hooks(&hooks);
+        self.shared.lock_recovered().hooks = hooks;
+        self
+    }
+
+    /// Close each resource the pool drops with `close`, spawned on `spawner`, instead of just dropping it
+    ///
+    /// This is for resources that need an async shutdown, like a TLS connection's `close_notify`. It runs
+    /// after the `on_destroy` hook, whether the resource was discarded, evicted, retired or closed, and
+    /// `close` awaits it for the resources that were idle rather than spawning it.
+    pub fn with_async_destroy<F>(
+        self,
+        spawner: impl Spawner + Send + Sync + 'static,
+        close: impl Fn(T) -> F + Send + Sync + 'static,
+    ) -> Self
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.shared.lock_recovered().destructor = Some(Arc::new(AsyncDestroy::new(spawner, close)));
+        self
+    }
+
+    /// Run `on_return` on each resource as its guard drops, in a task spawned on `spawner`, before it
+    /// re-enters the pool
+    ///
+    /// This is for cleanup that has to await, like rolling back a transaction or draining the rest of a
+    /// response. The resource still counts as reserved until `on_return` is done, and it's dropped
+    /// instead of re-entering the pool if `on_return` gives back `None`. The `on_release` hook runs
+    /// after it. This turns off `with_thread_cache`, as parked resources would skip it.
+    pub fn with_async_on_return<F>(
+        self,
+        spawner: impl Spawner + Send + Sync + 'static,
+        on_return: impl Fn(T) -> F + Send + Sync + 'static,
+    ) -> Self
+    where
+        F: Future<Output = Option<T>> + Send + 'static,
+    {
+        self.shared.hot.disable();
+        self.shared.lock_recovered().returner = Some(Arc::new(AsyncReturn::new(spawner, on_return)));
+        self
+    }
+
+    /// Park each returned resource on the thread that returned it, for that thread's next `rsvp` or
+    /// `try_rsvp` to take back without locking the pool
+    ///
+    /// This is for short-held resources, reserved and returned again and again by the same threads.
+    /// Each thread parks at most one resource, which still counts as in use, and is only checked for
+    /// its maximum lifetime once it's back in the pool. Parked resources go back to the pool as soon as
+    /// anything waits, or `try_rsvp` on another thread finds nothing idle, and when it's closed.
+    ///
+    /// This does nothing for pools with an async on-return hook, a rate limit or leak detection, which
+    /// every resource has to go through.
+    pub fn with_thread_cache(self) -> Self {
+        let skipped = {
+            let state = self.shared.lock_recovered();
+            state.returner.is_some() || state.limiter.is_some() || state.leaks.is_some()
+        };
+
+        if !skipped {
+            self.shared.hot.enable();
+        }
+        self
+    }
+
+    /// Use `timer` for timeouts and maintenance, instead of the `DefaultTimer`
+    pub fn with_timer(self, timer: impl Timer) -> Self {
+        self.shared.lock_recovered().timer = Arc::new(timer);
+        self
+    }
+
+    /// Limit the pool to owning at most `max_size` resources
+    pub fn with_max_size(self, max_size: usize) -> Self {
+        let mut state = self.shared.lock_recovered();
+        state.max_size = Some(max_size);
+        state.hot.oversized.store(state.size > max_size, Ordering::SeqCst);
+        drop(state);
+
+        self
+    }
+
+    /// The most resources the pool may own, if limited
+    pub fn max_size(&self) -> Option<usize> {
+        self.shared.lock_recovered().max_size
+    }
+
+    /// Change the pool's maximum size while it's in use, returning how many idle resources were retired
+    ///
+    /// Growing the pool lets `add` admit more resources, and waiting `rsvp` calls on a managed pool
+    /// create them. Shrinking it retires idle resources straight away, and reserved ones as they're
+    /// returned, until the pool fits.
+    pub fn resize(&self, max_size: usize) -> usize {
+        let retired = {
+            let mut state = self.shared.lock_recovered();
+            state.max_size = Some(max_size);
+            // Stop parking before taking parked resources back, so none are parked past the new size
+            state.hot.oversized.store(state.size > max_size, Ordering::SeqCst);
+            state.unpark_all();
+
+            let excess = state.size.saturating_sub(max_size).min(state.idle.len());
+            let at = state.idle.len() - excess;
+            let retired: Vec<_> = state.idle.drain(at..).map(|idle| idle.entry).collect();
+            for _ in &retired {
+                state.shrink();
+            }
+
+            // Wake everything that might claim one of the new slots, not just the first
+            if state.size < max_size {
+                for waiter in state.waiters.values().filter(|waiter| waiter.claims_slots) {
+                    waiter.waker.wake_by_ref();
+                }
+            }
+
+            event!(max_size, retired = retired.len(), "Resized the pool");
+            retired
+        };
+
+        let count = retired.len();
+        for entry in retired {
+            self.shared.destroy(entry, None);
+        }
+
+        count
+    }
+
+    /// Handle a panic that poisoned the pool's lock according to `policy`, instead of failing with
+    /// `PoolError::Poisoned`
+    pub fn with_poison_policy(self, policy: PoisonPolicy) -> Self {
+        self.shared.lock_recovered().poison_policy = policy;
+        self
+    }
+
+    /// Set aside resources that keep failing, as reported by `AsyncPoolGuard::report_failure`
+    pub fn with_circuit_breaker(self, breaker: CircuitBreaker) -> Self {
+        self.shared.lock_recovered().breaker = Some(breaker);
+        self
+    }
+
+    /// Hand out at most `limit.per_second` reservations a second, in bursts of up to `limit.burst`
+    ///
+    /// Reservations past the limit wait their turn, within the pool's timeout; `try_rsvp` fails
+    /// instead. This turns off `with_thread_cache`, as parked resources would skip the limit.
+    pub fn with_rate_limit(self, limit: RateLimit) -> Self {
+        self.shared.hot.disable();
+        self.shared.lock_recovered().limiter = Some(Bucket::new(limit));
+        self
+    }
+
+    /// Report the pool's size, use, wait times and discards to the `metrics` facade, labelled with
+    /// `pool = name`
+    ///
+    /// Whatever recorder is installed, like a Prometheus exporter, picks up the gauges
+    /// `async_pool_size`, `async_pool_in_use` and `async_pool_idle`, the counters
+    /// `async_pool_acquired_total`, `async_pool_returned_total`, `async_pool_discarded_total`,
+    /// `async_pool_quarantined_total`, `async_pool_exhausted_total` and
+    /// `async_pool_create_errors_total`, and the histogram `async_pool_wait_seconds`. The name can
+    /// only be set once, so calling this again does nothing.
+    #[cfg(feature = "metrics")]
+    pub fn with_metrics(self, name: &'static str) -> Self {
+        {
+            let state = self.shared.lock_recovered();
+            state.events.export(name, state.size, state.in_use);
+        }
+        self
+    }
+
+    /// Report every guard held for longer than `threshold` to `on_leak`, once
+    ///
+    /// This is for tracking down guards that are never dropped, which leave the pool exhausted. Guards
+    /// are checked when `rsvp` is called or the pool is maintained, at most every tenth of `threshold`,
+    /// and reported with the label they were reserved with, and where, with `with_leak_backtraces`.
+    /// With the `tracing` feature, each one is also logged as a warning. This turns off
+    /// `with_thread_cache`, as parked resources would look held.
+    pub fn with_leak_detection(self, threshold: Duration, on_leak: impl Fn(&Leak) + Send + Sync + 'static) -> Self {
+        self.shared.hot.disable();
+        self.shared.lock_recovered().leaks = Some(LeakDetector::new(threshold, on_leak));
+        self
+    }
+
+    /// Capture a backtrace wherever a guard is reserved, for the reports of `with_leak_detection`
+    ///
+    /// Backtraces are slow to capture, so this is off by default, and only meant for tracking down a
+    /// leak. They're captured once the pool is unlocked, and ignored without leak detection.
+    pub fn with_leak_backtraces(self) -> Self {
+        self.shared.backtraces.store(true, Ordering::SeqCst);
+        self
+    }
+
+    /// The rate reservations are limited to, if they are
+    pub fn rate_limit(&self) -> Option<RateLimit> {
+        self.shared.lock_recovered().limiter.as_ref().map(Bucket::limit)
+    }
+
+    /// Hand out idle resources in the order given by `strategy`, instead of first in, first out
+    pub fn with_strategy(self, strategy: Strategy) -> Self {
+        self.shared.lock_recovered().strategy = strategy;
+        self
+    }
+
+    /// The order idle resources are handed out in
+    pub fn strategy(&self) -> Strategy {
+        self.shared.lock_recovered().strategy
+    }
+
+    /// Raise a waiter's priority by one for each `every` it has waited, so that a steady stream of
+    /// `rsvp_with_priority` calls can't starve those with a lower priority
+    ///
+    /// Priorities only go as high as `255`, and an aged waiter is served before a later one with the
+    /// same priority. With an `every` of zero, waiters are served in the order they started waiting,
+    /// whatever their priority.
+    pub fn with_priority_aging(self, every: Duration) -> Self {
+        self.shared.lock_recovered().aging = Some(every);
+        self
+    }
+
+    /// Drop resources that have been idle for longer than `idle_timeout`
+    ///
+    /// Stale resources are dropped, freeing their slot, as the pool next hands out a resource or when
+    /// `evict_stale` is called. A `ManagedAsyncPool` replaces them on demand.
+    pub fn with_idle_timeout(self, idle_timeout: Duration) -> Self {
+        self.shared.lock_recovered().idle_timeout = Some(idle_timeout);
+        self
+    }
+
+    /// How long a resource may sit idle before it's dropped, if limited
+    pub fn idle_timeout(&self) -> Option<Duration> {
+        self.shared.lock_recovered().idle_timeout
+    }
+
+    /// Retire resources once they're older than `max_lifetime`
+    ///
+    /// Resources are retired when they're returned, or while idle as the pool next hands out a
+    /// resource or when `evict_stale` is called, but never while reserved. A `ManagedAsyncPool`
+    /// replaces them on demand.
+    pub fn with_max_lifetime(self, max_lifetime: Duration) -> Self {
+        self.shared.lock_recovered().max_lifetime = Some(max_lifetime);
+        self.shared.hot.set_max_lifetime(max_lifetime);
+        self
+    }
+
+    /// How long a resource may be owned by the pool before it's retired, if limited
+    pub fn max_lifetime(&self) -> Option<Duration> {
+        self.shared.lock_recovered().max_lifetime
+    }
+
+    /// Make every `rsvp` on this pool give up after `timeout`
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        self.set_timeout(Some(timeout));
+        self
+    }
+
+    /// Set or clear the time every `rsvp` waits before giving up
+    pub fn set_timeout(&self, timeout: Option<Duration>) {
+        self.shared.lock_recovered().timeout = timeout;
+    }
+
+    /// The time every `rsvp` waits before giving up, if any
+    pub fn timeout(&self) -> Option<Duration> {
+        self.shared.lock_recovered().timeout
+    }
+
+    /// Create a new `AsyncPool` using an initial set of resources
+    ///
+    /// This never actually waits; a pool can also be `collect`ed from any iterator in sync code.
+    pub async fn new_with(initial_resources: Vec<T>) -> Self {
+        initial_resources.into_iter().collect()
+    }
+
+    /// Await the next available resource
+    ///
+    /// Fails if the pool is closed, or if it was configured with a timeout and it elapsed.
+    ///
+    /// This is cancellation safe: resources are handed to waiters in two phases, so if the future is
+    /// dropped after a resource was set aside for it, the resource goes to the next waiter instead.
+    pub async fn rsvp(&self) -> Result<AsyncPoolGuard<T>, PoolError> {
+        if let Some(guard) = self.take_parked() {
+            return Ok(guard);
+        }
+
+        self.rsvp_with_priority(0).await
+    }
+
+    /// Await the next available resource, ahead of any waiters with a lower `priority`
+    ///
+    /// Waiters with the same priority are served in the order they started waiting. `rsvp` waits
+    /// with the lowest priority, `0`.
+    pub async fn rsvp_with_priority(&self, priority: u8) -> Result<AsyncPoolGuard<T>, PoolError> {
+        let wait = Wait::new(&self.shared, priority);
+
+        self.acquire_one(wait, self.timeout()).await
+    }
+
+    /// Await the next available resource, labelled `label` for diagnostics
+    ///
+    /// The label shows up in `waiters` while the call waits, then in `resource_stats`, leak reports
+    /// and the guard's `label` while the resource is reserved. With the `tracing` feature it's logged
+    /// with the reservation, and with `metrics` its wait is recorded under a `label` label too.
+    pub async fn rsvp_labeled(&self, label: &'static str) -> Result<AsyncPoolGuard<T>, PoolError> {
+        let wait = Wait::new(&self.shared, 0).labeled(label);
+
+        self.acquire_one(wait, self.timeout()).await
+    }
+
+    /// Await the next available resource, preferring the one last reserved with the same `key`
+    ///
+    /// This is for tasks that benefit from getting the same resource back, like a sticky session or
+    /// a connection with prepared statements. If that resource is reserved, or was since reserved
+    /// with another key, any idle one is used instead, so the preference never makes the call wait.
+    pub async fn rsvp_with_affinity(&self, key: impl Hash) -> Result<AsyncPoolGuard<T>, PoolError> {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        let wait = Wait::affine(&self.shared, hasher.finish());
+
+        self.acquire_one(wait, self.timeout()).await
+    }
+
+    /// Await the next available resource that was added with every one of `tags`
+    ///
+    /// This lets one pool hold different kinds of resources, like connections to a primary and to its
+    /// replicas. Matching resources go to the first waiter they match, so a call waits for as long
+    /// as the matching resources are reserved, even if others are idle.
+    pub async fn rsvp_matching<S: Into<String>>(
+        &self,
+        tags: impl IntoIterator<Item = S>,
+    ) -> Result<AsyncPoolGuard<T>, PoolError> {
+        let filter = Filter { tags: tags.into_iter().map(Into::into).collect(), ..Filter::default() };
+        let wait = Wait::matching(&self.shared, filter);
+
+        self.acquire_one(wait, self.timeout()).await
+    }
+
+    /// Await the next available resource with a weight of at least `weight`
+    ///
+    /// Of the idle resources heavy enough, the lightest is handed out, keeping heavier ones free for
+    /// calls that need them. Resources have a weight of 1 unless added with `add_weighted`.
+    pub async fn rsvp_weight(&self, weight: usize) -> Result<AsyncPoolGuard<T>, PoolError> {
+        let wait = Wait::matching(&self.shared, Filter { weight, ..Filter::default() });
+
+        self.acquire_one(wait, self.timeout()).await
+    }
+
+    /// Block the current thread until a resource is available, for use outside of async code
+    ///
+    /// This must never be called from a thread that runs async tasks, as they would stall until it
+    /// returns. With the `tokio` feature it panics when called from within a tokio runtime, and a
+    /// pool given `TokioRuntime` as its timer can't time out here, so keep the default timer.
+    ///
+    /// This isn't available on `wasm32-unknown-unknown`, where the thread can't be blocked.
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    pub fn rsvp_blocking(&self) -> Result<AsyncPoolGuard<T>, PoolError> {
+        crate::blocking::block_on(self.rsvp())
+    }
+
+    /// Await the next available resource, giving up with `PoolError::Cancelled` once `cancel` is done
+    ///
+    /// `cancel` can be any future, like a tokio-util `CancellationToken`'s `cancelled()`, so graceful
+    /// shutdown can unwind tasks stuck waiting on an exhausted pool. A cancelled call leaves the queue
+    /// straight away, and a resource already set aside for it goes to the next waiter. Nothing is
+    /// reserved if `cancel` is already done, and the pool's timeout still applies.
+    pub async fn rsvp_with_cancel(&self, cancel: impl Future) -> Result<AsyncPoolGuard<T>, PoolError> {
+        let rsvp = self.rsvp();
+        futures_util::pin_mut!(cancel, rsvp);
+
+        match select(cancel, rsvp).await {
+            Either::Left(_) => Err(PoolError::Cancelled),
+            Either::Right((result, _)) => result,
+        }
+    }
+
+    /// Await `n` resources at once, reserving none of them until all are available
+    ///
+    /// Waiting for the whole batch in one place in the queue means competing batches can't each end
+    /// up holding part of what the other needs. Fails with `PoolError::Full` if the pool can never
+    /// own `n` resources.
+    ///
+    /// A pool without a maximum size can always grow, so there a batch larger than the pool waits for
+    /// resources to be added. Meanwhile it holds on to every resource returned, and every waiter behind
+    /// it waits too, until the batch is filled, it times out, or it's cancelled.
+    pub async fn rsvp_many(&self, n: usize) -> Result<AsyncPoolBatchGuard<T>, PoolError> {
+        if self.max_size().is_some_and(|max| n > max) {
+            return Err(PoolError::Full);
+        }
+
+        let wait = Wait::many(&self.shared, 0, n);
+
+        let grant = self.acquire(wait, self.timeout()).await?;
+
+        Ok(AsyncPoolBatchGuard::new(self.shared.clone(), grant.into_many()))
+    }
+
+    /// Await the next available resource, through a future that owns this handle
+    ///
+    /// Unlike `rsvp`, the returned future doesn't borrow the pool, so it can be spawned as its own
+    /// task. Guards never borrow the pool either way, so they can always outlive the handle they came
+    /// from, though their resource is only returned if some handle still keeps the pool alive.
+    pub async fn rsvp_owned(self) -> Result<AsyncPoolGuard<T>, PoolError> {
+        self.rsvp().await
+    }
+
+    /// Stream guards on the pool's resources, reserving each as the stream is polled
+    pub fn guards(&self) -> Guards<T> {
+        Guards::new(self.clone())
+    }
+
+    /// A sink that adds every resource sent into it to the pool, like `add`
+    pub fn sink(&self) -> PoolSink<T> {
+        PoolSink::new(self.clone())
+    }
+
+    /// Await the next available resource without the pool's timeout, or `None` once it's closed
+    pub(crate) async fn rsvp_untimed(self) -> Option<AsyncPoolGuard<T>> {
+        self.acquire_one(Wait::new(&self.shared, 0), None).await.ok()
+    }
+
+    /// Reserve a resource just for the duration of `f`
+    ///
+    /// The guard never leaves this call, so the resource is returned as soon as `f` finishes, returns
+    /// early, panics, or is cancelled along with this future.
+    pub async fn with<R>(&self, f: impl AsyncFnOnce(&mut T) -> R) -> Result<R, PoolError> {
+        let mut guard = self.rsvp().await?;
+
+        Ok(f(&mut guard).await)
+    }
+
+    /// Run `f` with a `Scope` to reserve resources through, all of which are back in the pool by the
+    /// time this returns
+    ///
+    /// The guards from a `Scope` borrow it, so they can't be returned from `f`, stored outside it, or
+    /// sent to a spawned task. This makes it safe to, say, call `into_inner` right after.
+    pub async fn scope<R>(&self, f: impl for<'s> AsyncFnOnce(&'s Scope<'s, T>) -> R) -> R {
+        let scope = Scope::new(self);
+
+        f(&scope).await
+    }
+
+    /// Await the next available resource, giving up after `dur`
+    ///
+    /// This overrides the pool's default timeout.
+    pub async fn rsvp_timeout(&self, dur: Duration) -> Result<AsyncPoolGuard<T>, PoolError> {
+        self.acquire_one(Wait::new(&self.shared, 0), Some(dur)).await
+    }
+
+    /// Reserve a resource only if one is idle right now, without awaiting
+    ///
+    /// Returns `None` if every resource is currently reserved, or if the pool is closed or poisoned.
+    pub fn try_rsvp(&self) -> Option<AsyncPoolGuard<T>> {
+        if let Some(guard) = self.take_parked() {
+            return Some(guard);
+        }
+
+        self.shared.evict_stale();
+        let mut state = self.shared.lock().ok()?;
+
+        if state.closed {
+            return None;
+        }
+
+        if state.idle.is_empty() {
+            state.unpark_all();
+        }
+
+        if state.idle.is_empty() || state.limiter.as_mut().is_some_and(|bucket| !bucket.try_take()) {
+            return None;
+        }
+
+        let mut entry = state.idle.pop_front()?.entry;
+        entry.affinity = None;
+        state.reserve(&mut entry);
+        event!(id = entry.id, in_use = state.in_use, "Reserved an idle resource");
+        drop(state);
+
+        Some(AsyncPoolGuard::new(self.shared.clone(), entry))
+    }
+
+    /// Reserve the resource this thread parked, if it did, without locking the pool
+    fn take_parked(&self) -> Option<AsyncPoolGuard<T>> {
+        let (mut entry, hooks) = self.shared.hot.take()?;
+
+        // It could have outlived the pool's maximum lifetime while parked
+        if self.shared.hot.is_stale(&entry) {
+            let mut state = self.shared.lock_recovered();
+            state.unreserve(entry.id);
+            state.shrink();
+            drop(state);
+
+            event!(id = entry.id, "Retiring a stale parked resource");
+            self.shared.destroy(entry, None);
+            return None;
+        }
+
+        entry.checkouts += 1;
+        entry.used = Some(Instant::now());
+        entry.affinity = None;
+        event!(id = entry.id, "Reserved a resource parked on this thread");
+
+        Some(AsyncPoolGuard::hooked(self.shared.clone(), entry, &hooks))
+    }
+
+    /// Close the pool and hand back every resource, once none are reserved
+    ///
+    /// This is for shutting down cleanly, like flushing pooled file handles. Other handles to the pool
+    /// see it closed, and no hooks are run on the resources. Fails, handing the pool back open, if any
+    /// resources are still reserved or waited on.
+    pub fn into_inner(self) -> Result<Vec<T>, IntoInnerError<T>> {
+        let mut state = self.shared.lock_recovered();
+        state.unpark_all();
+
+        if state.in_use > 0 || !state.waiters.is_empty() || !state.granted.is_empty() {
+            let reserved = state.in_use;
+            drop(state);
+            return Err(IntoInnerError::new(self, reserved));
+        }
+
+        state.closed = true;
+        let mut items: Vec<T> = state.idle.drain(..).map(|idle| idle.entry.item).collect();
+        items.extend(state.quarantined.drain(..).map(|quarantined| quarantined.entry.item));
+        state.size = 0;
+        state.events.emit(PoolEvent::Resized { size: 0 });
+        state.events.occupancy(0, 0);
+        event!(resources = items.len(), "Tore down the pool");
+
+        Ok(items)
+    }
+
+    /// Remove and return every idle resource, shrinking the pool
+    ///
+    /// Reserved resources are left alone, and return to the pool as usual.
+    pub fn drain(&self) -> Vec<T> {
+        let mut state = self.shared.lock_recovered();
+        let drained: Vec<T> = state.idle.drain(..).map(|idle| idle.entry.item).collect();
+        for _ in &drained {
+            state.shrink();
+        }
+
+        drained
+    }
+
+    /// Drop every idle resource past the idle timeout or maximum lifetime, returning how many
+    pub fn evict_stale(&self) -> usize {
+        self.shared.evict_stale()
+    }
+
+    /// Evict stale resources every `interval`, until the pool is closed
+    ///
+    /// Without this, the idle timeout and maximum lifetime are only enforced as resources are handed
+    /// out. The future never borrows anything but the pool, so it can be spawned on any executor.
+    pub async fn maintain(self, interval: Duration) {
+        while !self.is_closed() {
+            self.sleep(interval).await;
+            self.evict_stale();
+        }
+    }
+
+    /// Run `maintain` in the background on `spawner`
+    pub fn spawn_maintenance(&self, spawner: &impl Spawner, interval: Duration) {
+        spawner.spawn(Box::pin(self.clone().maintain(interval)));
+    }
+
+    /// Resize the pool every `interval` as `policy` says, by how much of it is reserved and how long
+    /// reservations waited, until the pool is closed
+    ///
+    /// The maximum size is first brought within the policy's bounds. Growing only makes room: a
+    /// managed pool's waiting `rsvp` calls create resources in it, while other pools need `add`.
+    pub async fn autoscale(self, policy: AutoScale, interval: Duration) {
+        let size = self.max_size().unwrap_or_else(|| self.len());
+        self.resize(policy.clamp(size));
+        let mut scaler = Scaler::new(policy, self.wait_times());
+
+        while !self.is_closed() {
+            self.sleep(interval).await;
+
+            let (size, in_use) = {
+                let state = self.shared.lock_recovered();
+                (state.max_size.unwrap_or(state.size), state.in_use)
+            };
+            if let Some(size) = scaler.check(size, in_use, self.wait_times()) {
+                self.resize(size);
+            }
+        }
+    }
+
+    /// Run `autoscale` in the background on `spawner`
+    pub fn spawn_autoscaler(&self, spawner: &impl Spawner, policy: AutoScale, interval: Duration) {
+        spawner.spawn(Box::pin(self.clone().autoscale(policy, interval)));
+    }
+
+    /// Drop every idle resource for which `keep` returns `false`, returning how many were dropped
+    ///
+    /// Reserved resources are left alone. `keep` runs while the pool is locked, so it must not use
+    /// the pool. The dropped resources go through the destroy hook after it's unlocked.
+    pub fn retain(&self, mut keep: impl FnMut(&T) -> bool) -> usize {
+        let dropped: Vec<_> = {
+            let mut state = self.shared.lock_recovered();
+            let (kept, dropped): (VecDeque<_>, VecDeque<_>) = mem::take(&mut state.idle)
+                .into_iter()
+                .partition(|idle| keep(&idle.entry.item));
+            state.idle = kept;
+
+            for _ in &dropped {
+                state.shrink();
+            }
+
+            dropped.into_iter().map(|idle| idle.entry).collect()
+        };
+        let count = dropped.len();
+
+        for entry in dropped {
+            self.shared.destroy(entry, None);
+        }
+
+        count
+    }
+
+    /// Retire every resource the pool owns now, without disturbing the ones in use
+    ///
+    /// This is for recycling everything at once, like after credentials are rotated. Idle resources
+    /// are dropped right away, and reserved ones once they're returned, while resources added from now
+    /// on are kept as usual. Returns how many idle resources were dropped.
+    pub fn invalidate_all(&self) -> usize {
+        self.drop_idle(true)
+    }
+
+    /// Drop every idle resource right away, returning how many were dropped
+    ///
+    /// Reserved resources are left alone, and go back in the pool once they're returned as usual, as
+    /// do the ones set aside by the circuit breaker. This is for freeing memory under pressure, or
+    /// tearing down between tests, without closing the pool.
+    pub fn clear(&self) -> usize {
+        self.drop_idle(false)
+    }
+
+    /// Drop the idle resources and those parked on threads
+    ///
+    /// Invalidating the pool also drops the ones set aside, and retires the reserved ones once they're
+    /// returned.
+    fn drop_idle(&self, invalidate: bool) -> usize {
+        let dropped: Vec<_> = {
+            let mut state = self.shared.lock_recovered();
+
+            // Bump the generation first, so resources being parked meanwhile are either refused or taken
+            if invalidate {
+                state.generation += 1;
+                state.hot.generation.store(state.generation, Ordering::SeqCst);
+            }
+
+            let mut dropped: Vec<_> = state.idle.drain(..).map(|idle| idle.entry).collect();
+            for entry in self.shared.hot.take_all() {
+                state.unreserve(entry.id);
+                dropped.push(entry);
+            }
+            if invalidate {
+                dropped.extend(state.quarantined.drain(..).map(|quarantined| quarantined.entry));
+                event!(generation = state.generation, "Invalidated every resource");
+            }
+            for _ in &dropped {
+                state.shrink();
+            }
+
+            event!(dropped = dropped.len(), "Dropped the idle resources");
+            dropped
+        };
+        let count = dropped.len();
+
+        // Dropped outside the lock, in case their destructors use the pool
+        for entry in dropped {
+            self.shared.destroy(entry, None);
+        }
+
+        count
+    }
+
+    /// Apply `f` to every resource the pool owns: the idle ones right away, and the reserved ones once
+    /// they're returned, before anyone else can reserve them
+    ///
+    /// This is for pushing a change to long-lived resources, like a new setting or fresh credentials,
+    /// without waiting for them to be recycled. `f` runs while the pool is locked, so it must not use
+    /// the pool. Returns how many resources were changed right away.
+    pub fn map_resources(&self, f: impl Fn(&mut T) + Send + Sync + 'static) -> usize {
+        let f: Remap<T> = Arc::new(f);
+        let mut state = self.shared.lock_recovered();
+        let mut mapped = 0;
+
+        // Stop parking before taking back the parked resources, so none slip past the change
+        self.shared.hot.remapping.store(true, Ordering::SeqCst);
+
+        let waiting = state.granted.values_mut().flat_map(|grant| match grant {
+            Grant::One(entry) => vec![entry],
+            Grant::Many(entries) => entries.iter_mut().collect(),
+            Grant::Slot => Vec::new(),
+        });
+        for entry in waiting {
+            f(&mut entry.item);
+            mapped += 1;
+        }
+        for idle in &mut state.idle {
+            f(&mut idle.entry.item);
+            mapped += 1;
+        }
+        for quarantined in &mut state.quarantined {
+            f(&mut quarantined.entry.item);
+            mapped += 1;
+        }
+
+        // Parked resources count as reserved, so bring them back to map them now
+        for mut entry in self.shared.hot.take_all() {
+            f(&mut entry.item);
+            mapped += 1;
+            state.unreserve(entry.id);
+            state.requeue(entry);
+        }
+
+        let reserved: Vec<_> = state.reserved.keys().copied().collect();
+        for id in reserved {
+            state.remaps.entry(id).or_default().push(f.clone());
+        }
+        if state.remaps.is_empty() {
+            self.shared.hot.remapping.store(false, Ordering::SeqCst);
+        }
+
+        event!(mapped, "Mapped the idle resources");
+        mapped
+    }
+
+    /// Reserve each idle resource in turn to run `f` on it, returning how many it ran on
+    ///
+    /// This is for maintenance like pinging connections or refreshing their metadata. Only the
+    /// resources idle when this is called are visited, one at a time, and those reserved by someone
+    /// else in the meantime are skipped. Each goes back to the pool, and to the next waiter, as soon
+    /// as `f` is done with it, so waiters are held up for one call of `f` at most.
+    pub async fn for_each_idle(&self, mut f: impl AsyncFnMut(&mut T)) -> usize {
+        let mut visited = 0;
+
+        for id in self.idle_ids() {
+            if let Some(mut guard) = self.rsvp_idle(id) {
+                f(&mut guard).await;
+                visited += 1;
+            }
+        }
+
+        visited
+    }
+
+    /// How many handles to the pool are alive, counting clones but not guards or weak handles
+    pub(crate) fn handles(&self) -> usize {
+        Arc::strong_count(&self.shared)
+    }
+
+    /// When the most recently idled resource went idle, if any are
+    pub(crate) fn last_idle(&self) -> Option<Instant> {
+        self.shared.lock_recovered().idle.iter().map(|idle| idle.since).max()
+    }
+
+    /// The IDs of the idle resources, in the order they'd be handed out
+    pub(crate) fn idle_ids(&self) -> Vec<u64> {
+        let mut state = self.shared.lock_recovered();
+        state.unpark_all();
+        state.idle.iter().map(|idle| idle.entry.id).collect()
+    }
+
+    /// Take the resource with `id` out of the queue to be checked, if it's still idle
+    ///
+    /// Unlike reserving it, this doesn't count as a checkout, run hooks or use up the rate limit. The
+    /// resource goes back in its place once checked.
+    pub(crate) fn check_idle(&self, id: u64) -> Option<Checking<'_, T>> {
+        let mut state = self.shared.lock().ok()?;
+        let at = state.idle.iter().position(|idle| idle.entry.id == id).filter(|_| !state.closed)?;
+
+        let idle = state.idle.remove(at)?;
+        state.in_use += 1;
+
+        Some(Checking { shared: &self.shared, idle: Some(idle), at })
+    }
+
+    /// Reserve the resource with `id`, if it's still idle
+    fn rsvp_idle(&self, id: u64) -> Option<AsyncPoolGuard<T>> {
+        let mut state = self.shared.lock().ok()?;
+        let at = state.idle.iter().position(|idle| idle.entry.id == id).filter(|_| !state.closed)?;
+
+        let mut entry = state.idle.remove(at)?.entry;
+        state.reserve(&mut entry);
+        drop(state);
+
+        Some(AsyncPoolGuard::new(self.shared.clone(), entry))
+    }
+
+    /// How many times `invalidate_all` was called on the pool
+    pub fn generation(&self) -> u64 {
+        self.shared.lock_recovered().generation
+    }
+
+    /// Add `item` to the current `AsyncPool`.
+    ///
+    /// If the pool is at its maximum size, this waits for room, so a task feeding the pool can't grow
+    /// it past its bounds. Fails, handing `item` back, if the pool is closed or poisoned, or if the
+    /// pool's timeout passes first.
+    pub async fn add(&self, item: T) -> Result<(), AddError<T>> {
+        self.insert(item, BTreeSet::new(), 1).await
+    }
+
+    /// Add `item` to the pool if there's room for it right now
+    ///
+    /// Fails like `add`, and also with `PoolError::Full` instead of waiting.
+    pub fn try_add(&self, item: T) -> Result<(), AddError<T>> {
+        self.try_insert(item, BTreeSet::new(), 1)
+    }
+
+    /// Add `item` to the pool, labelled with `tags` for `rsvp_matching`
+    ///
+    /// Fails like `add`.
+    pub async fn add_tagged<S>(&self, item: T, tags: impl IntoIterator<Item = S>) -> Result<(), AddError<T>>
+    where
+        S: Into<String>,
+    {
+        self.insert(item, tags.into_iter().map(Into::into).collect(), 1).await
+    }
+
+    /// Add `item` to the pool with a capacity of `weight`, for `rsvp_weight`
+    ///
+    /// Fails like `add`.
+    pub async fn add_weighted(&self, item: T, weight: usize) -> Result<(), AddError<T>> {
+        self.insert(item, BTreeSet::new(), weight).await
+    }
+
+    /// Add `item` to the pool if there's room for it right now
+    pub(crate) fn try_insert(&self, item: T, tags: BTreeSet<String>, weight: usize) -> Result<(), AddError<T>> {
+        if let Err(e) = self.try_claim() {
+            return Err(AddError::new(e, item));
+        }
+
+        self.fill(item, tags, weight);
+        Ok(())
+    }
+
+    /// Add `item` to the pool, waiting for room if it's full
+    async fn insert(&self, item: T, tags: BTreeSet<String>, weight: usize) -> Result<(), AddError<T>> {
+        match self.try_claim() {
+            Ok(()) => {}
+            Err(PoolError::Full) => {
+                let wait = Wait::slot(&self.shared);
+                let claimed = match self.timeout() {
+                    Some(dur) => with_timeout(wait, self.sleep(dur)).await,
+                    None => wait.await,
+                };
+
+                if let Err(e) = claimed {
+                    return Err(AddError::new(e, item));
+                }
+            }
+            Err(e) => return Err(AddError::new(e, item)),
+        }
+
+        self.fill(item, tags, weight);
+        Ok(())
+    }
+
+    /// Count one more resource against the maximum size, failing if there's no room or the pool is
+    /// closed or poisoned
+    fn try_claim(&self) -> Result<(), PoolError> {
+        let mut state = self.shared.lock()?;
+
+        match (state.closed, state.claim_slot()) {
+            (true, _) => Err(PoolError::Closed),
+            (false, true) => Ok(()),
+            (false, false) => Err(PoolError::Full),
+        }
+    }
+
+    /// Put `item` into a slot claimed for it
+    fn fill(&self, item: T, tags: BTreeSet<String>, weight: usize) {
+        let mut entry = self.shared.create(item);
+        entry.tags = tags;
+        entry.weight = weight;
+        self.shared.put_back(self.shared.lock_recovered(), vec![entry], None);
+    }
+
+    /// Add every resource in `items` to the pool at once, waking as many waiters as they can serve
+    ///
+    /// The pool is locked once for the whole batch, rather than once per resource. Resources that
+    /// don't fit under the maximum size are handed back, with `PoolError::Full`.
+    pub async fn add_many(&self, items: impl IntoIterator<Item = T>) -> Result<(), AddError<Vec<T>>> {
+        self.insert_many(items)
+    }
+
+    /// Add every resource in `items` to the pool, like `add_many` but without the `async`
+    fn insert_many(&self, items: impl IntoIterator<Item = T>) -> Result<(), AddError<Vec<T>>> {
+        let mut state = match self.shared.lock() {
+            Ok(state) => state,
+            Err(e) => return Err(AddError::new(e, items.into_iter().collect())),
+        };
+
+        if state.closed {
+            return Err(AddError::new(PoolError::Closed, items.into_iter().collect()));
+        }
+
+        let (mut added, mut rejected) = (Vec::new(), Vec::new());
+        for item in items {
+            match state.claim_slot() {
+                true => added.push(item),
+                false => rejected.push(item),
+            }
+        }
+
+        let (hooks, generation) = (state.hooks.clone(), state.generation);
+        let stamped = |mut entry: Entry<T>| {
+            entry.generation = generation;
+            entry
+        };
+
+        if hooks.on_create.is_some() {
+            // Don't hold the lock while running user code
+            drop(state);
+            let entries: Vec<_> = added.into_iter().map(|item| stamped(Entry::create(item, &hooks))).collect();
+
+            // The pool may have been closed meanwhile
+            self.shared.put_back(self.shared.lock_recovered(), entries, None);
+        } else {
+            added.into_iter().for_each(|item| state.requeue(stamped(Entry::new(item))));
+        }
+
+        match rejected.is_empty() {
+            true => Ok(()),
+            false => Err(AddError::new(PoolError::Full, rejected)),
+        }
+    }
+
+    /// Stop handing out resources, then drop every resource once it's returned
+    ///
+    /// Any `rsvp` after, or still waiting when, the pool is closed fails with `PoolError::Closed`,
+    /// and `add` hands its resource back. Fails only if the pool is poisoned.
+    pub async fn close(&self) -> Result<(), PoolError> {
+        let dropped = {
+            let mut state = self.shared.lock()?;
+            state.closed = true;
+            self.shared.hot.disable();
+
+            let mut dropped: Vec<_> = state.idle.drain(..).map(|idle| idle.entry).collect();
+            dropped.extend(state.quarantined.drain(..).map(|quarantined| quarantined.entry));
+            for entry in self.shared.hot.take_all() {
+                state.unreserve(entry.id);
+                dropped.push(entry);
+            }
+            self.shared.hot.waiting.store(0, Ordering::SeqCst);
+            for (_, waiter) in mem::take(&mut state.waiters) {
+                waiter.waker.wake();
+                dropped.extend(waiter.held);
+            }
+            state.watchers.drain(..).for_each(Waker::wake);
+            for (_, grant) in state.granted.drain() {
+                dropped.extend(grant.into_many());
+            }
+
+            state.size -= dropped.len();
+            dropped
+        };
+
+        for entry in dropped {
+            if let Some((destructor, item)) = self.shared.retire(entry, None) {
+                destructor.close(item).await;
+            }
+        }
+
+        poll_fn(|cx| {
+            let mut state = self.shared.lock()?;
+
+            if state.size == 0 {
+                return Poll::Ready(Ok(()));
+            }
+
+            if !state.closers.iter().any(|w| w.will_wake(cx.waker())) {
+                state.closers.push(cx.waker().clone());
+            }
+
+            Poll::Pending
+        }).await
+    }
+
+    /// Move every resource of `source` into this pool, then close `source`
+    ///
+    /// The idle resources move right away, and the reserved ones as they're returned, keeping their
+    /// IDs, tags and stats. Those that don't fit under this pool's maximum size are dropped instead,
+    /// as are the ones `source` set aside with its circuit breaker. Waiters on `source` fail with
+    /// `PoolError::Closed`. Resolves once every resource has left `source`, and fails only if it's
+    /// poisoned.
+    pub async fn absorb(&self, source: &AsyncPool<T>) -> Result<(), PoolError> {
+        if Arc::ptr_eq(&self.shared, &source.shared) {
+            return Ok(());
+        }
+
+        source.hand_over(Successors { pools: vec![Arc::downgrade(&self.shared)], pick: Box::new(|_| 0) })?;
+        source.close().await
+    }
+
+    /// Split the pool's resources between `n` new pools, round-robin, then close it
+    ///
+    /// The idle resources move right away, and the reserved ones as they're returned, keeping their
+    /// IDs, tags and stats, so no resource is ever owned by two pools. The new pools have no maximum
+    /// size or other settings of their own. Waiters on this pool fail with `PoolError::Closed`, and
+    /// resources set aside by its circuit breaker are dropped. Fails only if the pool is poisoned.
+    ///
+    /// # Panics
+    ///
+    /// If `n` is zero.
+    pub fn split(&self, n: usize) -> Result<Vec<AsyncPool<T>>, PoolError> {
+        let mut next = 0;
+
+        self.split_by(n, move |_| {
+            next += 1;
+            next - 1
+        })
+    }
+
+    /// Split the pool's resources between `n` new pools, putting each in the pool numbered by
+    /// `pick`, modulo `n`, then close it
+    ///
+    /// This works like `split`. `pick` runs while the pool is locked, so it must not use the pool.
+    pub fn split_by(
+        &self,
+        n: usize,
+        pick: impl FnMut(&T) -> usize + Send + 'static,
+    ) -> Result<Vec<AsyncPool<T>>, PoolError> {
+        assert!(n > 0, "Can't split a pool into no pools");
+
+        let pools: Vec<_> = (0..n).map(|_| AsyncPool::new()).collect();
+        let successors = pools.iter().map(|pool| Arc::downgrade(&pool.shared)).collect();
+        self.hand_over(Successors { pools: successors, pick: Box::new(pick) })?;
+
+        Ok(pools)
+    }
+
+    /// Close the pool, handing its resources over to `successors`: the idle ones now, and the
+    /// reserved ones once they're returned
+    fn hand_over(&self, successors: Successors<T>) -> Result<(), PoolError> {
+        let (moved, dropped) = {
+            let mut state = self.shared.lock()?;
+            state.closed = true;
+            self.shared.hot.disable();
+
+            let mut moved: Vec<_> = state.idle.drain(..).map(|idle| idle.entry).collect();
+            for entry in self.shared.hot.take_all() {
+                state.unreserve(entry.id);
+                moved.push(entry);
+            }
+            self.shared.hot.waiting.store(0, Ordering::SeqCst);
+            for (_, waiter) in mem::take(&mut state.waiters) {
+                waiter.waker.wake();
+                moved.extend(waiter.held);
+            }
+            state.watchers.drain(..).for_each(Waker::wake);
+            for (_, grant) in state.granted.drain() {
+                moved.extend(grant.into_many());
+            }
+            let dropped: Vec<_> = state.quarantined.drain(..).map(|quarantined| quarantined.entry).collect();
+
+            state.size -= moved.len() + dropped.len();
+            if state.size == 0 {
+                state.closers.drain(..).for_each(Waker::wake);
+            }
+            state.successors = Some(successors);
+            event!(moved = moved.len(), "Handing over the pool's resources");
+            (moved, dropped)
+        };
+
+        for entry in dropped {
+            self.shared.destroy(entry, None);
+        }
+
+        // Each is picked for separately, so `pick` never runs while another pool is locked
+        for entry in moved {
+            let successor = self.shared.lock_recovered().successor(&entry.item);
+            self.shared.pass_on(successor, entry, None);
+        }
+
+        Ok(())
+    }
+
+    /// Wait until at least `n` resources are idle, without reserving any
+    ///
+    /// This is for holding off on reporting a service healthy until its pool is warm. Resources that
+    /// are handed straight to a waiting `rsvp` never count as idle, and by the time this resolves
+    /// another task could already have reserved some. Fails if the pool is closed or poisoned.
+    pub async fn wait_for_available(&self, n: usize) -> Result<(), PoolError> {
+        // Resources parked on threads would never count, so stop parking while this waits
+        let _watching = Watching::new(&self.shared.hot);
+
+        poll_fn(|cx| {
+            let mut state = self.shared.lock()?;
+            if state.closed {
+                return Poll::Ready(Err(PoolError::Closed));
+            }
+
+            if state.idle.len() < n {
+                state.unpark_all();
+            }
+            if state.idle.len() >= n {
+                return Poll::Ready(Ok(()));
+            }
+
+            if !state.watchers.iter().any(|w| w.will_wake(cx.waker())) {
+                state.watchers.push(cx.waker().clone());
+            }
+
+            Poll::Pending
+        }).await
+    }
+
+    /// Wait for the next resource to be returned or added to the pool, without reserving it
+    ///
+    /// This is for building admission control on top of the pool, instead of polling `try_rsvp`.
+    /// Only resources that come back after this is called count, and the resource could go straight
+    /// to a waiting `rsvp`, or another task, before the caller gets to it. Fails if the pool is closed
+    /// or poisoned.
+    pub fn notified(&self) -> impl Future<Output = Result<(), PoolError>> + '_ {
+        // Resources parked on threads don't go through the pool, so stop parking while this waits
+        let watching = Watching::new(&self.shared.hot);
+        let seen = self.shared.lock_recovered().put_backs;
+
+        poll_fn(move |cx| {
+            let _watching = &watching;
+            let mut state = self.shared.lock()?;
+            if state.closed {
+                return Poll::Ready(Err(PoolError::Closed));
+            }
+
+            if state.put_backs != seen {
+                return Poll::Ready(Ok(()));
+            }
+
+            if !state.watchers.iter().any(|w| w.will_wake(cx.waker())) {
+                state.watchers.push(cx.waker().clone());
+            }
+
+            Poll::Pending
+        })
+    }
+
+    /// The number of resources owned by the pool, reserved or not
+    pub fn len(&self) -> usize {
+        self.shared.lock_recovered().size
+    }
+
+    /// Whether the pool owns no resources at all
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The number of resources that aren't reserved
+    pub fn available(&self) -> usize {
+        let state = self.shared.lock_recovered();
+        state.size - state.in_use - state.quarantined.len()
+    }
+
+    /// The number of resources set aside by the circuit breaker
+    pub fn quarantined(&self) -> usize {
+        self.shared.lock_recovered().quarantined.len()
+    }
+
+    /// The number of resources currently reserved
+    pub fn in_use(&self) -> usize {
+        self.shared.lock_recovered().in_use
+    }
+
+    /// The number of `rsvp` calls waiting for a resource
+    pub fn waiting(&self) -> usize {
+        self.shared.lock_recovered().waiters.len()
+    }
+
+    /// Every `rsvp` call waiting for resources, in the order they'll be served
+    pub fn waiters(&self) -> Vec<WaiterInfo> {
+        let state = self.shared.lock_recovered();
+        let mut waiters: Vec<_> = state.waiters
+            .iter()
+            .map(|(key, waiter)| {
+                let info = WaiterInfo {
+                    label: waiter.label,
+                    priority: state.aged(key, waiter),
+                    wanted: waiter.wanted,
+                    waited: waiter.since.elapsed(),
+                };
+                (key.ticket, info)
+            })
+            .collect();
+
+        waiters.sort_by_key(|(ticket, info)| (Reverse(info.priority), *ticket));
+        waiters.into_iter().map(|(_, info)| info).collect()
+    }
+
+    /// The number of unreserved resources that were added with every one of `tags`
+    pub fn available_matching<S: Into<String>>(&self, tags: impl IntoIterator<Item = S>) -> usize {
+        let filter = Filter { tags: tags.into_iter().map(Into::into).collect(), ..Filter::default() };
+        self.shared.lock_recovered().idle.iter().filter(|idle| filter.matches(&idle.entry)).count()
+    }
+
+    /// How much each resource the pool owns has been used, by ID
+    ///
+    /// Resources waiting to be picked up by an `rsvp` are left out.
+    pub fn resource_stats(&self) -> Vec<ResourceStats> {
+        let state = self.shared.lock_recovered();
+        let mut stats: Vec<_> = state.idle.iter().map(|idle| idle.entry.stats(false)).collect();
+        stats.extend(state.reserved.values().copied());
+        stats.sort_by_key(|stats| stats.id);
+
+        stats
+    }
+
+    /// How long every `rsvp` that got its resources had waited for them
+    ///
+    /// Calls that failed, like those that timed out, aren't counted; nor is `try_rsvp`, which never
+    /// waits.
+    pub fn wait_times(&self) -> WaitHistogram {
+        self.shared.waits.snapshot()
+    }
+
+    /// Whether the pool has been closed
+    pub fn is_closed(&self) -> bool {
+        self.shared.lock_recovered().closed
+    }
+
+    /// Subscribe to everything that happens in the pool from now on
+    pub fn events(&self) -> PoolEvents {
+        self.shared.events.subscribe()
+    }
+
+    /// Get a handle to the pool that doesn't keep it alive
+    pub fn downgrade(&self) -> WeakPool<T> {
+        WeakPool {
+            shared: Arc::downgrade(&self.shared),
+        }
+    }
+
+    /// Await an idle resource, or a free slot to create one in
+    ///
+    /// Returns `None` once a slot was claimed for the caller, who must `fill_slot` or `release_slot`.
+    pub(crate) async fn rsvp_or_claim(&self) -> Result<Option<AsyncPoolGuard<T>>, PoolError> {
+        let wait = Wait::claiming(&self.shared, 0);
+        let started = Instant::now();
+
+        let grant = self.acquire(wait, self.timeout()).await?;
+        let waited = started.elapsed();
+
+        match grant {
+            Grant::Slot => Ok(None),
+            grant => Ok(Some(AsyncPoolGuard::new(self.shared.clone(), grant.into_one()).waited_for(waited))),
+        }
+    }
+
+    /// Await `wait` for one resource, as with `acquire`, and guard it
+    async fn acquire_one(&self, wait: Wait<'_, T>, timeout: Option<Duration>) -> Result<AsyncPoolGuard<T>, PoolError> {
+        let started = Instant::now();
+        let label = wait.label;
+        let grant = self.acquire(wait, timeout).await?;
+        let waited = started.elapsed();
+
+        Ok(AsyncPoolGuard::new(self.shared.clone(), grant.into_one()).waited_for(waited).labeled(label))
+    }
+
+    /// Await `wait`, failing if it takes longer than `timeout`
+    async fn acquire(&self, wait: Wait<'_, T>, timeout: Option<Duration>) -> Result<Grant<T>, PoolError> {
+        let started = Instant::now();
+        let label = wait.label;
+        event!(?timeout, label, "Reserving resources");
+
+        // A span for OpenTelemetry, as a child of the caller's, to show the wait in their trace
+        #[cfg(feature = "otel")]
+        let span = tracing::info_span!(
+            "async_pool.acquire",
+            otel.kind = "internal",
+            otel.status_code = tracing::field::Empty,
+            label,
+            waited_ms = tracing::field::Empty,
+        );
+
+        let reserve = async {
+            match self.throttle(timeout).await {
+                Ok(Some(dur)) => with_timeout(wait, self.sleep(dur)).await,
+                Ok(None) => wait.await,
+                Err(e) => Err(e),
+            }
+        };
+        #[cfg(feature = "otel")]
+        let reserve = tracing::Instrument::instrument(reserve, span.clone());
+        let result = reserve.await;
+
+        if result.is_ok() {
+            self.shared.waits.record(started.elapsed());
+            self.shared.events.waited(started.elapsed(), label);
+        }
+
+        #[cfg(feature = "otel")]
+        {
+            span.record("waited_ms", started.elapsed().as_millis() as u64);
+            if result.is_err() {
+                span.record("otel.status_code", "ERROR");
+            }
+        }
+
+        #[cfg(feature = "tracing")]
+        match &result {
+            Ok(grant) => {
+                tracing::debug!(reserved = grant.len(), waited = ?started.elapsed(), label, "Reserved resources")
+            }
+            Err(e) => tracing::debug!(error = %e, waited = ?started.elapsed(), label, "Failed to reserve resources"),
+        }
+
+        result
+    }
+
+    /// Wait for a token from the rate limiter, if there is one, returning what's left of `timeout`
+    async fn throttle(&self, timeout: Option<Duration>) -> Result<Option<Duration>, PoolError> {
+        let delay = match self.shared.lock()?.limiter.as_mut() {
+            Some(bucket) => bucket.take(timeout)?,
+            None => return Ok(timeout),
+        };
+
+        if !delay.is_zero() {
+            event!(?delay, "Rate limited, waiting for a token");
+            self.sleep(delay).await;
+        }
+
+        Ok(timeout.map(|timeout| timeout.saturating_sub(delay)))
+    }
+
+    /// Wait for `dur` to pass on the pool's timer
+    pub(crate) fn sleep(&self, dur: Duration) -> Sleep {
+        let timer = self.shared.lock_recovered().timer.clone();
+        timer.sleep(dur)
+    }
+
+    /// Count one more resource against the maximum size, if there's room for it and the pool is open
+    pub(crate) fn claim_slot(&self) -> bool {
+        self.shared.lock_recovered().claim_slot()
+    }
+
+    /// Report a manager failing to create a resource for the pool
+    pub(crate) fn failed_to_create(&self) {
+        self.shared.events.failed_to_create();
+    }
+
+    /// Give back a slot claimed for a resource that never made it into the pool
+    pub(crate) fn release_slot(&self) {
+        self.shared.lock_recovered().shrink();
+    }
+
+    /// Take out up to all but `keep` of the idle resources that have been idle for at least `idle_for`
+    ///
+    /// They keep their slots and IDs, to be given back with `destroy_slot` or guarded again with
+    /// `guard_entry`.
+    pub(crate) fn take_idle(&self, keep: usize, idle_for: Duration) -> Vec<Entry<T>> {
+        let mut state = self.shared.lock_recovered();
+        if state.closed {
+            return Vec::new();
+        }
+
+        let mut excess = state.idle.len().saturating_sub(keep);
+        let (taken, kept): (VecDeque<_>, _) = mem::take(&mut state.idle).into_iter().partition(|idle| {
+            let take = excess > 0 && idle.since.elapsed() >= idle_for;
+            excess -= usize::from(take);
+            take
+        });
+        state.idle = kept;
+
+        taken.into_iter().map(|idle| idle.entry).collect()
+    }
+
+    /// Put a resource that was taken out of the pool but kept its slot back in, unreserved
+    pub(crate) fn restore_slot(&self, entry: Entry<T>) {
+        self.shared.put_back(self.shared.lock_recovered(), vec![entry], None);
+    }
+
+    /// Drop a resource that was taken out of the pool but kept its slot, giving the slot back
+    pub(crate) fn destroy_slot(&self, entry: Entry<T>) {
+        self.release_slot();
+        self.shared.destroy(entry, None);
+    }
+
+    /// Put a resource created for a claimed slot into the pool, unreserved
+    pub(crate) fn fill_slot(&self, item: T) {
+        let entry = self.shared.create(item);
+        self.shared.put_back(self.shared.lock_recovered(), vec![entry], None);
+    }
+
+    /// Guard a resource created for a claimed slot
+    pub(crate) fn guard(&self, item: T) -> AsyncPoolGuard<T> {
+        self.guard_entry(self.shared.create(item))
+    }
+
+    /// Guard a resource that was taken out of the pool but kept its slot, as it was
+    pub(crate) fn guard_entry(&self, mut entry: Entry<T>) -> AsyncPoolGuard<T> {
+        self.shared.lock_recovered().reserve(&mut entry);
+
+        AsyncPoolGuard::new(self.shared.clone(), entry)
+    }
+}
+
+impl<T: Send + 'static> Clone for AsyncPool<T> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T: Send + 'static> WeakPool<T> {
+    /// Get the pool back, if it's still alive
+    pub fn upgrade(&self) -> Option<AsyncPool<T>> {
+        self.shared.upgrade().map(|shared| AsyncPool { shared })
+    }
+}
+
+impl<T: Send + 'static> Clone for WeakPool<T> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T: Send + 'static> Default for AsyncPool<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Send + 'static> FromIterator<T> for AsyncPool<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let new = Self::new();
+
+        {
+            let mut state = new.shared.lock_recovered();
+            state.idle.extend(iter.into_iter().map(|item| Idle::new(Entry::new(item))));
+            state.size = state.idle.len();
+        }
+
+        new
+    }
+}
+
+/// Adds each resource like `add`, dropping those that don't fit because the pool is full or closed
+impl<T: Send + 'static> Extend<T> for AsyncPool<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        let _ = self.insert_many(iter);
+    }
+}
+
+impl<T: Send + 'static> Shared<T> {
+    /// Lock the pool's state, handling poisoning according to the pool's policy
+    pub(crate) fn lock(&self) -> Result<MutexGuard<'_, State<T>>, PoolError> {
+        let state = match self.state.lock() {
+            Ok(state) => return Ok(state),
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        match state.poison_policy {
+            PoisonPolicy::Error => Err(PoolError::Poisoned),
+            PoisonPolicy::Recover => Ok(state),
+            PoisonPolicy::Panic => panic!("Pool state was poisoned by a panic"),
+        }
+    }
+
+    /// Lock the pool's state, even if it was poisoned
+    ///
+    /// This is for paths that can't fail, like `Drop`. The state is never left half-updated, as only
+    /// wakers and the resources' own destructors can panic while it's locked.
+    pub(crate) fn lock_recovered(&self) -> MutexGuard<'_, State<T>> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Record where the resources with `ids` were just reserved, if leak reports should say
+    ///
+    /// The backtrace is captured before locking the pool, as it's slow.
+    pub(crate) fn trace(&self, ids: impl IntoIterator<Item = u64>) {
+        if !self.backtraces.load(Ordering::SeqCst) {
+            return;
+        }
+
+        let backtrace = Arc::new(Backtrace::force_capture());
+        if let Some(leaks) = &mut self.lock_recovered().leaks {
+            for id in ids {
+                leaks.trace(id, backtrace.clone());
+            }
+        }
+    }
+
+    /// The callbacks run through each resource's life
+    pub(crate) fn hooks(&self) -> Arc<Hooks<T>> {
+        self.lock_recovered().hooks.clone()
+    }
+
+    /// Start tracking a resource that's entering the pool
+    pub(crate) fn create(&self, item: T) -> Entry<T> {
+        let (hooks, generation) = {
+            let state = self.lock_recovered();
+            (state.hooks.clone(), state.generation)
+        };
+
+        let mut entry = Entry::create(item, &hooks);
+        entry.generation = generation;
+        entry
+    }
+
+    /// Drop a resource the pool no longer counts, spawning its async destructor if it has one
+    pub(crate) fn destroy(&self, entry: Entry<T>, reserved: Option<Instant>) {
+        if let Some((destructor, item)) = self.retire(entry, reserved) {
+            destructor.spawn(item);
+        }
+    }
+
+    /// Run the destroy hook on a resource the pool no longer counts, handing it back if it still needs
+    /// its async destructor run
+    pub(crate) fn retire(&self, mut entry: Entry<T>, reserved: Option<Instant>) -> Option<(Arc<AsyncDestroy<T>>, T)> {
+        let (hooks, destructor) = {
+            let state = self.lock_recovered();
+            (state.hooks.clone(), state.destructor.clone())
+        };
+
+        let info = entry.info(reserved);
+        hooks::run(&hooks.on_destroy, &mut entry.item, info);
+        self.events.emit(PoolEvent::Discarded);
+
+        Some((destructor?, entry.item))
+    }
+
+    /// Take back a resource from a dropped guard, retiring it if it's past its maximum lifetime
+    pub(crate) fn release(self: &Arc<Self>, mut entry: Entry<T>, reserved: Instant) {
+        entry.held += reserved.elapsed();
+
+        // Resources the circuit breaker has its eye on always go through the pool
+        let parked = match entry.failures == 0 && !entry.probing {
+            true => self.hot.park(entry, reserved, || self.hooks()),
+            false => Err((entry, false)),
+        };
+
+        let (entry, released) = match parked {
+            Ok(()) => {
+                event!("Parked a returned resource on this thread");
+                self.events.emit(PoolEvent::Returned);
+                return;
+            }
+            Err(back) => back,
+        };
+
+        let state = self.lock_recovered();
+
+        match state.returner.clone() {
+            Some(returner) if !state.closed => {
+                drop(state);
+                let (item, meta) = entry.split();
+                let returned = returner.run(item);
+                let shared = Arc::downgrade(self);
+
+                returner.spawn(Box::pin(async move {
+                    let item = returned.await;
+                    if let Some(shared) = shared.upgrade() {
+                        shared.finish_return(meta, item, reserved);
+                    }
+                }));
+            }
+            _ => self.put_returned(state, entry, reserved, released),
+        }
+    }
+
+    /// Take back a resource once the async on-return hook is done with it
+    fn finish_return(&self, meta: Entry<()>, item: Option<T>, reserved: Instant) {
+        let mut state = self.lock_recovered();
+
+        match item {
+            Some(item) => self.put_returned(state, meta.join(item), reserved, false),
+            None => {
+                event!(id = meta.id, "Dropping a resource its on-return hook gave up on");
+                state.unreserve(meta.id);
+                state.shrink();
+                self.events.emit(PoolEvent::Discarded);
+            }
+        }
+    }
+
+    /// Put a returned resource back in the pool, unless it's to be retired or set aside, running the
+    /// release hook unless it already ran
+    fn put_returned<'a>(
+        &'a self,
+        mut state: MutexGuard<'a, State<T>>,
+        mut entry: Entry<T>,
+        reserved: Instant,
+        released: bool,
+    ) {
+        let remaps = state.take_remaps(entry.id);
+        state.unreserve(entry.id);
+
+        if state.successors.is_some() {
+            let successor = state.successor(&entry.item);
+            state.shrink();
+            drop(state);
+            self.pass_on(successor, entry, Some(reserved));
+            return;
+        }
+
+        if state.retires(&entry) {
+            event!(id = entry.id, "Retiring a returned resource");
+            state.shrink();
+            drop(state);
+            self.destroy(entry, Some(reserved));
+            return;
+        }
+
+        for remap in remaps.into_iter().flatten() {
+            remap(&mut entry.item);
+        }
+
+        if let Some(until) = state.breaker.as_ref().and_then(|breaker| breaker.trip(&mut entry)) {
+            event!(id = entry.id, failures = entry.failures, "Setting aside a failing resource");
+            self.events.emit(PoolEvent::Quarantined);
+            state.quarantined.push(Quarantined { entry, until });
+            return;
+        }
+
+        let hooks = state.hooks.clone();
+        if hooks.on_release.is_some() && !released {
+            // Don't hold the lock while running user code
+            drop(state);
+            let info = entry.info(Some(reserved));
+            hooks::run(&hooks.on_release, &mut entry.item, info);
+            state = self.lock_recovered();
+        }
+
+        event!(id = entry.id, in_use = state.in_use, "Returned a resource");
+        self.events.emit(PoolEvent::Returned);
+        // The pool may have been closed while the hook ran
+        self.put_back(state, vec![entry], Some(reserved));
+    }
+
+    /// Put unreserved resources back, destroying them instead if the pool was closed while it was
+    /// unlocked
+    fn put_back<'a>(&'a self, mut state: MutexGuard<'a, State<T>>, entries: Vec<Entry<T>>, reserved: Option<Instant>) {
+        let mut closed = Vec::new();
+        for entry in entries {
+            if let Err(entry) = state.put_back(entry) {
+                state.shrink();
+                closed.push(entry);
+            }
+        }
+        drop(state);
+
+        for entry in closed {
+            self.destroy(entry, reserved);
+        }
+    }
+
+    /// Drop the idle resources past the idle timeout or maximum lifetime, returning how many
+    pub(crate) fn evict_stale(&self) -> usize {
+        let (stale, leaks) = {
+            let mut state = self.lock_recovered();
+            state.reinstate();
+            let leaks = state.leaks.as_mut().map(|leaks| (leaks.overdue(), leaks.on_leak.clone()));
+            (state.take_stale(), leaks)
+        };
+        let evicted = stale.len();
+
+        // Checked here as it's run whenever a resource is waited for
+        if let Some((leaks, on_leak)) = leaks {
+            for leak in &leaks {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(id = leak.id, held = ?leak.held, label = ?leak.label, "A guard was held for too long");
+                on_leak(leak);
+            }
+        }
+
+        if evicted > 0 {
+            event!(evicted, "Evicted stale resources");
+        }
+
+        // Dropped outside the lock, in case their destructors use the pool
+        for entry in stale {
+            self.destroy(entry, None);
+        }
+
+        evicted
+    }
+
+    /// Start tracking `item`, reserved in place of the resource with `id`, which leaves the pool
+    pub(crate) fn replace(&self, id: u64, item: T) -> Entry<T> {
+        let mut entry = self.create(item);
+        let mut state = self.lock_recovered();
+        state.unreserve(id);
+        state.reserve(&mut entry);
+        event!(id, new_id = entry.id, "Replaced a reserved resource");
+
+        entry
+    }
+
+    /// Hand a resource that's no longer counted over to `successor`, or drop it if it's gone or full
+    fn pass_on(&self, successor: Option<Arc<Shared<T>>>, entry: Entry<T>, reserved: Option<Instant>) {
+        let rejected = match successor {
+            Some(successor) => successor.adopt(entry).err(),
+            None => Some(entry),
+        };
+
+        if let Some(entry) = rejected {
+            self.destroy(entry, reserved);
+        }
+    }
+
+    /// Take in a resource handed over by another pool, or hand it back if there's no room for it
+    fn adopt(&self, mut entry: Entry<T>) -> Result<(), Entry<T>> {
+        let mut state = self.lock_recovered();
+        if !state.claim_slot() {
+            return Err(entry);
+        }
+
+        entry.generation = state.generation;
+        state.requeue(entry);
+        Ok(())
+    }
+
+    /// Stop counting a resource that was taken out of its guard
+    pub(crate) fn forget(&self, id: u64) {
+        let mut state = self.lock_recovered();
+        state.unreserve(id);
+        state.shrink();
+        event!(id, size = state.size, "Took a reserved resource out of the pool");
+    }
+}
+
+impl<T: Send + 'static> State<T> {
+    /// Queue up a new waiter, returning its place in the queue
+    pub(crate) fn enqueue(&mut self, priority: u8, waiter: Waiter<T>) -> WaiterKey {
+        let key = WaiterKey {
+            priority: Reverse(priority),
+            ticket: self.next_ticket,
+        };
+        self.next_ticket += 1;
+        self.waiters.insert(key, waiter);
+        self.hot.waiting.fetch_add(1, Ordering::SeqCst);
+
+        key
+    }
+
+    /// The first waiter in line that `wants` a resource, with priorities raised by aging
+    fn first_waiter(&self, wants: impl Fn(&Waiter<T>) -> bool) -> Option<WaiterKey> {
+        let mut waiting = self.waiters.iter().filter(|(_, waiter)| wants(waiter));
+
+        match self.aging {
+            None => waiting.next().map(|(&key, _)| key),
+            Some(_) => waiting
+                .min_by_key(|(key, waiter)| (Reverse(self.aged(key, waiter)), key.ticket))
+                .map(|(&key, _)| key),
+        }
+    }
+
+    /// A waiter's priority, raised by one for each aging interval it has waited
+    fn aged(&self, key: &WaiterKey, waiter: &Waiter<T>) -> u8 {
+        let every = match self.aging {
+            Some(every) => every.as_nanos(),
+            None => return key.priority.0,
+        };
+        let raised = waiter.since.elapsed().as_nanos().checked_div(every).unwrap_or(u128::MAX);
+
+        u128::from(key.priority.0).saturating_add(raised).min(u8::MAX.into()) as u8
+    }
+
+    /// Take a waiter out of the queue
+    pub(crate) fn dequeue(&mut self, key: &WaiterKey) -> Option<Waiter<T>> {
+        let waiter = self.waiters.remove(key)?;
+        self.hot.waiting.fetch_sub(1, Ordering::SeqCst);
+
+        Some(waiter)
+    }
+
+    /// Take back every resource parked on a thread, handing them to waiters or leaving them idle
+    pub(crate) fn unpark_all(&mut self) {
+        for entry in self.hot.take_all() {
+            self.unreserve(entry.id);
+            self.requeue(entry);
+        }
+    }
+
+    /// Count `entry` as reserved
+    pub(crate) fn reserve(&mut self, entry: &mut Entry<T>) {
+        self.reserve_labeled(entry, None);
+    }
+
+    /// Count `entry` as reserved, by a reservation labelled `label`
+    pub(crate) fn reserve_labeled(&mut self, entry: &mut Entry<T>, label: Option<&'static str>) {
+        entry.checkouts += 1;
+        entry.used = Some(Instant::now());
+        self.in_use += 1;
+        self.reserved.insert(entry.id, ResourceStats { label, ..entry.stats(true) });
+        self.events.occupancy(self.size, self.in_use);
+
+        if let Some(leaks) = &mut self.leaks {
+            leaks.track(entry.id, label);
+        }
+    }
+
+    /// Stop counting the resource with `id` as reserved
+    fn unreserve(&mut self, id: u64) {
+        self.in_use -= 1;
+        self.reserved.remove(&id);
+        self.take_remaps(id);
+        self.events.occupancy(self.size, self.in_use);
+
+        if let Some(leaks) = &mut self.leaks {
+            leaks.untrack(id);
+        }
+    }
+
+    /// The pool to hand `item` over to, if the pool was handed over and it's still alive
+    fn successor(&mut self, item: &T) -> Option<Arc<Shared<T>>> {
+        let successors = self.successors.as_mut()?;
+        let at = (successors.pick)(item) % successors.pools.len();
+
+        successors.pools[at].upgrade()
+    }
+
+    /// Take the changes from `map_resources` still to be applied to the resource with `id`
+    fn take_remaps(&mut self, id: u64) -> Option<Vec<Remap<T>>> {
+        let remaps = self.remaps.remove(&id)?;
+        if self.remaps.is_empty() {
+            self.hot.remapping.store(false, Ordering::SeqCst);
+        }
+
+        Some(remaps)
+    }
+
+    /// Whether `entry` should leave the pool instead of going back in: whether the pool is closed or
+    /// oversized, or the resource is invalidated or past its maximum lifetime
+    fn retires(&self, entry: &Entry<T>) -> bool {
+        let oversized = self.max_size.is_some_and(|max| self.size > max);
+        let expired = self.max_lifetime.is_some_and(|max| entry.created.elapsed() >= max);
+
+        self.closed || oversized || entry.generation < self.generation || expired
+    }
+
+    /// Put a checked resource back at `at` in the queue, unless someone's waiting for it
+    fn restore(&mut self, idle: Idle<T>, at: usize) {
+        if self.first_waiter(|waiter| !waiter.only_slots && waiter.filter.matches(&idle.entry)).is_some() {
+            self.requeue(idle.entry);
+        } else {
+            self.idle.insert(at.min(self.idle.len()), idle);
+        }
+    }
+
+    /// Put back a resource that can't reach a closed pool, as `close` takes every parked, set-aside
+    /// and waiter-held resource itself, and the lock is held from checking the pool is open
+    pub(crate) fn requeue(&mut self, entry: Entry<T>) {
+        if let Err(entry) = self.put_back(entry) {
+            debug_assert!(false, "Resource {} was requeued into a closed pool", entry.id);
+            self.shrink();
+        }
+    }
+
+    /// Hand an unreserved resource to the first waiting `rsvp`, or leave it idle
+    ///
+    /// If the pool is closed, it's handed back, for the caller to shrink the pool and destroy it
+    /// outside the lock.
+    pub(crate) fn put_back(&mut self, entry: Entry<T>) -> Result<(), Entry<T>> {
+        if self.closed {
+            return Err(entry);
+        }
+
+        self.put_backs += 1;
+        self.watchers.drain(..).for_each(Waker::wake);
+
+        let key = match self.first_waiter(|waiter| !waiter.only_slots && waiter.filter.matches(&entry)) {
+            Some(key) => key,
+            None => {
+                // The front of the queue is handed out first
+                match self.strategy {
+                    Strategy::Fifo => self.idle.push_back(Idle::new(entry)),
+                    Strategy::Lifo => self.idle.push_front(Idle::new(entry)),
+                    Strategy::Lru => {
+                        let at = self.idle.partition_point(|idle| idle.entry.used <= entry.used);
+                        self.idle.insert(at, Idle::new(entry));
+                    }
+                }
+
+                return Ok(());
+            }
+        };
+
+        // Batches hold on to what they're handed until they have everything they're waiting for
+        let waiter = self.waiters.get_mut(&key).expect("Waiter was just found");
+        waiter.held.push(entry);
+
+        if waiter.held.len() == waiter.wanted {
+            let waiter = self.dequeue(&key).expect("Waiter was just found");
+
+            let grant = match waiter.wanted {
+                1 => Grant::One(waiter.held.into_iter().next().expect("Waiter was just handed a resource")),
+                _ => Grant::Many(waiter.held),
+            };
+
+            self.granted.insert(key.ticket, grant);
+            waiter.waker.wake();
+        }
+
+        Ok(())
+    }
+
+    /// Where the idle resource to hand a waiter with `filter` is, preferring one last reserved with
+    /// `affinity`, then the lightest
+    pub(crate) fn find_idle(&self, filter: &Filter, affinity: Option<u64>) -> Option<usize> {
+        let matching = self.idle.iter().enumerate().filter(|(_, idle)| filter.matches(&idle.entry));
+
+        let affine = affinity.and_then(|key| matching.clone().find(|(_, idle)| idle.entry.affinity == Some(key)));
+        let (at, _) = affine.or_else(|| matching.min_by_key(|(_, idle)| idle.entry.weight))?;
+
+        Some(at)
+    }
+
+    /// Stop counting the idle resources past the idle timeout or maximum lifetime, and hand them over
+    fn take_stale(&mut self) -> Vec<Entry<T>> {
+        let (idle_timeout, max_lifetime, generation) = (self.idle_timeout, self.max_lifetime, self.generation);
+
+        if idle_timeout.is_none() && max_lifetime.is_none() && generation == 0 {
+            return Vec::new();
+        }
+
+        // Resources created before the pool was last invalidated may have slipped in since
+        let (stale, fresh): (VecDeque<_>, _) = mem::take(&mut self.idle)
+            .into_iter()
+            .partition(|idle| idle.entry.generation < generation || idle.is_stale(idle_timeout, max_lifetime));
+        self.idle = fresh;
+
+        for _ in &stale {
+            self.shrink();
+        }
+
+        stale.into_iter().map(|idle| idle.entry).collect()
+    }
+
+    /// Put the resources set aside by the circuit breaker back on probation, once their time is up
+    fn reinstate(&mut self) {
+        if self.quarantined.is_empty() {
+            return;
+        }
+
+        let now = Instant::now();
+        let (ready, waiting) = mem::take(&mut self.quarantined).into_iter().partition(|q| q.until <= now);
+        self.quarantined = waiting;
+
+        for Quarantined { mut entry, .. } in ready {
+            event!(id = entry.id, "Putting a set-aside resource on probation");
+            entry.failures = 0;
+            entry.probing = true;
+            self.requeue(entry);
+        }
+    }
+
+    /// Count a new resource, if there's room for it and the pool is open
+    pub(crate) fn claim_slot(&mut self) -> bool {
+        if self.closed || self.max_size.is_some_and(|max| self.size >= max) {
+            return false;
+        }
+
+        self.size += 1;
+        self.events.emit(PoolEvent::Resized { size: self.size });
+        self.events.occupancy(self.size, self.in_use);
+        true
+    }
+
+    /// Stop counting a resource, waking `close` if it was the last
+    ///
+    /// While the pool is open, the first waiter that can create a resource is woken to claim the
+    /// freed slot.
+    pub(crate) fn shrink(&mut self) {
+        self.size -= 1;
+        if self.max_size.is_none_or(|max| self.size <= max) {
+            self.hot.oversized.store(false, Ordering::SeqCst);
+        }
+        self.events.emit(PoolEvent::Resized { size: self.size });
+        self.events.occupancy(self.size, self.in_use);
+
+        if !self.closed {
+            if let Some(key) = self.first_waiter(|waiter| waiter.claims_slots) {
+                self.waiters[&key].waker.wake_by_ref();
+            }
+        } else if self.size == 0 {
+            for waker in self.closers.drain(..) {
+                waker.wake();
+            }
+        }
+    }
+}
+
+impl<T> Entry<T> {
+    /// Start counting the age of a new resource
+    pub(crate) fn new(item: T) -> Self {
+        Self {
+            item,
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            created: Instant::now(),
+            used: None,
+            checkouts: 0,
+            held: Duration::ZERO,
+            affinity: None,
+            tags: BTreeSet::new(),
+            weight: 1,
+            failures: 0,
+            probing: false,
+            generation: 0,
+        }
+    }
+
+    /// Set the resource aside from what the pool knows about it
+    pub(crate) fn split(self) -> (T, Entry<()>) {
+        let Self {
+            item,
+            id, created, used, checkouts, held, affinity, tags, weight, failures, probing, generation,
+        } = self;
+        let meta = Entry {
+            item: (),
+            id, created, used, checkouts, held, affinity, tags, weight, failures, probing, generation,
+        };
+
+        (item, meta)
+    }
+
+    /// Start tracking a resource that's entering the pool, running its create hook
+    pub(crate) fn create(item: T, hooks: &Hooks<T>) -> Self {
+        let mut entry = Self::new(item);
+        let info = entry.info(None);
+        hooks::run(&hooks.on_create, &mut entry.item, info);
+
+        entry
+    }
+
+    /// How much the resource has been used
+    fn stats(&self, reserved: bool) -> ResourceStats {
+        ResourceStats {
+            id: self.id,
+            checkouts: self.checkouts,
+            held: self.held,
+            last_used: self.used,
+            reserved,
+            label: None,
+        }
+    }
+
+    /// The resource's timings, for hooks
+    pub(crate) fn info(&self, reserved: Option<Instant>) -> ResourceInfo {
+        ResourceInfo {
+            id: self.id,
+            created: self.created,
+            reserved,
+        }
+    }
+}
+
+impl Entry<()> {
+    /// Put back a resource set aside by `split`
+    pub(crate) fn join<T>(self, item: T) -> Entry<T> {
+        let Self {
+            item: (),
+            id, created, used, checkouts, held, affinity, tags, weight, failures, probing, generation,
+        } = self;
+
+        Entry { item, id, created, used, checkouts, held, affinity, tags, weight, failures, probing, generation }
+    }
+}
+
+impl<T: Send + 'static> Checking<'_, T> {
+    pub(crate) fn item_mut(&mut self) -> &mut T {
+        &mut self.idle.as_mut().expect("Resource dropped while being checked").entry.item
+    }
+
+    /// Drop the resource for failing its check
+    pub(crate) fn fail(mut self) {
+        let idle = self.idle.take().expect("Resource dropped while being checked");
+        event!(id = idle.entry.id, "Discarding an idle resource that failed validation");
+
+        let mut state = self.shared.lock_recovered();
+        state.in_use -= 1;
+        state.shrink();
+        drop(state);
+
+        self.shared.destroy(idle.entry, None);
+    }
+}
+
+impl<T: Send + 'static> Drop for Checking<'_, T> {
+    fn drop(&mut self) {
+        let idle = match self.idle.take() {
+            Some(idle) => idle,
+            None => return,
+        };
+
+        let mut state = self.shared.lock_recovered();
+        state.in_use -= 1;
+        if !state.retires(&idle.entry) {
+            state.restore(idle, self.at);
+            return;
+        }
+
+        state.shrink();
+        drop(state);
+        self.shared.destroy(idle.entry, None);
+    }
+}
+
+impl<T> Idle<T> {
+    /// Start counting how long `entry` has been idle
+    pub(crate) fn new(entry: Entry<T>) -> Self {
+        Self {
+            entry,
+            since: Instant::now(),
+        }
+    }
+
+    /// Whether the resource has been idle or alive for too long
+    fn is_stale(&self, idle_timeout: Option<Duration>, max_lifetime: Option<Duration>) -> bool {
+        idle_timeout.is_some_and(|max| self.since.elapsed() >= max)
+            || max_lifetime.is_some_and(|max| self.entry.created.elapsed() >= max)
+    }
+}
+
+/// Fail `wait` if it isn't done before `delay`
+async fn with_timeout<T: Send + 'static>(wait: Wait<'_, T>, delay: Sleep) -> Result<Grant<T>, PoolError> {
+    match select(wait, delay).await {
+        Either::Left((guard, _)) => guard,
+        Either::Right(_) => Err(PoolError::Timeout),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_executor::block_on;
+    use futures_timer::Delay;
+    use futures_util::{future, pin_mut, stream::StreamExt};
+
+    use super::*;
+
+    #[test]
+    fn try_rsvp_returns_none_when_exhausted() {
+        block_on(async {
+            let pool = AsyncPool::new_with(vec![1]).await;
+
+            let guard = pool.try_rsvp().expect("Resource should be idle");
+            assert_eq!(*guard, 1);
+            assert!(pool.try_rsvp().is_none());
+
+            drop(guard);
+            assert!(pool.try_rsvp().is_some());
+        });
+    }
+
+    #[test]
+    fn rsvp_timeout_expires_when_exhausted() {
+        block_on(async {
+            let pool = AsyncPool::new_with(vec![1]).await;
+
+            let guard = pool.rsvp_timeout(Duration::from_millis(10)).await;
+            assert!(guard.is_ok());
+
+            let timed_out = pool.rsvp_timeout(Duration::from_millis(10)).await;
+            assert_eq!(timed_out.err(), Some(PoolError::Timeout));
+        });
+    }
+
+    #[test]
+    fn default_timeout_applies_to_rsvp() {
+        block_on(async {
+            let pool = AsyncPool::new_with(vec![1]).await
+                .with_timeout(Duration::from_millis(10));
+
+            let _guard = pool.rsvp().await.expect("Resource should be idle");
+            assert_eq!(pool.rsvp().await.err(), Some(PoolError::Timeout));
+        });
+    }
+
+    #[test]
+    fn pools_collect_and_extend_without_awaiting() {
+        let mut pool: AsyncPool<_> = (1..=2).collect();
+        assert_eq!(pool.len(), 2);
+
+        let mut limited = pool.clone().with_max_size(3);
+        limited.extend(vec![3, 4]);
+        assert_eq!(pool.len(), 3);
+
+        pool.extend(Some(5));
+        assert_eq!(pool.drain(), [1, 2, 3]);
+    }
+
+    #[test]
+    fn lifo_hands_out_the_last_returned_first() {
+        block_on(async {
+            let pool = AsyncPool::new_with(vec![1, 2, 3]).await.with_strategy(Strategy::Lifo);
+            let (one, two) = (pool.rsvp().await.unwrap(), pool.rsvp().await.unwrap());
+
+            drop((two, one));
+            assert_eq!(pool.drain(), [1, 2, 3]);
+
+            let fifo = AsyncPool::new_with(vec![1, 2, 3]).await;
+            drop((fifo.rsvp().await.unwrap(), fifo.rsvp().await.unwrap()));
+            assert_eq!(fifo.drain(), [3, 1, 2]);
+        });
+    }
+
+    #[test]
+    fn lru_hands_out_the_least_recently_reserved_first() {
+        block_on(async {
+            let pool = AsyncPool::new_with(vec![1, 2, 3]).await.with_strategy(Strategy::Lru);
+            let one = pool.rsvp().await.unwrap();
+            std::thread::sleep(Duration::from_millis(1));
+            let two = pool.rsvp().await.unwrap();
+
+            drop((two, one));
+            assert_eq!(pool.drain(), [3, 1, 2]);
+        });
+    }
+
+    #[test]
+    fn affinity_prefers_the_resource_last_used_with_the_key() {
+        block_on(async {
+            let pool = AsyncPool::new_with(vec![1, 2, 3]).await;
+            let (one, two) = (pool.rsvp().await.unwrap(), pool.rsvp_with_affinity("session").await.unwrap());
+            assert_eq!(*two, 2);
+
+            drop((two, one));
+            assert_eq!(*pool.rsvp_with_affinity("session").await.unwrap(), 2);
+            assert_eq!(*pool.rsvp_with_affinity("other").await.unwrap(), 3);
+        });
+    }
+
+    #[test]
+    fn tagged_rsvps_wait_for_a_matching_resource() {
+        block_on(async {
+            let pool = AsyncPool::new();
+            pool.add_tagged("primary", vec!["db", "write"]).await.unwrap();
+            pool.add_tagged("replica", vec!["db"]).await.unwrap();
+            assert_eq!(pool.available_matching(vec!["db"]), 2);
+
+            let primary = pool.rsvp_matching(vec!["write"]).await.unwrap();
+            assert_eq!(*primary, "primary");
+
+            let mut waiting = Box::pin(pool.rsvp_matching(vec!["write"]));
+            assert!(futures_util::poll!(waiting.as_mut()).is_pending());
+            assert_eq!(*pool.rsvp_matching(vec!["db"]).await.unwrap(), "replica");
+
+            drop(primary);
+            assert_eq!(*waiting.await.unwrap(), "primary");
+        });
+    }
+
+    #[test]
+    fn weighted_rsvps_get_the_lightest_resource_that_fits() {
+        block_on(async {
+            let pool = AsyncPool::new();
+            pool.add_weighted("16gb", 16).await.unwrap();
+            pool.add_weighted("8gb", 8).await.unwrap();
+            pool.add_weighted("4gb", 4).await.unwrap();
+
+            let eight = pool.rsvp_weight(6).await.unwrap();
+            assert_eq!(*eight, "8gb");
+            assert_eq!(*pool.rsvp_weight(6).await.unwrap(), "16gb");
+
+            let _sixteen = pool.rsvp_weight(12).await.unwrap();
+            let mut waiting = Box::pin(pool.rsvp_weight(6));
+            assert!(futures_util::poll!(waiting.as_mut()).is_pending());
+
+            drop(eight);
+            assert_eq!(*waiting.await.unwrap(), "8gb");
+        });
+    }
+
+    #[test]
+    fn resource_stats_count_checkouts_and_hold_time() {
+        block_on(async {
+            let pool = AsyncPool::new_with(vec![1, 2]).await;
+            drop(pool.rsvp().await.unwrap());
+            let guard = pool.rsvp().await.unwrap();
+            let second = guard.id();
+            std::thread::sleep(Duration::from_millis(1));
+            drop(guard);
+            let _guard = pool.rsvp().await.unwrap();
+
+            let stats = pool.resource_stats();
+            assert_eq!(stats.iter().map(|stats| (stats.checkouts, stats.reserved)).collect::<Vec<_>>(), [
+                (2, true),
+                (1, false),
+            ]);
+            assert_eq!(stats[1].id, second);
+            assert!(stats[1].held >= Duration::from_millis(1));
+        });
+    }
+
+    #[test]
+    fn labels_follow_the_reservation() {
+        block_on(async {
+            let pool = AsyncPool::new_with(vec![1, 2]).await;
+            let labeled = pool.rsvp_labeled("checkout").await.unwrap();
+            let plain = pool.rsvp().await.unwrap();
+
+            assert_eq!((labeled.label(), plain.label()), (Some("checkout"), None));
+            let labels: Vec<_> = pool.resource_stats().iter().map(|stats| stats.label).collect();
+            assert_eq!(labels, [Some("checkout"), None]);
+
+            drop(labeled);
+            assert_eq!(pool.resource_stats()[0].label, None);
+        });
+    }
+
+    #[test]
+    fn waiters_are_listed_in_queue_order() {
+        block_on(async {
+            let pool = AsyncPool::<u8>::new();
+            let mut plain = Box::pin(pool.rsvp());
+            let mut labeled = Box::pin(pool.rsvp_labeled("checkout"));
+            let mut urgent = Box::pin(pool.rsvp_with_priority(1));
+            assert!(futures_util::poll!(plain.as_mut()).is_pending());
+            assert!(futures_util::poll!(labeled.as_mut()).is_pending());
+            assert!(futures_util::poll!(urgent.as_mut()).is_pending());
+
+            let waiters = pool.waiters();
+            let order: Vec<_> = waiters.iter().map(|waiter| (waiter.label, waiter.priority)).collect();
+            assert_eq!(order, [(None, 1), (None, 0), (Some("checkout"), 0)]);
+            assert!(waiters[1].waited >= waiters[2].waited);
+        });
+    }
+
+    #[test]
+    fn weak_pools_upgrade_only_while_the_pool_lives() {
+        let pool: AsyncPool<_> = Some(1).into_iter().collect();
+        let weak = pool.downgrade();
+
+        assert_eq!(*weak.upgrade().unwrap().try_rsvp().unwrap(), 1);
+        drop(pool);
+        assert!(weak.upgrade().is_none());
+    }
+
+    #[test]
+    fn add_many_serves_waiters_and_hands_back_the_rest() {
+        block_on(async {
+            let pool = AsyncPool::new_with(vec![1]).await.with_max_size(3);
+            let _guard = pool.rsvp().await.unwrap();
+
+            let mut waiting = Box::pin(pool.rsvp());
+            assert!(futures_util::poll!(waiting.as_mut()).is_pending());
+
+            let rejected = pool.add_many(vec![2, 3, 4]).await.unwrap_err();
+            assert_eq!((rejected.kind(), rejected.into_inner()), (PoolError::Full, vec![4]));
+            assert_eq!(*waiting.await.unwrap(), 2);
+            assert_eq!(pool.drain(), [3, 2]);
+        });
+    }
+
+    #[test]
+    fn resizing_retires_idle_resources_then_returned_ones() {
+        block_on(async {
+            let pool = AsyncPool::new_with(vec![1, 2, 3]).await;
+            let (first, second) = (pool.rsvp().await.unwrap(), pool.rsvp().await.unwrap());
+
+            assert_eq!(pool.resize(1), 1);
+            assert_eq!(pool.len(), 2);
+            drop(first);
+            assert_eq!((pool.len(), pool.available()), (1, 0));
+            drop(second);
+            assert_eq!((pool.len(), pool.available()), (1, 1));
+
+            assert!(pool.try_add(4).is_err());
+            assert_eq!(pool.resize(2), 0);
+            assert!(pool.add(4).await.is_ok());
+        });
+    }
+
+    #[test]
+    fn invalidated_resources_are_retired_once_returned() {
+        block_on(async {
+            let pool = AsyncPool::new_with(vec![1, 2]).await;
+            let reserved = pool.rsvp().await.unwrap();
+
+            assert_eq!((pool.invalidate_all(), pool.generation()), (1, 1));
+            assert_eq!(*reserved, 1);
+            drop(reserved);
+            assert!(pool.is_empty());
+
+            pool.add(3).await.unwrap();
+            drop(pool.rsvp().await.unwrap());
+            assert_eq!(pool.available(), 1);
+        });
+    }
+
+    #[test]
+    fn clear_drops_only_idle_resources() {
+        block_on(async {
+            let pool = AsyncPool::new_with(vec![1, 2, 3]).await;
+            let reserved = pool.rsvp().await.unwrap();
+
+            assert_eq!(pool.clear(), 2);
+            assert_eq!((pool.len(), pool.available()), (1, 0));
+            drop(reserved);
+            assert_eq!(*pool.rsvp().await.unwrap(), 1);
+        });
+    }
+
+    #[test]
+    fn map_resources_changes_reserved_resources_once_returned() {
+        block_on(async {
+            let pool = AsyncPool::new_with(vec![1, 2]).await.with_thread_cache();
+            let mut reserved = pool.rsvp().await.unwrap();
+            drop(pool.rsvp().await.unwrap());
+
+            assert_eq!(pool.map_resources(|n| *n *= 10), 1);
+            *reserved += 1;
+            assert_eq!(*pool.try_rsvp().unwrap(), 20);
+
+            drop(reserved);
+            drop(pool.rsvp().await.unwrap());
+            let mut all = pool.into_inner().unwrap();
+            all.sort();
+            assert_eq!(all, vec![20, 20]);
+        });
+    }
+
+    #[test]
+    fn for_each_idle_visits_only_the_idle_resources() {
+        block_on(async {
+            let pool = AsyncPool::new_with(vec![1, 2, 3]).await;
+            let reserved = pool.rsvp().await.unwrap();
+
+            let visited = pool.for_each_idle(async |n: &mut i32| {
+                assert_eq!(pool.available(), 1);
+                *n += 10;
+            }).await;
+
+            assert_eq!((visited, *reserved), (2, 1));
+            drop(reserved);
+            assert_eq!(pool.into_inner().unwrap().iter().sum::<i32>(), 26);
+        });
+    }
+
+    #[test]
+    fn absorbed_pools_hand_over_their_resources_as_they_are_returned() {
+        block_on(async {
+            let pool = AsyncPool::new_with(vec![1]).await.with_max_size(4);
+            let source = AsyncPool::new_with(vec![2, 3, 4]).await;
+            let reserved = source.rsvp().await.unwrap();
+
+            let absorbing = pool.absorb(&source);
+            futures_util::pin_mut!(absorbing);
+            assert!(futures_util::poll!(absorbing.as_mut()).is_pending());
+            assert_eq!((pool.len(), source.len(), source.rsvp().await.err()), (3, 1, Some(PoolError::Closed)));
+
+            drop(reserved);
+            absorbing.await.unwrap();
+            assert_eq!(source.len(), 0);
+            assert_eq!(pool.drain().iter().sum::<i32>(), 10);
+        });
+    }
+
+    #[test]
+    fn split_pools_share_out_every_resource_once() {
+        block_on(async {
+            let pool = AsyncPool::new_with(vec![1, 2, 3, 4]).await;
+            let reserved = pool.rsvp().await.unwrap();
+
+            let halves = pool.split_by(2, |n| (n % 2) as usize).unwrap();
+            assert_eq!((halves[0].len(), halves[1].len(), pool.len()), (2, 1, 1));
+            assert!(pool.is_closed());
+
+            drop(reserved);
+            assert_eq!((halves[1].drain(), pool.len()), (vec![3, 1], 0));
+            assert_eq!(pool.split(3).unwrap().iter().map(AsyncPool::len).sum::<usize>(), 0);
+        });
+    }
+
+    #[test]
+    fn wait_for_available_resolves_once_enough_resources_are_idle() {
+        block_on(async {
+            let pool = AsyncPool::new_with(vec![1]).await.with_thread_cache();
+            let guard = pool.rsvp().await.unwrap();
+
+            let warm = pool.wait_for_available(2);
+            pin_mut!(warm);
+            assert!(futures_util::poll!(warm.as_mut()).is_pending());
+            pool.add(2).await.unwrap();
+            assert!(futures_util::poll!(warm.as_mut()).is_pending());
+
+            drop(guard);
+            warm.await.unwrap();
+            assert_eq!(pool.available(), 2);
+        });
+    }
+
+    #[test]
+    fn notified_wakes_once_a_resource_is_returned() {
+        block_on(async {
+            let pool = AsyncPool::new_with(vec![1]).await.with_thread_cache();
+            let guard = pool.rsvp().await.unwrap();
+
+            let notified = pool.notified();
+            pin_mut!(notified);
+            assert!(futures_util::poll!(notified.as_mut()).is_pending());
+
+            drop(guard);
+            notified.await.unwrap();
+            assert_eq!(pool.available(), 1);
+        });
+    }
+
+    #[test]
+    fn into_inner_waits_for_every_guard() {
+        block_on(async {
+            let pool = AsyncPool::new_with(vec![1, 2]).await;
+            let guard = pool.rsvp().await.unwrap();
+
+            let err = pool.clone().into_inner().unwrap_err();
+            assert_eq!(err.reserved(), 1);
+            assert!(!err.into_pool().is_closed());
+
+            drop(guard);
+            let handle = pool.clone();
+            let mut items = pool.into_inner().unwrap();
+            items.sort();
+            assert_eq!(items, [1, 2]);
+            assert!(handle.is_closed() && handle.is_empty());
+        });
+    }
+
+    #[test]
+    fn try_add_fails_past_max_size() {
+        block_on(async {
+            let pool = AsyncPool::new().with_max_size(1);
+
+            assert!(pool.try_add(1).is_ok());
+            let err = pool.try_add(2).unwrap_err();
+            assert_eq!((err.kind(), err.into_inner()), (PoolError::Full, 2));
+        });
+    }
+
+    #[test]
+    fn add_waits_for_room() {
+        block_on(async {
+            let pool = AsyncPool::new_with(vec![1]).await.with_max_size(1);
+            let mut adding = Box::pin(pool.add(2));
+            assert!(futures_util::poll!(adding.as_mut()).is_pending());
+
+            // A returned resource takes the slot back, but a discarded one frees it
+            drop(pool.rsvp().await.unwrap());
+            assert!(futures_util::poll!(adding.as_mut()).is_pending());
+            pool.rsvp().await.unwrap().discard();
+            adding.await.unwrap();
+            assert_eq!(*pool.rsvp().await.unwrap(), 2);
+
+            let err = pool.with_timeout(Duration::from_millis(1)).add(3).await.unwrap_err();
+            assert_eq!((err.kind(), err.into_inner()), (PoolError::Timeout, 3));
+        });
+    }
+
+    #[test]
+    fn on_return_resets_resources() {
+        block_on(async {
+            let pool = AsyncPool::new_with(vec![Vec::new()]).await
+                .with_on_return(Vec::clear);
+
+            pool.rsvp().await.unwrap().push(1);
+            assert!(pool.rsvp().await.unwrap().is_empty());
+        });
+    }
+
+    #[test]
+    fn stale_resources_are_evicted() {
+        block_on(async {
+            let pool = AsyncPool::new_with(vec![1, 2]).await
+                .with_idle_timeout(Duration::from_millis(10));
+
+            drop(pool.rsvp().await.unwrap());
+            assert_eq!(pool.evict_stale(), 0);
+
+            Delay::new(Duration::from_millis(20)).await;
+            assert_eq!(pool.len(), 2);
+            assert!(pool.try_rsvp().is_none());
+            assert_eq!(pool.len(), 0);
+        });
+    }
+
+    #[test]
+    fn old_resources_are_retired() {
+        block_on(async {
+            let pool = AsyncPool::new_with(vec![1, 2]).await
+                .with_max_lifetime(Duration::from_millis(10));
+
+            let guard = pool.rsvp().await.unwrap();
+            Delay::new(Duration::from_millis(20)).await;
+
+            drop(guard);
+            assert_eq!(pool.len(), 1);
+            assert_eq!(pool.evict_stale(), 1);
+            assert!(pool.is_empty());
+        });
+    }
+
+    #[test]
+    fn maintain_evicts_until_closed() {
+        block_on(async {
+            let pool = AsyncPool::new_with(vec![1, 2]).await
+                .with_idle_timeout(Duration::from_millis(5));
+            let maintain = pool.clone().maintain(Duration::from_millis(1));
+            pin_mut!(maintain);
+
+            assert!(futures_util::poll!(maintain.as_mut()).is_pending());
+            Delay::new(Duration::from_millis(20)).await;
+            assert!(futures_util::poll!(maintain.as_mut()).is_pending());
+            assert!(pool.is_empty());
+
+            pool.close().await.unwrap();
+            maintain.await;
+        });
+    }
+
+    #[test]
+    fn retain_drops_only_rejected_idle_resources() {
+        block_on(async {
+            let pool = AsyncPool::new_with(vec![1, 2, 3, 4]).await;
+            let guard = pool.rsvp().await.unwrap();
+
+            assert_eq!(pool.retain(|n| n % 2 == 0), 1);
+            assert_eq!(pool.len(), 3);
+
+            drop(guard);
+            assert_eq!(pool.drain(), [2, 4, 1]);
+        });
+    }
+
+    #[test]
+    fn take_frees_a_slot() {
+        block_on(async {
+            let pool = AsyncPool::new().with_max_size(1);
+            pool.add(1).await.unwrap();
+
+            assert_eq!(pool.rsvp().await.unwrap().take(), 1);
+            assert!(pool.try_rsvp().is_none());
+            assert!(pool.add(2).await.is_ok());
+        });
+    }
+
+    #[test]
+    fn drain_takes_only_idle_resources() {
+        block_on(async {
+            let pool = AsyncPool::new_with(vec![1, 2, 3]).await.with_max_size(3);
+            let guard = pool.rsvp().await.unwrap();
+
+            assert_eq!(pool.drain(), vec![2, 3]);
+            assert!(pool.try_rsvp().is_none());
+            assert!(pool.add(4).await.is_ok());
+
+            drop(guard);
+            assert_eq!(pool.drain(), vec![4, 1]);
+        });
+    }
+
+    #[test]
+    fn draining_makes_room_for_waiting_adds() {
+        block_on(async {
+            let pool = AsyncPool::new_with(vec![1]).await.with_max_size(1);
+            let events = pool.events();
+
+            let mut add = Box::pin(pool.add(2));
+            assert!(futures_util::poll!(add.as_mut()).is_pending());
+
+            assert_eq!(pool.drain(), vec![1]);
+            assert!(add.await.is_ok());
+            drop(pool);
+
+            let events: Vec<_> = events.collect().await;
+            let resized: Vec<_> = events.into_iter()
+                .filter(|event| matches!(event, PoolEvent::Resized { .. }))
+                .collect();
+            assert_eq!(resized, [PoolEvent::Resized { size: 0 }, PoolEvent::Resized { size: 1 }]);
+        });
+    }
+
+    #[test]
+    fn stats_track_reservations_and_waiters() {
+        block_on(async {
+            let pool = AsyncPool::new_with(vec![1, 2]).await;
+            let guard = pool.rsvp().await.unwrap();
+            assert_eq!((pool.len(), pool.available(), pool.in_use()), (2, 1, 1));
+
+            let _other = pool.rsvp().await.unwrap();
+            let waiter = pool.rsvp();
+            pin_mut!(waiter);
+            assert!(futures_util::poll!(waiter.as_mut()).is_pending());
+            assert_eq!((pool.available(), pool.waiting()), (0, 1));
+
+            drop(guard);
+            let guard = waiter.await.unwrap();
+            assert_eq!((pool.in_use(), pool.waiting()), (2, 0));
+
+            guard.discard();
+            assert_eq!((pool.len(), pool.in_use()), (1, 1));
+        });
+    }
+
+    #[test]
+    fn clones_share_resources() {
+        block_on(async {
+            let pool = AsyncPool::new_with(vec![1]).await;
+            let clone = pool.clone();
+
+            let guard = clone.rsvp().await.unwrap();
+            assert!(pool.try_rsvp().is_none());
+
+            drop((guard, clone));
+            assert!(pool.try_rsvp().is_some());
+        });
+    }
+
+    #[test]
+    fn rsvp_owned_outlives_the_handle() {
+        let pool = block_on(AsyncPool::new_with(vec![1]));
+        let rsvp = pool.clone().rsvp_owned();
+
+        let guard = std::thread::spawn(move || block_on(rsvp)).join().unwrap().unwrap();
+        assert!(pool.try_rsvp().is_none());
+
+        drop(pool);
+        assert_eq!(*guard, 1);
+    }
+
+    #[test]
+    fn with_returns_the_resource_afterwards() {
+        block_on(async {
+            let pool = AsyncPool::new_with(vec![1]).await;
+
+            let doubled = pool.with(async |n: &mut i32| {
+                *n *= 2;
+                *n
+            }).await;
+            assert_eq!(doubled, Ok(2));
+            assert_eq!((pool.available(), pool.in_use()), (1, 0));
+
+            let early: Result<Option<i32>, _> = pool.with(async |n: &mut i32| {
+                let odd = Some(*n).filter(|n| n % 2 == 1)?;
+                Some(odd)
+            }).await;
+            assert_eq!(early, Ok(None));
+            assert_eq!(*pool.try_rsvp().unwrap(), 2);
+        });
+    }
+
+    #[test]
+    fn rsvp_blocking_parks_until_a_return() {
+        let pool = block_on(AsyncPool::new_with(vec![1]));
+        let guard = pool.rsvp_blocking().unwrap();
+
+        let waiter = {
+            let pool = pool.clone();
+            std::thread::spawn(move || *pool.rsvp_blocking().unwrap())
+        };
+
+        while pool.waiting() == 0 {
+            std::thread::yield_now();
+        }
+
+        drop(guard);
+        assert_eq!(waiter.join().unwrap(), 1);
+    }
+
+    #[test]
+    fn waiters_are_served_in_arrival_order() {
+        block_on(async {
+            let pool = AsyncPool::new_with(vec![0]).await;
+            let mut guard = pool.rsvp().await.unwrap();
+
+            let mut waiters: Vec<_> = (0..3).map(|_| Box::pin(pool.rsvp())).collect();
+            for waiter in waiters.iter_mut() {
+                assert!(futures_util::poll!(waiter.as_mut()).is_pending());
+            }
+
+            while !waiters.is_empty() {
+                *guard += 1;
+                drop(guard);
+
+                // Only the longest waiting gets the resource
+                for later in waiters.iter_mut().skip(1) {
+                    assert!(futures_util::poll!(later.as_mut()).is_pending());
+                }
+
+                guard = waiters.remove(0).await.unwrap();
+            }
+            assert_eq!(*guard, 3);
+        });
+    }
+
+    #[test]
+    fn higher_priority_waiters_go_first() {
+        block_on(async {
+            let pool = AsyncPool::new_with(vec![()]).await;
+            let guard = pool.rsvp().await.unwrap();
+
+            let mut low = Box::pin(pool.rsvp());
+            let mut high = Box::pin(pool.rsvp_with_priority(1));
+            assert!(futures_util::poll!(low.as_mut()).is_pending());
+            assert!(futures_util::poll!(high.as_mut()).is_pending());
+
+            drop(guard);
+            assert!(futures_util::poll!(low.as_mut()).is_pending());
+            drop(high.await.unwrap());
+            assert!(low.await.is_ok());
+        });
+    }
+
+    #[test]
+    fn cancelled_waits_leave_the_queue_without_taking_a_resource() {
+        block_on(async {
+            let pool = AsyncPool::new_with(vec![1]).await;
+            let cancelled = std::cell::Cell::new(false);
+            let cancel = poll_fn(|_| if cancelled.get() { Poll::Ready(()) } else { Poll::Pending });
+
+            let guard = pool.rsvp().await.unwrap();
+            let mut waiting = Box::pin(pool.rsvp_with_cancel(cancel));
+            let mut next = Box::pin(pool.rsvp());
+            assert!(futures_util::poll!(waiting.as_mut()).is_pending());
+            assert!(futures_util::poll!(next.as_mut()).is_pending());
+
+            // The resource is set aside for `waiting`, which is cancelled before it can pick it up
+            drop(guard);
+            cancelled.set(true);
+            assert_eq!(waiting.await.err(), Some(PoolError::Cancelled));
+            assert_eq!((*next.await.unwrap(), pool.waiting()), (1, 0));
+
+            assert_eq!(pool.rsvp_with_cancel(future::ready(())).await.err(), Some(PoolError::Cancelled));
+            assert_eq!(pool.available(), 1);
+        });
+    }
+
+    #[test]
+    fn long_waiters_age_past_later_higher_priorities() {
+        block_on(async {
+            let pool = AsyncPool::new_with(vec![()]).await.with_priority_aging(Duration::from_millis(10));
+            let guard = pool.rsvp().await.unwrap();
+
+            let mut low = Box::pin(pool.rsvp_labeled("low"));
+            assert!(futures_util::poll!(low.as_mut()).is_pending());
+            std::thread::sleep(Duration::from_millis(25));
+
+            let mut high = Box::pin(pool.rsvp_with_priority(1));
+            assert!(futures_util::poll!(high.as_mut()).is_pending());
+            let waiters = pool.waiters();
+            assert_eq!((waiters[0].label, waiters[0].priority, waiters[1].priority), (Some("low"), 2, 1));
+
+            drop(guard);
+            assert!(futures_util::poll!(high.as_mut()).is_pending());
+            drop(low.await.unwrap());
+            assert!(high.await.is_ok());
+        });
+    }
+
+    #[test]
+    fn rsvp_many_waits_for_the_whole_batch() {
+        block_on(async {
+            let pool = AsyncPool::new_with(vec![1, 2, 3]).await.with_max_size(3);
+            let guard = pool.rsvp().await.unwrap();
+
+            let mut batch = Box::pin(pool.rsvp_many(3));
+            assert!(futures_util::poll!(batch.as_mut()).is_pending());
+            assert!(pool.try_rsvp().is_none());
+
+            drop(guard);
+            let batch = batch.await.unwrap();
+            assert_eq!(batch.len(), 3);
+            assert_eq!(pool.in_use(), 3);
+
+            drop(batch);
+            assert_eq!(pool.available(), 3);
+            assert_eq!(pool.rsvp_many(4).await.err(), Some(PoolError::Full));
+        });
+    }
+
+    #[test]
+    fn cancelled_rsvp_many_returns_what_it_held() {
+        block_on(async {
+            let pool = AsyncPool::new_with(vec![1, 2]).await;
+
+            let mut batch = Box::pin(pool.rsvp_many(3));
+            assert!(futures_util::poll!(batch.as_mut()).is_pending());
+            drop(batch);
+
+            assert_eq!(pool.drain().len(), 2);
+        });
+    }
+
+    #[test]
+    fn rsvp_many_beyond_an_unbounded_pool_waits_for_resources_to_be_added() {
+        block_on(async {
+            let pool = AsyncPool::new_with(vec![1]).await;
+            let guard = pool.rsvp().await.unwrap();
+
+            let mut batch = Box::pin(pool.rsvp_many(2));
+            let mut single = Box::pin(pool.rsvp());
+            assert!(futures_util::poll!(batch.as_mut()).is_pending());
+            assert!(futures_util::poll!(single.as_mut()).is_pending());
+
+            // The returned resource is held for the batch, not handed to the waiter behind it
+            drop(guard);
+            assert!(futures_util::poll!(single.as_mut()).is_pending());
+            assert!(futures_util::poll!(batch.as_mut()).is_pending());
+
+            pool.add(2).await.unwrap();
+            drop(batch.await.unwrap());
+            assert!(single.await.is_ok());
+        });
+    }
+
+    #[test]
+    fn cancelled_rsvp_passes_its_resource_on() {
+        block_on(async {
+            let pool = AsyncPool::new_with(vec![1]).await;
+            let guard = pool.rsvp().await.unwrap();
+
+            let mut first = Box::pin(pool.rsvp());
+            let mut second = Box::pin(pool.rsvp());
+            assert!(futures_util::poll!(first.as_mut()).is_pending());
+            assert!(futures_util::poll!(second.as_mut()).is_pending());
+
+            // The resource is set aside for `first`, which is cancelled before it can pick it up
+            drop(guard);
+            match select(future::ready(()), first).await {
+                Either::Left((_, first)) => drop(first),
+                Either::Right(_) => unreachable!("The ready future is polled first"),
+            }
+
+            assert_eq!(*second.await.unwrap(), 1);
+            assert_eq!((pool.in_use(), pool.waiting()), (0, 0));
+        });
+    }
+
+    #[test]
+    fn timed_out_rsvp_loses_nothing() {
+        block_on(async {
+            let pool = AsyncPool::new_with(vec![1]).await;
+            let guard = pool.rsvp().await.unwrap();
+
+            assert_eq!(pool.rsvp_timeout(Duration::from_millis(1)).await.err(), Some(PoolError::Timeout));
+            drop(guard);
+            assert_eq!((pool.available(), pool.waiting()), (1, 0));
+        });
+    }
+
+    #[test]
+    fn poisoned_pool_fails_instead_of_panicking() {
+        let pool = block_on(AsyncPool::new_with(vec![1]));
+        let shared = pool.shared.clone();
+
+        let _ = std::thread::spawn(move || {
+            let _state = shared.state.lock();
+            panic!("Poisoning the pool");
+        }).join();
+
+        assert_eq!(block_on(pool.rsvp()).err(), Some(PoolError::Poisoned));
+        assert_eq!(block_on(pool.add(2)).unwrap_err().kind(), PoolError::Poisoned);
+        assert!(pool.try_rsvp().is_none());
+        assert_eq!(pool.len(), 1);
+    }
+
+    #[test]
+    fn poisoned_pool_can_recover() {
+        let pool = block_on(AsyncPool::new_with(vec![1])).with_poison_policy(PoisonPolicy::Recover);
+        let shared = pool.shared.clone();
+
+        let _ = std::thread::spawn(move || {
+            let _state = shared.state.lock();
+            panic!("Poisoning the pool");
+        }).join();
+
+        assert_eq!(*block_on(pool.rsvp()).unwrap(), 1);
+        block_on(pool.add(2)).unwrap();
+        assert_eq!(pool.len(), 2);
+    }
+
+    #[test]
+    fn close_waits_for_guards_and_rejects_rsvp() {
+        block_on(async {
+            let pool = AsyncPool::new_with(vec![1, 2]).await;
+            let guard = pool.rsvp().await.unwrap();
+
+            let close = pool.close();
+            pin_mut!(close);
+            assert!(futures_util::poll!(close.as_mut()).is_pending());
+            assert_eq!(pool.rsvp().await.err(), Some(PoolError::Closed));
+            assert!(pool.try_rsvp().is_none());
+
+            drop(guard);
+            assert_eq!(close.await, Ok(()));
+            assert_eq!(pool.add(3).await.unwrap_err().kind(), PoolError::Closed);
+        });
+    }
+}
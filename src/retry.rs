@@ -0,0 +1,103 @@
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    time::Duration,
+};
+
+/// How a `ManagedAsyncPool` retries a `Manager` that failed to create a resource
+///
+/// The delay before each retry grows by `multiplier`, starting at `initial_delay` and capped at
+/// `max_delay`. With `jitter` enabled, each delay is shortened by a random amount of up to half, so
+/// callers that failed together don't all retry together.
+///
+/// With the `serde` feature this can be deserialized, with every field optional and delays given in
+/// (fractional) seconds.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize), serde(default, deny_unknown_fields))]
+pub struct RetryPolicy {
+    /// How many times to retry after the first failure
+    pub max_retries: u32,
+    /// The delay before the first retry
+    #[cfg_attr(feature = "serde", serde(with = "crate::config::secs"))]
+    pub initial_delay: Duration,
+    /// The longest delay between two retries
+    #[cfg_attr(feature = "serde", serde(with = "crate::config::secs"))]
+    pub max_delay: Duration,
+    /// The factor the delay grows by after each retry
+    pub multiplier: f64,
+    /// Whether to randomize each delay
+    pub jitter: bool,
+}
+
+impl RetryPolicy {
+    /// A policy that retries up to `max_retries` times, with the default delays
+    pub fn new(max_retries: u32) -> Self {
+        Self {
+            max_retries,
+            ..Self::default()
+        }
+    }
+
+    /// The delay before retry number `retry`, counting from zero
+    pub fn delay(&self, retry: u32) -> Duration {
+        let factor = self.multiplier.max(1.0).powi(retry.min(i32::MAX as u32) as i32);
+        let delay = self.initial_delay.as_secs_f64() * factor;
+        let delay = Duration::from_secs_f64(delay.min(self.max_delay.as_secs_f64()));
+
+        if self.jitter {
+            delay / 2 + delay.mul_f64(random_fraction() / 2.0)
+        } else {
+            delay
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(10),
+            multiplier: 2.0,
+            jitter: true,
+        }
+    }
+}
+
+/// A random number in `[0, 1)`, good enough to spread out retries
+fn random_fraction() -> f64 {
+    // Every `RandomState` is seeded differently, so hashing nothing still gives a fresh value
+    let bits = RandomState::new().build_hasher().finish();
+
+    (bits >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delay_grows_up_to_max() {
+        let policy = RetryPolicy {
+            max_retries: 10,
+            initial_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(500),
+            multiplier: 2.0,
+            jitter: false,
+        };
+
+        assert_eq!(policy.delay(0), Duration::from_millis(100));
+        assert_eq!(policy.delay(2), Duration::from_millis(400));
+        assert_eq!(policy.delay(3), Duration::from_millis(500));
+    }
+
+    #[test]
+    fn jitter_stays_within_half_the_delay() {
+        let policy = RetryPolicy::default();
+
+        for _ in 0..100 {
+            let delay = policy.delay(0);
+            assert!(delay >= Duration::from_millis(50) && delay <= Duration::from_millis(100));
+        }
+    }
+}
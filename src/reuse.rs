@@ -0,0 +1,117 @@
+use std::{
+    collections::{BinaryHeap, HashMap, HashSet, VecDeque},
+    hash::{BuildHasher, Hash},
+};
+
+/// A resource that can be reset to a clean state for its next user, by `AsyncPool::with_reset`
+///
+/// With the `derive` feature, `#[derive(Reusable)]` implements it for a struct by resetting each of
+/// its fields to its `Default`.
+pub trait Reusable {
+    /// Clear whatever the last user left behind
+    fn reset(&mut self);
+}
+
+impl<T> Reusable for Vec<T> {
+    fn reset(&mut self) {
+        self.clear();
+    }
+}
+
+impl Reusable for String {
+    fn reset(&mut self) {
+        self.clear();
+    }
+}
+
+impl<T> Reusable for VecDeque<T> {
+    fn reset(&mut self) {
+        self.clear();
+    }
+}
+
+impl<T> Reusable for BinaryHeap<T> {
+    fn reset(&mut self) {
+        self.clear();
+    }
+}
+
+impl<K, V, S> Reusable for HashMap<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    fn reset(&mut self) {
+        self.clear();
+    }
+}
+
+impl<T: Eq + Hash, S: BuildHasher> Reusable for HashSet<T, S> {
+    fn reset(&mut self) {
+        self.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_executor::block_on;
+
+    use crate::AsyncPool;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct Request {
+        path: String,
+        headers: Vec<(String, String)>,
+        retries: u32,
+    }
+
+    impl Reusable for Request {
+        fn reset(&mut self) {
+            self.path.clear();
+            self.headers.clear();
+            self.retries = 0;
+        }
+    }
+
+    #[test]
+    fn resources_are_reset_as_they_are_returned() {
+        block_on(async {
+            let pool = AsyncPool::new_with(vec![Request::default()]).await.with_reset();
+
+            let mut request = pool.rsvp().await.unwrap();
+            request.path.push_str("/index.html");
+            request.headers.push(("Host".to_owned(), "khionu.net".to_owned()));
+            request.retries = 2;
+            drop(request);
+
+            let request = pool.rsvp().await.unwrap();
+            assert!(request.path.is_empty() && request.headers.is_empty() && request.retries == 0);
+            assert!(request.path.capacity() >= "/index.html".len());
+        });
+    }
+
+    #[cfg(feature = "derive")]
+    #[test]
+    fn derived_resets_set_each_field_to_its_default() {
+        use crate::Reusable;
+
+        #[derive(Reusable)]
+        struct Scratch<T: Default> {
+            items: Vec<T>,
+            seen: usize,
+        }
+
+        #[derive(Reusable)]
+        struct Pair(String, Option<u8>);
+
+        let mut scratch = Scratch { items: vec![1, 2], seen: 2 };
+        scratch.reset();
+        assert!(scratch.items.is_empty() && scratch.seen == 0);
+
+        let mut pair = Pair("left".to_owned(), Some(1));
+        pair.reset();
+        assert_eq!((pair.0.as_str(), pair.1), ("", None));
+    }
+}
@@ -0,0 +1,70 @@
+use std::future::Future;
+
+use crate::{
+    AsyncPool, AsyncPoolGuard, FallbackPool, ManagedAsyncPool, ManagedError, Manager, PoolError, ShardedPool,
+    TieredPool, Tiering,
+};
+
+/// Anything resources can be reserved from, like an `AsyncPool`
+///
+/// Code that only reserves resources can take any `Pool`, so it can be given a `MockPool` in tests.
+pub trait Pool: Send + Sync {
+    /// The resource reserved from the pool
+    type Resource: Send + 'static;
+    /// The error returned when a resource couldn't be reserved
+    type Error;
+
+    /// Await the next available resource
+    fn rsvp(&self) -> impl Future<Output = Result<AsyncPoolGuard<Self::Resource>, Self::Error>> + Send;
+}
+
+impl<T: Send + 'static> Pool for AsyncPool<T> {
+    type Resource = T;
+    type Error = PoolError;
+
+    fn rsvp(&self) -> impl Future<Output = Result<AsyncPoolGuard<T>, PoolError>> + Send {
+        AsyncPool::rsvp(self)
+    }
+}
+
+impl<T: Send + 'static> Pool for ShardedPool<T> {
+    type Resource = T;
+    type Error = PoolError;
+
+    fn rsvp(&self) -> impl Future<Output = Result<AsyncPoolGuard<T>, PoolError>> + Send {
+        ShardedPool::rsvp(self)
+    }
+}
+
+impl<T: Send + 'static> Pool for FallbackPool<T> {
+    type Resource = T;
+    type Error = PoolError;
+
+    fn rsvp(&self) -> impl Future<Output = Result<AsyncPoolGuard<T>, PoolError>> + Send {
+        FallbackPool::rsvp(self)
+    }
+}
+
+impl<M: Manager> Pool for ManagedAsyncPool<M>
+where
+    M::Error: Send,
+{
+    type Resource = M::Resource;
+    type Error = ManagedError<M::Error>;
+
+    fn rsvp(&self) -> impl Future<Output = Result<AsyncPoolGuard<M::Resource>, Self::Error>> + Send {
+        ManagedAsyncPool::rsvp(self)
+    }
+}
+
+impl<M: Tiering> Pool for TieredPool<M>
+where
+    M::Error: Send,
+{
+    type Resource = M::Resource;
+    type Error = ManagedError<M::Error>;
+
+    fn rsvp(&self) -> impl Future<Output = Result<AsyncPoolGuard<M::Resource>, Self::Error>> + Send {
+        TieredPool::rsvp(self)
+    }
+}
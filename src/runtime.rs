@@ -0,0 +1,183 @@
+use std::{future::Future, pin::Pin, sync::Arc, time::Duration};
+
+/// A future that completes once a `Timer`'s sleep is over
+pub type Sleep = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// A background task for a `Spawner` to run
+pub type Task = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// The source of the delays behind timeouts, retries and maintenance
+///
+/// Pools use `DefaultTimer` unless given another with `with_timer`.
+pub trait Timer: Send + Sync + 'static {
+    /// Wait for `dur` to pass
+    fn sleep(&self, dur: Duration) -> Sleep;
+}
+
+impl<T: Timer + ?Sized> Timer for Arc<T> {
+    fn sleep(&self, dur: Duration) -> Sleep {
+        (**self).sleep(dur)
+    }
+}
+
+/// Runs background tasks, like `spawn_maintenance`, on an executor
+pub trait Spawner {
+    /// Run `task` to completion in the background
+    fn spawn(&self, task: Task);
+}
+
+/// A `Timer` backed by a timer thread, which works with any executor
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FuturesTimer;
+
+impl Timer for FuturesTimer {
+    fn sleep(&self, dur: Duration) -> Sleep {
+        Box::pin(futures_timer::Delay::new(dur))
+    }
+}
+
+/// The timer and spawner of the tokio runtime the pool is used from
+///
+/// Pools given this with `with_timer` must only wait from within a tokio runtime with the time
+/// driver enabled.
+#[cfg(feature = "tokio")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TokioRuntime;
+
+#[cfg(feature = "tokio")]
+impl Timer for TokioRuntime {
+    fn sleep(&self, dur: Duration) -> Sleep {
+        Box::pin(tokio::time::sleep(dur))
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl Spawner for TokioRuntime {
+    fn spawn(&self, task: Task) {
+        tokio::spawn(task);
+    }
+}
+
+/// The timer and spawner of async-std
+#[cfg(feature = "async-std")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AsyncStdRuntime;
+
+#[cfg(feature = "async-std")]
+impl Timer for AsyncStdRuntime {
+    fn sleep(&self, dur: Duration) -> Sleep {
+        Box::pin(async_std::task::sleep(dur))
+    }
+}
+
+#[cfg(feature = "async-std")]
+impl Spawner for AsyncStdRuntime {
+    fn spawn(&self, task: Task) {
+        async_std::task::spawn(task);
+    }
+}
+
+/// The timer and global executor of smol
+#[cfg(feature = "smol")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SmolRuntime;
+
+#[cfg(feature = "smol")]
+impl Timer for SmolRuntime {
+    fn sleep(&self, dur: Duration) -> Sleep {
+        Box::pin(async move {
+            smol::Timer::after(dur).await;
+        })
+    }
+}
+
+#[cfg(feature = "smol")]
+impl Spawner for SmolRuntime {
+    fn spawn(&self, task: Task) {
+        smol::spawn(task).detach();
+    }
+}
+
+/// The timer pools use by default, whatever features are enabled
+///
+/// This works with any executor, so enabling a runtime's feature never changes where pools can be
+/// used. Pass that runtime to `with_timer` to use its timer instead.
+pub type DefaultTimer = FuturesTimer;
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        thread,
+    };
+
+    use futures_executor::block_on;
+    use futures_util::future;
+
+    use crate::{AsyncPool, PoolError};
+
+    use super::*;
+
+    /// Never waits, counting how often it was asked to
+    struct Instant(Arc<AtomicUsize>);
+
+    impl Timer for Instant {
+        fn sleep(&self, _dur: Duration) -> Sleep {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Box::pin(future::ready(()))
+        }
+    }
+
+    /// Runs each task on its own thread
+    struct Threads;
+
+    impl Spawner for Threads {
+        fn spawn(&self, task: Task) {
+            thread::spawn(move || block_on(task));
+        }
+    }
+
+    #[test]
+    fn timeouts_use_the_pool_timer() {
+        let sleeps = Arc::new(AtomicUsize::new(0));
+        let pool = block_on(AsyncPool::new_with(vec![1]))
+            .with_timer(Instant(sleeps.clone()))
+            .with_timeout(Duration::from_secs(60));
+
+        let _guard = pool.try_rsvp().unwrap();
+        assert_eq!(block_on(pool.rsvp()).err(), Some(PoolError::Timeout));
+        assert_eq!(sleeps.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn maintenance_runs_on_the_spawner() {
+        let pool = block_on(AsyncPool::new_with(vec![1])).with_idle_timeout(Duration::ZERO);
+
+        pool.spawn_maintenance(&Threads, Duration::from_millis(1));
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        while !pool.is_empty() {
+            assert!(std::time::Instant::now() < deadline, "Maintenance never evicted the idle resource");
+            thread::yield_now();
+        }
+
+        block_on(pool.close()).unwrap();
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn tokio_timers_drive_timeouts_from_within_the_runtime() {
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_time().build().unwrap();
+
+        runtime.block_on(async {
+            let pool = AsyncPool::new_with(vec![1]).await
+                .with_timer(TokioRuntime)
+                .with_timeout(Duration::from_millis(10));
+
+            let _guard = pool.rsvp().await.unwrap();
+            assert_eq!(pool.rsvp().await.err(), Some(PoolError::Timeout));
+        });
+    }
+}
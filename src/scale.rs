@@ -0,0 +1,130 @@
+use std::time::Duration;
+
+use crate::WaitHistogram;
+
+/// When `AsyncPool::autoscale` grows and shrinks a pool's maximum size
+///
+/// Each check grows the maximum size by `step` if at least `grow_above` of it was reserved, or if
+/// the 90th percentile of the waits since the last check was over `max_wait`. Once `shrink_after`
+/// checks in a row find less than `shrink_below` of it reserved, it shrinks by `step`. The maximum
+/// size always stays between `min_size` and `max_size`.
+///
+/// With the `serde` feature this can be deserialized, with every field optional and the wait given
+/// in (fractional) seconds.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize), serde(default, deny_unknown_fields))]
+pub struct AutoScale {
+    /// The smallest the maximum size shrinks to
+    pub min_size: usize,
+    /// The largest the maximum size grows to
+    pub max_size: usize,
+    /// How many resources to grow or shrink by at each check
+    pub step: usize,
+    /// The fraction of the maximum size that, once reserved, makes the pool grow
+    pub grow_above: f64,
+    /// The wait that, if most reservations took longer, makes the pool grow
+    #[cfg_attr(feature = "serde", serde(with = "crate::config::secs"))]
+    pub max_wait: Duration,
+    /// The fraction of the maximum size that, while less is reserved, makes the pool shrink
+    pub shrink_below: f64,
+    /// How many checks in a row must find the pool under `shrink_below` before it shrinks
+    pub shrink_after: u32,
+}
+
+/// What `AsyncPool::autoscale` has seen of the pool so far
+pub(crate) struct Scaler {
+    policy: AutoScale,
+    /// The wait times as of the last check, so each check only sees the waits since.
+    waits: WaitHistogram,
+    /// How many checks in a row found the pool under `shrink_below`.
+    calm: u32,
+}
+
+impl AutoScale {
+    /// Scale between `min_size` and `max_size`, with the default thresholds
+    pub fn new(min_size: usize, max_size: usize) -> Self {
+        Self { min_size, max_size, ..Self::default() }
+    }
+
+    /// Keep `size` within the bounds
+    pub(crate) fn clamp(&self, size: usize) -> usize {
+        size.min(self.max_size).max(self.min_size)
+    }
+}
+
+impl Scaler {
+    pub(crate) fn new(policy: AutoScale, waits: WaitHistogram) -> Self {
+        Self { policy, waits, calm: 0 }
+    }
+
+    /// The maximum size to change to, given the current one, how much of it is reserved and the wait
+    /// times so far, if it should change
+    pub(crate) fn check(&mut self, size: usize, in_use: usize, waits: WaitHistogram) -> Option<usize> {
+        let recent = waits.since(&self.waits);
+        self.waits = waits;
+
+        let used = in_use as f64 / size.max(1) as f64;
+        let slow = recent.percentile(0.9).is_some_and(|wait| wait > self.policy.max_wait);
+
+        let next = if used >= self.policy.grow_above || slow {
+            self.calm = 0;
+            size.saturating_add(self.policy.step)
+        } else if used < self.policy.shrink_below {
+            self.calm += 1;
+            if self.calm < self.policy.shrink_after {
+                return None;
+            }
+
+            self.calm = 0;
+            size.saturating_sub(self.policy.step)
+        } else {
+            self.calm = 0;
+            return None;
+        };
+
+        Some(self.policy.clamp(next)).filter(|&next| next != size)
+    }
+}
+
+impl Default for AutoScale {
+    fn default() -> Self {
+        Self {
+            min_size: 1,
+            max_size: 16,
+            step: 1,
+            grow_above: 0.9,
+            max_wait: Duration::from_millis(50),
+            shrink_below: 0.5,
+            shrink_after: 10,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stats::WaitTimes;
+
+    use super::*;
+
+    #[test]
+    fn pools_grow_under_load_and_shrink_once_calm() {
+        let waits = WaitTimes::new();
+        let policy = AutoScale { shrink_after: 2, ..AutoScale::new(2, 4) };
+        let mut scaler = Scaler::new(policy, waits.snapshot());
+
+        assert_eq!(scaler.check(2, 2, waits.snapshot()), Some(3));
+        assert_eq!(scaler.check(3, 2, waits.snapshot()), None);
+
+        // Waits that have already been seen don't count again
+        waits.record(Duration::from_millis(200));
+        assert_eq!(scaler.check(3, 2, waits.snapshot()), Some(4));
+        assert_eq!(scaler.check(4, 4, waits.snapshot()), None);
+
+        assert_eq!(scaler.check(4, 1, waits.snapshot()), None);
+        assert_eq!(scaler.check(4, 1, waits.snapshot()), Some(3));
+        assert_eq!(scaler.check(3, 0, waits.snapshot()), None);
+        assert_eq!(scaler.check(3, 0, waits.snapshot()), Some(2));
+        assert_eq!(scaler.check(2, 0, waits.snapshot()), None);
+        assert_eq!(scaler.check(2, 0, waits.snapshot()), None);
+    }
+}
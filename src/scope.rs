@@ -0,0 +1,84 @@
+use std::{
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+};
+
+use crate::{AsyncPool, AsyncPoolGuard, PoolError};
+
+/// A handle to reserve resources that can't outlive the `AsyncPool::scope` call it's given to
+pub struct Scope<'s, T: Send + 'static> {
+    pool: &'s AsyncPool<T>,
+    /// Keeps `'s` invariant, so it can't be stretched to let a guard out of the scope.
+    scope: PhantomData<&'s mut &'s ()>,
+}
+
+/// The guard on a resource reserved through a `Scope`, returned to the pool on drop like any other
+pub struct ScopedGuard<'s, T: Send + 'static> {
+    guard: AsyncPoolGuard<T>,
+    scope: PhantomData<&'s ()>,
+}
+
+impl<'s, T: Send + 'static> Scope<'s, T> {
+    pub(crate) fn new(pool: &'s AsyncPool<T>) -> Self {
+        Self { pool, scope: PhantomData }
+    }
+
+    /// Await the next available resource, like `AsyncPool::rsvp`
+    pub async fn rsvp(&'s self) -> Result<ScopedGuard<'s, T>, PoolError> {
+        Ok(ScopedGuard::new(self.pool.rsvp().await?))
+    }
+
+    /// Reserve a resource only if one is idle right now, like `AsyncPool::try_rsvp`
+    pub fn try_rsvp(&'s self) -> Option<ScopedGuard<'s, T>> {
+        self.pool.try_rsvp().map(ScopedGuard::new)
+    }
+
+    /// The pool this reserves from
+    pub fn pool(&self) -> &'s AsyncPool<T> {
+        self.pool
+    }
+}
+
+impl<T: Send + 'static> ScopedGuard<'_, T> {
+    fn new(guard: AsyncPoolGuard<T>) -> Self {
+        Self { guard, scope: PhantomData }
+    }
+}
+
+impl<T: Send + 'static> Deref for ScopedGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.guard
+    }
+}
+
+impl<T: Send + 'static> DerefMut for ScopedGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.guard
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_executor::block_on;
+
+    use super::*;
+
+    #[test]
+    fn every_scoped_guard_is_returned_when_the_scope_ends() {
+        block_on(async {
+            let pool = AsyncPool::new_with(vec![1, 2]).await;
+
+            let sum = pool.scope(async |scoped: &Scope<'_, i32>| {
+                let (mut one, two) = (scoped.rsvp().await.unwrap(), scoped.try_rsvp().unwrap());
+                *one += 10;
+                assert!(scoped.try_rsvp().is_none());
+                *one + *two
+            }).await;
+
+            assert_eq!((sum, pool.available()), (13, 2));
+            assert_eq!(pool.into_inner().unwrap().iter().sum::<i32>(), 13);
+        });
+    }
+}
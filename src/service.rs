@@ -0,0 +1,173 @@
+use std::{
+    error::Error,
+    fmt, mem,
+    task::{Context, Poll},
+};
+
+use futures_util::future::BoxFuture;
+use tower::{Layer, Service};
+
+use crate::{AsyncPool, AsyncPoolGuard, PoolError};
+
+/// A `tower` layer that reserves a resource from a pool for each request
+///
+/// The wrapped service is called with a `Pooled` request, holding the resource for as long as the
+/// service's future runs, so it drops into middleware stacks like any other layer.
+pub struct PoolLayer<T: Send + 'static> {
+    pool: AsyncPool<T>,
+}
+
+/// The service made by a `PoolLayer`
+pub struct PooledService<S, T: Send + 'static> {
+    inner: S,
+    pool: AsyncPool<T>,
+}
+
+/// A request, along with the resource reserved for it
+pub struct Pooled<R, T: Send + 'static> {
+    /// The request the layer was called with.
+    pub request: R,
+    /// The resource reserved for it, returned once the wrapped service is done with it.
+    pub resource: AsyncPoolGuard<T>,
+}
+
+/// The error returned by a `PooledService`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PooledError<E> {
+    /// The pool could not reserve a resource for the request
+    Pool(PoolError),
+    /// The wrapped service failed
+    Service(E),
+}
+
+impl<T: Send + 'static> PoolLayer<T> {
+    /// Reserve a resource from `pool` for each request
+    pub fn new(pool: AsyncPool<T>) -> Self {
+        Self { pool }
+    }
+}
+
+impl<S, T: Send + 'static> Layer<S> for PoolLayer<T> {
+    type Service = PooledService<S, T>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        PooledService {
+            inner,
+            pool: self.pool.clone(),
+        }
+    }
+}
+
+impl<T: Send + 'static> Clone for PoolLayer<T> {
+    fn clone(&self) -> Self {
+        Self {
+            pool: self.pool.clone(),
+        }
+    }
+}
+
+impl<S, R, T> Service<R> for PooledService<S, T>
+where
+    S: Service<Pooled<R, T>> + Clone + Send + 'static,
+    S::Future: Send,
+    R: Send + 'static,
+    T: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = PooledError<S::Error>;
+    type Future = BoxFuture<'static, Result<S::Response, PooledError<S::Error>>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(PooledError::Service)
+    }
+
+    fn call(&mut self, request: R) -> Self::Future {
+        // Only the service polled ready may be called, so take it and leave a clone for next time
+        let clone = self.inner.clone();
+        let mut inner = mem::replace(&mut self.inner, clone);
+        let pool = self.pool.clone();
+
+        Box::pin(async move {
+            let resource = pool.rsvp().await.map_err(PooledError::Pool)?;
+
+            inner.call(Pooled { request, resource }).await.map_err(PooledError::Service)
+        })
+    }
+}
+
+impl<S: Clone, T: Send + 'static> Clone for PooledService<S, T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            pool: self.pool.clone(),
+        }
+    }
+}
+
+impl<E> From<PoolError> for PooledError<E> {
+    fn from(e: PoolError) -> Self {
+        PooledError::Pool(e)
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for PooledError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PooledError::Pool(e) => e.fmt(f),
+            PooledError::Service(e) => e.fmt(f),
+        }
+    }
+}
+
+impl<E: Error + 'static> Error for PooledError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            PooledError::Pool(e) => Some(e),
+            PooledError::Service(e) => Some(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::{ready, Ready};
+
+    use futures_executor::block_on;
+    use futures_util::future::poll_fn;
+
+    use super::*;
+
+    /// Answers each request with the resource it was handed, plus the request
+    #[derive(Clone)]
+    struct Add;
+
+    impl Service<Pooled<u32, u32>> for Add {
+        type Response = u32;
+        type Error = ();
+        type Future = Ready<Result<u32, ()>>;
+
+        fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), ()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, req: Pooled<u32, u32>) -> Self::Future {
+            ready(Ok(*req.resource + req.request))
+        }
+    }
+
+    #[test]
+    fn pooled_services_get_a_resource_per_request() {
+        block_on(async {
+            let pool = AsyncPool::new_with(vec![40]).await;
+            let mut service = PoolLayer::new(pool.clone()).layer(Add);
+
+            poll_fn(|cx| service.poll_ready(cx)).await.unwrap();
+            assert_eq!(service.call(2).await, Ok(42));
+            assert_eq!(pool.available(), 1);
+
+            pool.close().await.unwrap();
+            poll_fn(|cx| service.poll_ready(cx)).await.unwrap();
+            assert_eq!(service.call(2).await, Err(PooledError::Pool(PoolError::Closed)));
+        });
+    }
+}
@@ -0,0 +1,169 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    thread,
+};
+
+use futures_util::future::select_all;
+
+use crate::{AddError, AsyncPool, AsyncPoolGuard, PoolError};
+
+/// A pool split into shards, so acquirers on different threads mostly lock different state
+///
+/// Each thread has a home shard it reserves from, and steals from the others, in turn, when its home
+/// shard has nothing idle. If no shard has anything idle, it waits on every shard, and takes the first
+/// resource returned to any of them.
+///
+/// Cloning a `ShardedPool` is cheap, and gives another handle to the same shards.
+pub struct ShardedPool<T: Send + 'static> {
+    shards: Vec<AsyncPool<T>>,
+}
+
+impl<T: Send + 'static> ShardedPool<T> {
+    /// Create an empty pool of `shards` shards
+    ///
+    /// # Panics
+    /// If `shards` is zero.
+    pub fn new(shards: usize) -> Self {
+        assert!(shards > 0, "A sharded pool needs at least one shard");
+
+        Self {
+            shards: (0..shards).map(|_| AsyncPool::new()).collect(),
+        }
+    }
+
+    /// Create a pool with a shard per available CPU
+    pub fn per_cpu() -> Self {
+        Self::new(thread::available_parallelism().map_or(1, usize::from))
+    }
+
+    /// Add `item` to whichever shard owns the fewest resources
+    ///
+    /// Fails like `AsyncPool::add`.
+    pub async fn add(&self, item: T) -> Result<(), AddError<T>> {
+        let shard = self.shards.iter()
+            .min_by_key(|shard| shard.len())
+            .expect("A sharded pool has at least one shard");
+
+        shard.add(item).await
+    }
+
+    /// Await a resource from this thread's shard, stealing from the others if it has none idle
+    ///
+    /// If no shard has anything idle, it waits on all of them, and the waits that lose are cancelled.
+    /// Fails once every shard has failed, as when they're all closed, or each times out.
+    pub async fn rsvp(&self) -> Result<AsyncPoolGuard<T>, PoolError> {
+        if let Some(guard) = self.try_rsvp() {
+            return Ok(guard);
+        }
+
+        let mut waits: Vec<_> = self.in_order().map(|shard| Box::pin(shard.rsvp())).collect();
+        loop {
+            match select_all(waits).await {
+                (Ok(guard), _, _) => return Ok(guard),
+                (Err(e), _, rest) if rest.is_empty() => return Err(e),
+                (Err(_), _, rest) => waits = rest,
+            }
+        }
+    }
+
+    /// Reserve an idle resource from any shard, this thread's first, without awaiting
+    pub fn try_rsvp(&self) -> Option<AsyncPoolGuard<T>> {
+        self.in_order().find_map(AsyncPool::try_rsvp)
+    }
+
+    /// Close every shard, like `AsyncPool::close`
+    pub async fn close(&self) -> Result<(), PoolError> {
+        for shard in &self.shards {
+            shard.close().await?;
+        }
+
+        Ok(())
+    }
+
+    /// The shards, to configure or inspect one by one
+    pub fn shards(&self) -> &[AsyncPool<T>] {
+        &self.shards
+    }
+
+    /// The number of resources owned by every shard, reserved or not
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(AsyncPool::len).sum()
+    }
+
+    /// Whether no shard owns any resources
+    pub fn is_empty(&self) -> bool {
+        self.shards.iter().all(AsyncPool::is_empty)
+    }
+
+    /// The number of unreserved resources across every shard
+    pub fn available(&self) -> usize {
+        self.shards.iter().map(AsyncPool::available).sum()
+    }
+
+    /// Every shard, this thread's first
+    fn in_order(&self) -> impl Iterator<Item = &AsyncPool<T>> {
+        let (before, after) = self.shards.split_at(self.home_index());
+
+        after.iter().chain(before)
+    }
+
+    /// The shard this thread reserves from first
+    fn home_index(&self) -> usize {
+        let mut hasher = DefaultHasher::new();
+        thread::current().id().hash(&mut hasher);
+
+        (hasher.finish() % self.shards.len() as u64) as usize
+    }
+}
+
+impl<T: Send + 'static> Clone for ShardedPool<T> {
+    fn clone(&self) -> Self {
+        Self {
+            shards: self.shards.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_executor::block_on;
+    use futures_util::poll;
+
+    use super::*;
+
+    #[test]
+    fn sharded_pools_steal_from_other_shards() {
+        block_on(async {
+            let pool = ShardedPool::new(3);
+            for i in 0..3 {
+                pool.add(i).await.unwrap();
+            }
+            assert!(pool.shards().iter().all(|shard| shard.len() == 1));
+
+            let guards: Vec<_> = (0..3).map(|_| pool.try_rsvp().unwrap()).collect();
+            assert!(pool.try_rsvp().is_none());
+
+            drop(guards);
+            assert_eq!(pool.available(), 3);
+            assert!(pool.rsvp().await.is_ok());
+        });
+    }
+
+    #[test]
+    fn sharded_pools_wait_on_every_shard() {
+        block_on(async {
+            let pool = ShardedPool::new(2);
+            let other = 1 - pool.home_index();
+            pool.shards()[other].add(1).await.unwrap();
+            let guard = pool.try_rsvp().unwrap();
+
+            let mut rsvp = Box::pin(pool.rsvp());
+            assert!(poll!(rsvp.as_mut()).is_pending());
+
+            drop(guard);
+            assert_eq!(*rsvp.await.unwrap(), 1);
+            assert!(pool.shards().iter().all(|shard| shard.waiting() == 0));
+        });
+    }
+}
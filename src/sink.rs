@@ -0,0 +1,85 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures_util::{future::BoxFuture, sink::Sink};
+
+use crate::{AddError, AsyncPool};
+
+/// A sink that adds every resource sent into it to the pool
+///
+/// Created by `AsyncPool::sink`, to pipe a stream of new resources into the pool with `forward`.
+/// Each resource is added like `add`, so sending waits for room once the pool is full, and fails,
+/// handing the resource back, if the pool is closed or its timeout passes first.
+pub struct PoolSink<T: Send + 'static> {
+    pool: AsyncPool<T>,
+    /// The addition of the last resource sent, until it's done.
+    adding: Option<BoxFuture<'static, Result<(), AddError<T>>>>,
+}
+
+impl<T: Send + 'static> PoolSink<T> {
+    pub(crate) fn new(pool: AsyncPool<T>) -> Self {
+        Self { pool, adding: None }
+    }
+}
+
+impl<T: Send + 'static> Sink<T> for PoolSink<T> {
+    type Error = AddError<T>;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.poll_flush(cx)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: T) -> Result<(), Self::Error> {
+        let pool = self.pool.clone();
+        self.adding = Some(Box::pin(async move { pool.add(item).await }));
+
+        Ok(())
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let adding = match self.adding.as_mut() {
+            Some(adding) => adding,
+            None => return Poll::Ready(Ok(())),
+        };
+
+        let added = match adding.as_mut().poll(cx) {
+            Poll::Ready(added) => added,
+            Poll::Pending => return Poll::Pending,
+        };
+
+        self.adding = None;
+        Poll::Ready(added)
+    }
+
+    /// Finish adding the last resource sent, leaving the pool open
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.poll_flush(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures_executor::block_on;
+    use futures_util::stream::{self, StreamExt};
+
+    use crate::PoolError;
+
+    use super::*;
+
+    #[test]
+    fn streams_of_resources_forward_into_the_pool() {
+        block_on(async {
+            let pool = AsyncPool::new().with_max_size(2).with_timeout(Duration::from_millis(10));
+            let resources = stream::iter(vec![Ok(1), Ok(2), Ok(3)]);
+
+            // The last resource waits for room that never comes
+            let err = resources.forward(pool.sink()).await.unwrap_err();
+            assert_eq!((err.kind(), err.into_inner()), (PoolError::Timeout, 3));
+            assert_eq!(pool.drain(), [1, 2]);
+        });
+    }
+}
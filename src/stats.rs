@@ -0,0 +1,145 @@
+use std::{
+    sync::atomic::Ordering,
+    time::Duration,
+};
+
+use crate::{sync::AtomicU64, time::Instant};
+
+/// How much a resource has been used, from `AsyncPool::resource_stats`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResourceStats {
+    /// The resource's ID, unique among every resource in the process and kept for its whole life
+    ///
+    /// IDs are handed out in increasing order as resources enter a pool, and are the `id` of the
+    /// resource's guard, hook info, leak reports and `tracing` events.
+    pub id: u64,
+    /// How many times the resource was reserved
+    pub checkouts: u64,
+    /// How long the resource was reserved for in total, not counting a reservation still held
+    pub held: Duration,
+    /// When the resource was last reserved, if ever
+    pub last_used: Option<Instant>,
+    /// Whether the resource is reserved right now
+    pub reserved: bool,
+    /// The label it's reserved under, if it's reserved by `rsvp_labeled`
+    pub label: Option<&'static str>,
+}
+
+/// A call waiting in the pool's queue, from `AsyncPool::waiters`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WaiterInfo {
+    /// The label the call was made with, if any
+    pub label: Option<&'static str>,
+    /// The call's priority, as raised by `AsyncPool::with_priority_aging` while it waited
+    pub priority: u8,
+    /// How many resources the call is waiting for
+    pub wanted: usize,
+    /// How long the call has been waiting
+    pub waited: Duration,
+}
+
+/// The number of buckets in a `WaitHistogram`
+const BUCKETS: usize = 32;
+
+/// How long reservations waited, from `AsyncPool::wait_times`
+///
+/// Waits are counted in buckets that double in width, the first holding waits under 2µs and the
+/// last everything from about 36 minutes up.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WaitHistogram {
+    counts: [u64; BUCKETS],
+}
+
+/// The pool's record of how long reservations waited
+pub(crate) struct WaitTimes {
+    counts: [AtomicU64; BUCKETS],
+}
+
+impl WaitHistogram {
+    /// The number of reservations recorded
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// The wait that `quantile` of reservations took no longer than, like `0.99` for the 99th
+    /// percentile, or `None` if nothing was recorded
+    ///
+    /// This is the upper bound of the bucket the quantile falls in, so it can be up to twice the
+    /// actual wait.
+    pub fn percentile(&self, quantile: f64) -> Option<Duration> {
+        let count = self.count();
+        if count == 0 {
+            return None;
+        }
+
+        let rank = ((quantile.clamp(0.0, 1.0) * count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        let bucket = self.counts.iter().position(|&n| {
+            seen += n;
+            seen >= rank
+        })?;
+
+        Some(upper_bound(bucket))
+    }
+
+    /// Each bucket's upper bound, with how many reservations waited less than it and at least as long
+    /// as the last bound
+    pub fn buckets(&self) -> impl Iterator<Item = (Duration, u64)> + '_ {
+        self.counts.iter().enumerate().map(|(bucket, &n)| (upper_bound(bucket), n))
+    }
+
+    /// The reservations recorded since `earlier` was taken
+    pub(crate) fn since(&self, earlier: &WaitHistogram) -> WaitHistogram {
+        WaitHistogram {
+            counts: std::array::from_fn(|bucket| self.counts[bucket].saturating_sub(earlier.counts[bucket])),
+        }
+    }
+}
+
+impl WaitTimes {
+    pub(crate) fn new() -> Self {
+        Self {
+            counts: std::array::from_fn(|_| AtomicU64::new(0)),
+        }
+    }
+
+    /// Count a reservation that waited for `waited`
+    pub(crate) fn record(&self, waited: Duration) {
+        let micros = waited.as_micros();
+        let bucket = ((u128::BITS - micros.leading_zeros()) as usize).saturating_sub(1).min(BUCKETS - 1);
+
+        self.counts[bucket].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self) -> WaitHistogram {
+        WaitHistogram {
+            counts: std::array::from_fn(|bucket| self.counts[bucket].load(Ordering::Relaxed)),
+        }
+    }
+}
+
+/// The longest wait counted in `bucket`
+fn upper_bound(bucket: usize) -> Duration {
+    Duration::from_micros(2 << bucket)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percentiles_come_from_bucket_bounds() {
+        let waits = WaitTimes::new();
+        assert_eq!(waits.snapshot().percentile(0.5), None);
+
+        for micros in &[1, 3, 3, 100, 5_000] {
+            waits.record(Duration::from_micros(*micros));
+        }
+
+        let histogram = waits.snapshot();
+        assert_eq!(histogram.count(), 5);
+        assert_eq!(histogram.percentile(0.5), Some(Duration::from_micros(4)));
+        assert_eq!(histogram.percentile(0.8), Some(Duration::from_micros(128)));
+        assert_eq!(histogram.percentile(1.0), Some(Duration::from_micros(8192)));
+    }
+}
@@ -0,0 +1,14 @@
+/// The order in which idle resources are handed out
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize), serde(rename_all = "lowercase"))]
+pub enum Strategy {
+    /// The resource returned longest ago goes first, so every resource is kept in rotation
+    #[default]
+    Fifo,
+    /// The resource returned most recently goes first, keeping a small set of resources warm while
+    /// the rest are left to reach the idle timeout
+    Lifo,
+    /// The resource reserved longest ago goes first, so none sit unused for long, even while some are
+    /// held much longer than others. Resources that were never reserved go before any that were.
+    Lru,
+}
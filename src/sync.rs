@@ -0,0 +1,13 @@
+//! The synchronization primitives the pool's state is built on, swapped for loom's when model
+//! checking with `--cfg loom`
+
+#[cfg(loom)]
+pub(crate) use loom::sync::{
+    atomic::{AtomicBool, AtomicU64, AtomicUsize},
+    Mutex, MutexGuard,
+};
+#[cfg(not(loom))]
+pub(crate) use std::sync::{
+    atomic::{AtomicBool, AtomicU64, AtomicUsize},
+    Mutex, MutexGuard,
+};
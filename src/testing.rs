@@ -0,0 +1,172 @@
+//! Test doubles for code that uses a pool
+
+use std::{
+    collections::VecDeque,
+    future::Future,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    time::Duration,
+};
+
+use crate::{AsyncPool, AsyncPoolGuard, Pool, PoolError, Timer};
+
+/// A pool with scripted behaviour, for testing how code handles delays, timeouts and exhaustion
+///
+/// It hands out real guards on a fixed set of resources, but each `rsvp` can be made to wait first,
+/// or to fail without waiting at all. Cloning a `MockPool` gives another handle to the same script.
+pub struct MockPool<T: Send + 'static> {
+    pool: AsyncPool<T>,
+    script: Arc<Mutex<Script>>,
+}
+
+struct Script {
+    /// How long every `rsvp` waits before it's answered.
+    delay: Option<Duration>,
+    /// Whether every `rsvp` fails as if no resource became available.
+    exhausted: bool,
+    /// The errors the next `rsvp` calls fail with, in order.
+    failures: VecDeque<PoolError>,
+    /// How many times `rsvp` or `try_rsvp` was called.
+    calls: usize,
+}
+
+impl<T: Send + 'static> MockPool<T> {
+    /// Create a mock pool of the given resources
+    pub fn new(resources: impl IntoIterator<Item = T>) -> Self {
+        Self {
+            pool: resources.into_iter().collect(),
+            script: Arc::new(Mutex::new(Script {
+                delay: None,
+                exhausted: false,
+                failures: VecDeque::new(),
+                calls: 0,
+            })),
+        }
+    }
+
+    /// Use `timer` for delays, instead of the `DefaultTimer`
+    pub fn with_timer(self, timer: impl Timer) -> Self {
+        Self {
+            pool: self.pool.with_timer(timer),
+            script: self.script,
+        }
+    }
+
+    /// Make every `rsvp` wait for `delay` before it's answered
+    pub fn set_delay(&self, delay: Option<Duration>) {
+        self.script().delay = delay;
+    }
+
+    /// Make every `rsvp` fail with `PoolError::Timeout`, and `try_rsvp` return `None`, until unset
+    ///
+    /// The timeout is immediate, after any delay, so tests don't have to wait for it.
+    pub fn set_exhausted(&self, exhausted: bool) {
+        self.script().exhausted = exhausted;
+    }
+
+    /// Make the next `rsvp` fail with `error`, after any failures already queued
+    pub fn fail_next(&self, error: PoolError) {
+        self.script().failures.push_back(error);
+    }
+
+    /// The number of times `rsvp` or `try_rsvp` was called
+    pub fn calls(&self) -> usize {
+        self.script().calls
+    }
+
+    /// The pool the resources are reserved from, to check what was returned
+    pub fn pool(&self) -> &AsyncPool<T> {
+        &self.pool
+    }
+
+    /// Await a resource, as scripted
+    pub async fn rsvp(&self) -> Result<AsyncPoolGuard<T>, PoolError> {
+        let (delay, failure) = {
+            let mut script = self.script();
+            script.calls += 1;
+            (script.delay, script.failure())
+        };
+
+        if let Some(delay) = delay {
+            self.pool.sleep(delay).await;
+        }
+
+        match failure {
+            Some(error) => Err(error),
+            None => self.pool.rsvp().await,
+        }
+    }
+
+    /// Reserve a resource if one is idle and no failure is scripted, without awaiting
+    pub fn try_rsvp(&self) -> Option<AsyncPoolGuard<T>> {
+        let mut script = self.script();
+        script.calls += 1;
+
+        match script.failure() {
+            Some(_) => None,
+            None => self.pool.try_rsvp(),
+        }
+    }
+
+    fn script(&self) -> MutexGuard<'_, Script> {
+        self.script.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Script {
+    /// The error the current call should fail with, if any
+    fn failure(&mut self) -> Option<PoolError> {
+        self.failures.pop_front().or(if self.exhausted { Some(PoolError::Timeout) } else { None })
+    }
+}
+
+impl<T: Send + 'static> Pool for MockPool<T> {
+    type Resource = T;
+    type Error = PoolError;
+
+    fn rsvp(&self) -> impl Future<Output = Result<AsyncPoolGuard<T>, PoolError>> + Send {
+        MockPool::rsvp(self)
+    }
+}
+
+impl<T: Send + 'static> Clone for MockPool<T> {
+    fn clone(&self) -> Self {
+        Self {
+            pool: self.pool.clone(),
+            script: self.script.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_executor::block_on;
+
+    use crate::FuturesTimer;
+
+    use super::*;
+
+    /// Code under test, falling back to a default when no resource is available
+    async fn read(pool: &impl Pool<Resource = u8, Error = PoolError>) -> u8 {
+        pool.rsvp().await.map(|guard| *guard).unwrap_or(0)
+    }
+
+    #[test]
+    fn mock_pools_follow_their_script() {
+        block_on(async {
+            let pool = MockPool::new(vec![7]).with_timer(FuturesTimer);
+            pool.set_delay(Some(Duration::from_millis(1)));
+            assert_eq!(read(&pool).await, 7);
+
+            pool.fail_next(PoolError::Closed);
+            assert_eq!(pool.rsvp().await.err(), Some(PoolError::Closed));
+
+            pool.set_exhausted(true);
+            assert_eq!(read(&pool).await, 0);
+            assert!(pool.try_rsvp().is_none());
+
+            pool.set_exhausted(false);
+            assert!(pool.try_rsvp().is_some());
+            assert_eq!(pool.calls(), 5);
+        });
+    }
+}
@@ -0,0 +1,387 @@
+use std::{
+    future::Future,
+    mem,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    time::Duration,
+};
+
+use crate::{
+    pool::Entry,
+    trace::event,
+    AsyncPool,
+    AsyncPoolGuard,
+    ManagedAsyncPool,
+    ManagedError,
+    Manager,
+    PoolError,
+};
+
+/// A `Manager` for resources that can be put to sleep while they're unused, for a `TieredPool`
+pub trait Tiering: Manager {
+    /// Put an idle resource to sleep, as it's moved to the cold tier
+    ///
+    /// By default, this does nothing.
+    fn suspend(&self, _resource: &mut Self::Resource) -> impl Future<Output = ()> + Send {
+        async {}
+    }
+
+    /// Wake a resource as it's moved back to the hot tier, returning whether it's still usable
+    ///
+    /// Resources that fail to resume are dropped, and the next one is tried. By default, every
+    /// resource resumes.
+    fn resume(&self, _resource: &mut Self::Resource) -> impl Future<Output = bool> + Send {
+        async { true }
+    }
+}
+
+/// A pool of expensive resources, with a few kept ready and the rest suspended until they're needed
+///
+/// The hot tier is a `ManagedAsyncPool` holding the resources ready to hand out. Resources that have
+/// been idle there for a while are demoted by `cool`, suspended with `Tiering::suspend`, and kept in
+/// the cold tier, where they still count against the maximum size. When the hot tier has nothing
+/// idle, a cold resource is resumed with `Tiering::resume` before a new one is created, and only once
+/// the pool is at capacity does `rsvp` wait for a returned one. Resources keep their IDs and stats
+/// through the cold tier, and `Hooks::on_create` only runs as they're first created.
+///
+/// Like `ManagedAsyncPool`, cloning is cheap and the clones share their resources and manager.
+pub struct TieredPool<M: Tiering> {
+    hot: ManagedAsyncPool<M>,
+    cold: Arc<Mutex<Cold<M::Resource>>>,
+    /// How many idle resources `cool` leaves in the hot tier.
+    hot_size: usize,
+    /// How long a resource sits idle in the hot tier before `cool` demotes it.
+    cool_after: Duration,
+}
+
+/// The suspended resources
+struct Cold<T> {
+    items: Vec<Entry<T>>,
+    closed: bool,
+}
+
+/// A resource being suspended or resumed, which keeps its slot in the hot tier meanwhile
+///
+/// If it's dropped midway, as when a `cool` or `rsvp` future is, the resource is destroyed and its
+/// slot given back.
+struct Moving<'a, T: Send + 'static> {
+    pool: &'a AsyncPool<T>,
+    entry: Option<Entry<T>>,
+}
+
+/// The resources `cool` took out of the hot tier but hasn't suspended yet, which go back in if it's
+/// dropped
+struct Cooling<'a, T: Send + 'static> {
+    pool: &'a AsyncPool<T>,
+    entries: Vec<Entry<T>>,
+}
+
+impl<M: Tiering> TieredPool<M> {
+    /// Create an empty pool of up to `max_size` resources, created by `manager`
+    ///
+    /// By default one idle resource stays hot, and the others are demoted once they've been idle for a
+    /// minute.
+    pub fn new(manager: M, max_size: usize) -> Self {
+        Self {
+            hot: ManagedAsyncPool::new(manager).with_max_size(max_size),
+            cold: Arc::new(Mutex::new(Cold { items: Vec::new(), closed: false })),
+            hot_size: 1,
+            cool_after: Duration::from_secs(60),
+        }
+    }
+
+    /// Keep up to `hot_size` idle resources in the hot tier
+    pub fn with_hot_size(mut self, hot_size: usize) -> Self {
+        self.hot_size = hot_size;
+        self
+    }
+
+    /// Demote idle resources once they've been idle for `cool_after`
+    pub fn with_cool_after(mut self, cool_after: Duration) -> Self {
+        self.cool_after = cool_after;
+        self
+    }
+
+    /// The hot tier, to configure or inspect
+    ///
+    /// Its size and available resources count the cold ones too.
+    pub fn hot(&self) -> &ManagedAsyncPool<M> {
+        &self.hot
+    }
+
+    /// The number of resources in the cold tier
+    pub fn cold(&self) -> usize {
+        self.lock().items.len()
+    }
+
+    /// Reserve a hot resource, resuming a cold one or creating a new one if none are idle
+    ///
+    /// Fails like `ManagedAsyncPool::rsvp` once it comes to creating or waiting for a resource.
+    pub async fn rsvp(&self) -> Result<AsyncPoolGuard<M::Resource>, ManagedError<M::Error>> {
+        loop {
+            if let Some(guard) = self.hot.pool().try_rsvp() {
+                return Ok(guard);
+            }
+
+            let popped = self.lock().items.pop();
+            let mut moving = match popped {
+                Some(entry) => Moving { pool: self.hot.pool(), entry: Some(entry) },
+                None => return self.hot.rsvp().await,
+            };
+
+            if self.hot.manager().resume(moving.item()).await {
+                let entry = moving.land();
+                event!(id = entry.id, "Resumed a cold resource");
+                return Ok(self.hot.pool().guard_entry(entry));
+            }
+
+            event!("Dropping a cold resource that failed to resume");
+        }
+    }
+
+    /// Reserve an idle hot resource, without awaiting
+    pub fn try_rsvp(&self) -> Option<AsyncPoolGuard<M::Resource>> {
+        self.hot.pool().try_rsvp()
+    }
+
+    /// Demote the idle hot resources past the hot size that have been idle for long enough, returning
+    /// how many were
+    pub async fn cool(&self) -> usize {
+        let mut cooled = self.hot.pool().take_idle(self.hot_size, self.cool_after);
+        let count = cooled.len();
+
+        cooled.reverse();
+        let mut cooling = Cooling { pool: self.hot.pool(), entries: cooled };
+        while let Some(entry) = cooling.entries.pop() {
+            let mut moving = Moving { pool: self.hot.pool(), entry: Some(entry) };
+            self.hot.manager().suspend(moving.item()).await;
+            let entry = moving.land();
+
+            let mut cold = self.lock();
+            if cold.closed {
+                drop(cold);
+                self.hot.pool().destroy_slot(entry);
+            } else {
+                cold.items.push(entry);
+            }
+        }
+
+        if count > 0 {
+            event!(cooled = count, "Demoted idle resources to the cold tier");
+        }
+
+        count
+    }
+
+    /// Demote idle resources every `interval`, until the pool is closed
+    pub async fn maintain(self, interval: Duration) {
+        while !self.hot.pool().is_closed() {
+            self.hot.pool().sleep(interval).await;
+            self.cool().await;
+        }
+    }
+
+    /// Close both tiers, dropping the cold resources right away and the hot ones as `AsyncPool::close`
+    ///
+    /// Either way, `Hooks::on_destroy` runs on each of them.
+    pub async fn close(&self) -> Result<(), PoolError> {
+        let cold = {
+            let mut cold = self.lock();
+            cold.closed = true;
+            mem::take(&mut cold.items)
+        };
+
+        for entry in cold {
+            self.hot.pool().destroy_slot(entry);
+        }
+
+        self.hot.pool().close().await
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Cold<M::Resource>> {
+        // The cold tier is only ever pushed to and popped from, so it's safe to use after a panic
+        self.cold.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<T: Send + 'static> Moving<'_, T> {
+    fn item(&mut self) -> &mut T {
+        &mut self.entry.as_mut().expect("Resource dropped while moving between tiers").item
+    }
+
+    /// Take the resource, now that it's made it to the other tier
+    fn land(mut self) -> Entry<T> {
+        self.entry.take().expect("Resource dropped while moving between tiers")
+    }
+}
+
+impl<T: Send + 'static> Drop for Moving<'_, T> {
+    fn drop(&mut self) {
+        if let Some(entry) = self.entry.take() {
+            self.pool.destroy_slot(entry);
+        }
+    }
+}
+
+impl<T: Send + 'static> Drop for Cooling<'_, T> {
+    fn drop(&mut self) {
+        for entry in self.entries.drain(..) {
+            self.pool.restore_slot(entry);
+        }
+    }
+}
+
+impl<M: Tiering> Clone for TieredPool<M> {
+    fn clone(&self) -> Self {
+        Self {
+            hot: self.hot.clone(),
+            cold: self.cold.clone(),
+            hot_size: self.hot_size,
+            cool_after: self.cool_after,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        future,
+        sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    };
+
+    use futures_executor::block_on;
+
+    use crate::Hooks;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct Interpreters {
+        created: AtomicUsize,
+        resumed: AtomicUsize,
+    }
+
+    impl Manager for Interpreters {
+        type Resource = usize;
+        type Error = ();
+
+        async fn create(&self) -> Result<usize, ()> {
+            Ok(self.created.fetch_add(1, Ordering::SeqCst))
+        }
+    }
+
+    impl Tiering for Interpreters {
+        async fn resume(&self, _resource: &mut usize) -> bool {
+            self.resumed.fetch_add(1, Ordering::SeqCst);
+            true
+        }
+    }
+
+    /// Never finishes suspending or resuming while `stalled`
+    #[derive(Default)]
+    struct Stalling {
+        created: AtomicUsize,
+        stalled: AtomicBool,
+    }
+
+    impl Stalling {
+        async fn stall(&self) {
+            if self.stalled.load(Ordering::SeqCst) {
+                future::pending::<()>().await;
+            }
+        }
+    }
+
+    impl Manager for Stalling {
+        type Resource = usize;
+        type Error = ();
+
+        async fn create(&self) -> Result<usize, ()> {
+            Ok(self.created.fetch_add(1, Ordering::SeqCst))
+        }
+    }
+
+    impl Tiering for Stalling {
+        async fn suspend(&self, _resource: &mut usize) {
+            self.stall().await;
+        }
+
+        async fn resume(&self, _resource: &mut usize) -> bool {
+            self.stall().await;
+            true
+        }
+    }
+
+    #[test]
+    fn dropping_a_move_between_tiers_gives_back_its_slots() {
+        block_on(async {
+            let pool = TieredPool::new(Stalling::default(), 3).with_hot_size(0).with_cool_after(Duration::ZERO);
+            let stalled = &pool.hot().manager().stalled;
+
+            drop((pool.rsvp().await.unwrap(), pool.rsvp().await.unwrap(), pool.rsvp().await.unwrap()));
+            assert_eq!(pool.cool().await, 3);
+
+            // The resource being resumed is destroyed
+            stalled.store(true, Ordering::SeqCst);
+            let mut rsvp = Box::pin(pool.rsvp());
+            assert!(futures_util::poll!(rsvp.as_mut()).is_pending());
+            drop(rsvp);
+            assert_eq!((pool.hot().pool().len(), pool.cold()), (2, 2));
+
+            stalled.store(false, Ordering::SeqCst);
+            drop((pool.rsvp().await.unwrap(), pool.rsvp().await.unwrap()));
+            assert_eq!((pool.hot().pool().available(), pool.cold()), (2, 0));
+
+            // The resource being suspended is destroyed, and the one after it goes back
+            stalled.store(true, Ordering::SeqCst);
+            let mut cool = Box::pin(pool.cool());
+            assert!(futures_util::poll!(cool.as_mut()).is_pending());
+            drop(cool);
+            assert_eq!((pool.hot().pool().len(), pool.hot().pool().available(), pool.cold()), (1, 1, 0));
+        });
+    }
+
+    #[test]
+    fn idle_resources_are_demoted_then_resumed_before_creating_more() {
+        block_on(async {
+            let pool = TieredPool::new(Interpreters::default(), 3).with_cool_after(Duration::ZERO);
+
+            drop((pool.rsvp().await.unwrap(), pool.rsvp().await.unwrap()));
+            assert_eq!(pool.cool().await, 1);
+            assert_eq!((pool.try_rsvp().is_some(), pool.try_rsvp().is_some(), pool.cold()), (true, false, 1));
+
+            let _guards = (pool.rsvp().await.unwrap(), pool.rsvp().await.unwrap());
+            let manager = pool.hot().manager();
+            assert_eq!((manager.created.load(Ordering::SeqCst), manager.resumed.load(Ordering::SeqCst)), (2, 1));
+            assert_eq!((pool.hot().pool().len(), pool.cold()), (2, 0));
+        });
+    }
+
+    #[test]
+    fn cold_resources_keep_their_ids_and_are_destroyed_on_close() {
+        block_on(async {
+            let (created, destroyed) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+            let (on_create, on_destroy) = (created.clone(), destroyed.clone());
+            let mut pool = TieredPool::new(Interpreters::default(), 2).with_hot_size(0).with_cool_after(Duration::ZERO);
+            pool.hot = pool.hot.with_hooks(Hooks::new()
+                .on_create(move |_, _| {
+                    on_create.fetch_add(1, Ordering::SeqCst);
+                })
+                .on_destroy(move |_, _| {
+                    on_destroy.fetch_add(1, Ordering::SeqCst);
+                }));
+
+            let guard = pool.rsvp().await.unwrap();
+            let id = guard.id();
+            drop(guard);
+            assert_eq!(pool.cool().await, 1);
+
+            let guard = pool.rsvp().await.unwrap();
+            assert_eq!((guard.id(), created.load(Ordering::SeqCst)), (id, 1));
+            drop(guard);
+
+            assert_eq!(pool.cool().await, 1);
+            pool.close().await.unwrap();
+            assert_eq!((destroyed.load(Ordering::SeqCst), pool.hot().pool().len()), (1, 0));
+        });
+    }
+}
@@ -0,0 +1,9 @@
+//! The clock pools keep time with
+//!
+//! This is `std::time::Instant`, except on `wasm32-unknown-unknown`, where that isn't available and
+//! the browser's clock is used instead, through `web_time::Instant`.
+
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+pub use std::time::Instant;
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+pub use web_time::Instant;
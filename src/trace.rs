@@ -0,0 +1,144 @@
+/// Emit a debug-level `tracing` event with the `tracing` feature, and nothing otherwise
+///
+/// Only use this as a statement, with arguments that are cheap and used elsewhere too.
+macro_rules! event {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::debug!($($arg)*);
+    };
+}
+
+pub(crate) use event;
+
+#[cfg(all(test, feature = "tracing"))]
+mod tests {
+    use std::{
+        collections::BTreeMap,
+        fmt::Debug,
+        sync::{Arc, Mutex},
+    };
+
+    use futures_executor::block_on;
+    use tracing::{
+        field::{Field, Visit},
+        span::{Attributes, Id, Record},
+        Event,
+        Metadata,
+        Subscriber,
+    };
+
+    use crate::AsyncPool;
+
+    /// The fields recorded on a span or event, by name
+    #[derive(Debug, Default, PartialEq)]
+    struct Fields(BTreeMap<&'static str, String>);
+
+    /// A subscriber that keeps every span and event it's given in memory
+    #[derive(Clone, Default)]
+    struct Recorder {
+        spans: Arc<Mutex<Vec<(&'static str, Fields)>>>,
+        events: Arc<Mutex<Vec<Fields>>>,
+    }
+
+    impl Recorder {
+        /// The messages of the events so far
+        fn messages(&self) -> Vec<String> {
+            let events = self.events.lock().unwrap();
+            events.iter().filter_map(|fields| fields.0.get("message").cloned()).collect()
+        }
+    }
+
+    impl Visit for Fields {
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0.insert(field.name(), value.to_owned());
+        }
+
+        fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+            self.0.insert(field.name(), format!("{:?}", value));
+        }
+    }
+
+    impl Subscriber for Recorder {
+        fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            let mut fields = Fields::default();
+            span.record(&mut fields);
+
+            let mut spans = self.spans.lock().unwrap();
+            spans.push((span.metadata().name(), fields));
+            Id::from_u64(spans.len() as u64)
+        }
+
+        fn record(&self, span: &Id, values: &Record<'_>) {
+            values.record(&mut self.spans.lock().unwrap()[span.into_u64() as usize - 1].1);
+        }
+
+        fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            let mut fields = Fields::default();
+            event.record(&mut fields);
+            self.events.lock().unwrap().push(fields);
+        }
+
+        fn enter(&self, _span: &Id) {}
+
+        fn exit(&self, _span: &Id) {}
+    }
+
+    #[test]
+    fn reservations_returns_discards_and_exhaustion_are_traced() {
+        let recorder = Recorder::default();
+
+        tracing::subscriber::with_default(recorder.clone(), || block_on(async {
+            let pool = AsyncPool::new_with(vec![1]).await;
+            let guard = pool.rsvp().await.unwrap();
+
+            let mut waiting = Box::pin(pool.rsvp());
+            assert!(futures_util::poll!(waiting.as_mut()).is_pending());
+            drop(guard);
+            waiting.await.unwrap().discard();
+        }));
+
+        assert_eq!(recorder.messages(), [
+            "Reserving resources",
+            "Reserved resources",
+            "Reserving resources",
+            "Pool exhausted, waiting for resources",
+            "Returned a resource",
+            "Reserved resources",
+            "Discarding a reserved resource",
+            "Took a reserved resource out of the pool",
+        ]);
+    }
+
+    #[cfg(feature = "otel")]
+    #[test]
+    fn reservations_are_recorded_as_acquire_spans() {
+        use std::time::Duration;
+
+        use crate::PoolError;
+
+        let recorder = Recorder::default();
+
+        tracing::subscriber::with_default(recorder.clone(), || block_on(async {
+            let pool = AsyncPool::new_with(vec![1]).await.with_timeout(Duration::from_millis(1));
+            let guard = pool.rsvp_labeled("db").await.unwrap();
+            assert_eq!(pool.rsvp().await.err(), Some(PoolError::Timeout));
+            drop(guard);
+        }));
+
+        let spans = recorder.spans.lock().unwrap();
+        let acquires: Vec<_> = spans.iter().filter(|(name, _)| *name == "async_pool.acquire").collect();
+        assert_eq!(acquires.len(), 2);
+
+        let (reserved, timed_out) = (&(acquires[0].1).0, &(acquires[1].1).0);
+        assert_eq!((reserved["otel.kind"].as_str(), reserved["label"].as_str()), ("internal", "db"));
+        assert!(reserved.contains_key("waited_ms") && !reserved.contains_key("otel.status_code"));
+        assert_eq!(timed_out["otel.status_code"], "ERROR");
+        assert!(timed_out["waited_ms"].parse::<u64>().unwrap() >= 1);
+    }
+}
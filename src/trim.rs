@@ -0,0 +1,109 @@
+use std::{
+    collections::{BinaryHeap, HashMap, HashSet, VecDeque},
+    hash::{BuildHasher, Hash},
+};
+
+/// A collection whose spare capacity can be given back, for `AsyncPool::with_capacity_limit`
+pub trait Trim {
+    /// The number of elements it has room for without reallocating
+    fn capacity(&self) -> usize;
+
+    /// Shrink its capacity to `capacity`, or to its length if that's larger
+    fn shrink_to(&mut self, capacity: usize);
+}
+
+impl<T> Trim for Vec<T> {
+    fn capacity(&self) -> usize {
+        Vec::capacity(self)
+    }
+
+    fn shrink_to(&mut self, capacity: usize) {
+        Vec::shrink_to(self, capacity)
+    }
+}
+
+impl Trim for String {
+    fn capacity(&self) -> usize {
+        String::capacity(self)
+    }
+
+    fn shrink_to(&mut self, capacity: usize) {
+        String::shrink_to(self, capacity)
+    }
+}
+
+impl<T> Trim for VecDeque<T> {
+    fn capacity(&self) -> usize {
+        VecDeque::capacity(self)
+    }
+
+    fn shrink_to(&mut self, capacity: usize) {
+        VecDeque::shrink_to(self, capacity)
+    }
+}
+
+impl<T: Ord> Trim for BinaryHeap<T> {
+    fn capacity(&self) -> usize {
+        BinaryHeap::capacity(self)
+    }
+
+    fn shrink_to(&mut self, capacity: usize) {
+        BinaryHeap::shrink_to(self, capacity)
+    }
+}
+
+impl<K: Eq + Hash, V, S: BuildHasher> Trim for HashMap<K, V, S> {
+    fn capacity(&self) -> usize {
+        HashMap::capacity(self)
+    }
+
+    fn shrink_to(&mut self, capacity: usize) {
+        HashMap::shrink_to(self, capacity)
+    }
+}
+
+impl<T: Eq + Hash, S: BuildHasher> Trim for HashSet<T, S> {
+    fn capacity(&self) -> usize {
+        HashSet::capacity(self)
+    }
+
+    fn shrink_to(&mut self, capacity: usize) {
+        HashSet::shrink_to(self, capacity)
+    }
+}
+
+/// Give back the capacity of `item` past `max_capacity`
+pub(crate) fn trim<T: Trim>(item: &mut T, max_capacity: usize) {
+    if item.capacity() > max_capacity {
+        item.shrink_to(max_capacity);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_executor::block_on;
+
+    use crate::{AsyncPool, BufPool};
+
+    #[test]
+    fn returned_collections_give_back_capacity_past_the_limit() {
+        block_on(async {
+            let pool = AsyncPool::new_with(vec![String::new()]).await
+                .with_on_return(String::clear)
+                .with_capacity_limit(64);
+
+            pool.rsvp().await.unwrap().push_str(&"x".repeat(4096));
+            let name = pool.rsvp().await.unwrap();
+            assert!(name.is_empty() && name.capacity() <= 64);
+            drop(name);
+
+            let bufs = BufPool::new().with_max_capacity(64);
+            bufs.rsvp(16).await.unwrap().extend_from_slice(&[0; 16]);
+            assert!(bufs.rsvp(0).await.unwrap().capacity() >= 16);
+
+            drop(bufs.rsvp(1 << 20).await.unwrap());
+            let buf = bufs.rsvp(0).await.unwrap();
+            assert!(buf.is_empty() && buf.capacity() <= 64);
+        });
+    }
+}
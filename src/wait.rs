@@ -0,0 +1,283 @@
+use std::{
+    cmp::Reverse,
+    collections::BTreeSet,
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll, Waker},
+};
+
+use crate::{
+    pool::{Entry, Shared},
+    time::Instant,
+    trace::event,
+    PoolError, PoolEvent,
+};
+
+/// A future waiting its turn for one or more resources
+///
+/// Returned resources are handed straight to the first `Wait` in the queue. If it's dropped after
+/// being handed resources, they go to the next waiter instead of being lost.
+pub(crate) struct Wait<'a, T: Send + 'static> {
+    shared: &'a Arc<Shared<T>>,
+    priority: u8,
+    /// How many resources to wait for.
+    wanted: usize,
+    /// Whether a free slot will do instead of a resource, for the caller to create one in.
+    claims_slots: bool,
+    /// Whether only a free slot will do, for the caller to add a resource in.
+    only_slots: bool,
+    /// The affinity key to prefer a resource by, and to mark the granted one with.
+    affinity: Option<u64>,
+    /// What a resource needs to be handed to this waiter.
+    filter: Filter,
+    /// What the caller called this wait, for diagnostics.
+    pub(crate) label: Option<&'static str>,
+    /// This waiter's place in the queue, once it's waiting.
+    key: Option<WaiterKey>,
+}
+
+/// A waiter's place in the queue: by highest priority, then by longest waiting
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) struct WaiterKey {
+    pub(crate) priority: Reverse<u8>,
+    pub(crate) ticket: u64,
+}
+
+/// A queued waiter, as the pool sees it
+pub(crate) struct Waiter<T> {
+    pub(crate) waker: Waker,
+    /// How many resources it's waiting for.
+    pub(crate) wanted: usize,
+    /// Whether it's woken to claim a slot when one frees up.
+    pub(crate) claims_slots: bool,
+    /// Whether it's never handed resources, only slots.
+    pub(crate) only_slots: bool,
+    /// What a resource needs to be handed to it.
+    pub(crate) filter: Filter,
+    /// What the caller called it, for diagnostics.
+    pub(crate) label: Option<&'static str>,
+    /// When it started waiting.
+    pub(crate) since: Instant,
+    /// What it has been handed so far, while waiting for the rest of a batch.
+    pub(crate) held: Vec<Entry<T>>,
+}
+
+/// What a waiter needs of a resource, for it to be handed over
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Filter {
+    /// The tags the resource needs every one of.
+    pub(crate) tags: BTreeSet<String>,
+    /// The least weight the resource needs.
+    pub(crate) weight: usize,
+}
+
+/// The resources handed to a waiter, ready to be picked up
+pub(crate) enum Grant<T> {
+    One(Entry<T>),
+    Many(Vec<Entry<T>>),
+    /// A slot claimed for the waiter to create a resource in
+    Slot,
+}
+
+impl<'a, T: Send + 'static> Wait<'a, T> {
+    pub(crate) fn new(shared: &'a Arc<Shared<T>>, priority: u8) -> Self {
+        Self::many(shared, priority, 1)
+    }
+
+    /// Wait for `wanted` resources, handed out all at once
+    pub(crate) fn many(shared: &'a Arc<Shared<T>>, priority: u8, wanted: usize) -> Self {
+        Self {
+            shared,
+            priority,
+            wanted,
+            claims_slots: false,
+            only_slots: false,
+            affinity: None,
+            filter: Filter::default(),
+            label: None,
+            key: None,
+        }
+    }
+
+    /// Wait for a resource, preferring an idle one last reserved with `affinity`
+    pub(crate) fn affine(shared: &'a Arc<Shared<T>>, affinity: u64) -> Self {
+        let mut wait = Self::new(shared, 0);
+        wait.affinity = Some(affinity);
+        wait
+    }
+
+    /// Wait for a resource that passes `filter`
+    pub(crate) fn matching(shared: &'a Arc<Shared<T>>, filter: Filter) -> Self {
+        let mut wait = Self::new(shared, 0);
+        wait.filter = filter;
+        wait
+    }
+
+    /// Name the wait, for diagnostics
+    pub(crate) fn labeled(mut self, label: &'static str) -> Self {
+        self.label = Some(label);
+        self
+    }
+
+    /// Wait for a resource, or for room to create one
+    pub(crate) fn claiming(shared: &'a Arc<Shared<T>>, priority: u8) -> Self {
+        let mut wait = Self::new(shared, priority);
+        wait.claims_slots = true;
+        wait
+    }
+
+    /// Wait for room to add a resource, and nothing else
+    pub(crate) fn slot(shared: &'a Arc<Shared<T>>) -> Self {
+        let mut wait = Self::claiming(shared, 0);
+        wait.only_slots = true;
+        wait
+    }
+}
+
+impl<T: Send + 'static> Future for Wait<'_, T> {
+    type Output = Result<Grant<T>, PoolError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let shared = self.shared;
+
+        if self.key.is_none() {
+            shared.evict_stale();
+        }
+
+        let mut state = shared.lock()?;
+        let found = match (self.key, self.wanted) {
+            (None, 1) if !self.only_slots => state.find_idle(&self.filter, self.affinity),
+            _ => None,
+        };
+
+        let mut grant = match self.key {
+            Some(key) => match state.granted.remove(&key.ticket) {
+                Some(grant) => grant,
+                None if state.closed => {
+                    self.key = None;
+                    return Poll::Ready(Err(PoolError::Closed));
+                }
+                None if self.claims_slots && state.claim_slot() => {
+                    state.dequeue(&key);
+                    Grant::Slot
+                }
+                None => {
+                    if let Some(waiter) = state.waiters.get_mut(&key) {
+                        waiter.waker.clone_from(cx.waker());
+                    }
+                    return Poll::Pending;
+                }
+            },
+            None if state.closed => return Poll::Ready(Err(PoolError::Closed)),
+            None if found.is_some() => {
+                let at = found.expect("Idle resources were just checked");
+                Grant::One(state.idle.remove(at).expect("Idle resources were just checked").entry)
+            }
+            None if self.filter.is_any() && !self.only_slots && self.wanted <= state.idle.len() => {
+                let wanted = self.wanted;
+                Grant::Many(state.idle.drain(..wanted).map(|idle| idle.entry).collect())
+            }
+            None if self.claims_slots && state.claim_slot() => Grant::Slot,
+            None => {
+                // Idle resources would already have gone to any waiter they matched, so an unfiltered
+                // waiter is first in line for them and can hold them
+                let held = match self.filter.is_any() && !self.only_slots {
+                    true => state.idle.drain(..).map(|idle| idle.entry).collect(),
+                    false => Vec::new(),
+                };
+                let waiter = Waiter {
+                    waker: cx.waker().clone(),
+                    wanted: self.wanted,
+                    claims_slots: self.claims_slots,
+                    only_slots: self.only_slots,
+                    filter: self.filter.clone(),
+                    label: self.label,
+                    since: Instant::now(),
+                    held,
+                };
+                self.key = Some(state.enqueue(self.priority, waiter));
+
+                // Resources parked on other threads can only reach a waiter once it's queued
+                state.unpark_all();
+
+                event!(waiting = state.waiters.len(), "Pool exhausted, waiting for resources");
+                state.events.emit(PoolEvent::Exhausted { waiting: state.waiters.len() });
+                return Poll::Pending;
+            }
+        };
+
+        self.key = None;
+
+        match &mut grant {
+            Grant::One(entry) => {
+                entry.affinity = self.affinity;
+                state.reserve_labeled(entry, self.label);
+            }
+            Grant::Many(entries) => entries.iter_mut().for_each(|entry| state.reserve_labeled(entry, self.label)),
+            Grant::Slot => {}
+        }
+
+        Poll::Ready(Ok(grant))
+    }
+}
+
+impl<T: Send + 'static> Drop for Wait<'_, T> {
+    fn drop(&mut self) {
+        if let Some(key) = self.key.take() {
+            let mut state = self.shared.lock_recovered();
+
+            // Pass on whatever we were handed but gave up before picking up
+            let entries = match (state.dequeue(&key), state.granted.remove(&key.ticket)) {
+                (Some(waiter), _) => waiter.held,
+                (None, Some(Grant::One(entry))) => vec![entry],
+                (None, Some(Grant::Many(entries))) => entries,
+                (None, Some(Grant::Slot)) | (None, None) => Vec::new(),
+            };
+
+            for entry in entries {
+                state.requeue(entry);
+            }
+        }
+    }
+}
+
+impl Filter {
+    /// Whether every resource passes
+    pub(crate) fn is_any(&self) -> bool {
+        self.tags.is_empty() && self.weight == 0
+    }
+
+    pub(crate) fn matches<T>(&self, entry: &Entry<T>) -> bool {
+        entry.weight >= self.weight && self.tags.is_subset(&entry.tags)
+    }
+}
+
+impl<T> Grant<T> {
+    #[cfg(feature = "tracing")]
+    pub(crate) fn len(&self) -> usize {
+        match self {
+            Grant::One(_) => 1,
+            Grant::Many(items) => items.len(),
+            Grant::Slot => 0,
+        }
+    }
+
+    /// The granted resource, for a wait on just one
+    pub(crate) fn into_one(self) -> Entry<T> {
+        match self {
+            Grant::One(entry) => entry,
+            Grant::Many(mut entries) => entries.pop().expect("Waited for at least one resource"),
+            Grant::Slot => unreachable!("Only claiming waits are granted slots"),
+        }
+    }
+
+    /// The granted resources, for a wait on a batch
+    pub(crate) fn into_many(self) -> Vec<Entry<T>> {
+        match self {
+            Grant::One(entry) => vec![entry],
+            Grant::Many(entries) => entries,
+            Grant::Slot => Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use alloc::collections::VecDeque;
+use core::task::{Context, Poll, Waker};
+
+/// The wakers of the waiting reservations of a pool without priorities, by ticket, in the order they
+/// started waiting
+///
+/// Shared by `LocalAsyncPool` and `ArrayPool`, whose futures only differ in what they take from their
+/// pool once it's their turn.
+pub(crate) struct Waiters {
+    queue: VecDeque<(u64, Waker)>,
+    next_ticket: u64,
+}
+
+impl Waiters {
+    /// Create an empty queue, with room for `capacity` waiters before it allocates again
+    pub(crate) fn with_capacity(capacity: usize) -> Self {
+        Self { queue: VecDeque::with_capacity(capacity), next_ticket: 0 }
+    }
+
+    /// The number of waiters
+    pub(crate) fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Poll the waiter holding `ticket`, given the resource it can take right now, if any
+    ///
+    /// A waiter that gets its resource leaves the queue and gives up its ticket. One that doesn't is
+    /// queued, or has its waker updated if it's already waiting.
+    pub(crate) fn poll<R>(&mut self, ticket: &mut Option<u64>, cx: &mut Context<'_>, ready: Option<R>) -> Poll<R> {
+        let queued = ticket.and_then(|ticket| self.position(ticket));
+
+        if let Some(ready) = ready {
+            if let Some(at) = queued {
+                self.queue.remove(at);
+            }
+            *ticket = None;
+
+            return Poll::Ready(ready);
+        }
+
+        match queued {
+            Some(at) => self.queue[at].1.clone_from(cx.waker()),
+            None => {
+                let next = self.next_ticket;
+                self.next_ticket += 1;
+                self.queue.push_back((next, cx.waker().clone()));
+                *ticket = Some(next);
+            }
+        }
+
+        Poll::Pending
+    }
+
+    /// Take the waiter holding `ticket` out of the queue, as its future is dropped
+    ///
+    /// A waiter that was already woken has left the queue, so if a resource is `available` for it,
+    /// the wake goes to the next waiter instead.
+    pub(crate) fn cancel(&mut self, ticket: Option<u64>, available: bool) {
+        let ticket = match ticket {
+            Some(ticket) => ticket,
+            None => return,
+        };
+
+        match self.position(ticket) {
+            Some(at) => drop(self.queue.remove(at)),
+            None if available => self.wake_next(),
+            None => {}
+        }
+    }
+
+    /// Wake the first waiter, as a resource was returned
+    pub(crate) fn wake_next(&mut self) {
+        if let Some((_, waker)) = self.queue.pop_front() {
+            waker.wake();
+        }
+    }
+
+    fn position(&self, ticket: u64) -> Option<usize> {
+        self.queue.iter().position(|(t, _)| *t == ticket)
+    }
+}
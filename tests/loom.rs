@@ -0,0 +1,37 @@
+//! Model checks of the pool's synchronization, run with
+//! `RUSTFLAGS="--cfg loom" cargo test --test loom --release`
+#![cfg(loom)]
+
+use async_pool::AsyncPool;
+use loom::{future::block_on, thread};
+
+#[test]
+fn return_racing_a_cancelled_rsvp_isnt_lost() {
+    loom::model(|| {
+        let pool: AsyncPool<u8> = Some(1).into_iter().collect();
+        let guard = pool.try_rsvp().unwrap();
+
+        let returner = thread::spawn(move || drop(guard));
+        block_on(async {
+            let mut rsvp = Box::pin(pool.rsvp());
+            let _ = futures_util::poll!(rsvp.as_mut());
+        });
+        returner.join().unwrap();
+
+        assert_eq!(pool.available(), 1);
+    });
+}
+
+#[test]
+fn concurrent_rsvps_share_one_resource() {
+    loom::model(|| {
+        let pool: AsyncPool<u8> = Some(1).into_iter().collect();
+        let other = pool.clone();
+
+        let borrower = thread::spawn(move || drop(block_on(other.rsvp()).unwrap()));
+        drop(block_on(pool.rsvp()).unwrap());
+        borrower.join().unwrap();
+
+        assert_eq!((pool.available(), pool.in_use()), (1, 0));
+    });
+}